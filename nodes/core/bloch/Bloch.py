@@ -1,7 +1,7 @@
 import numpy as np
-from gpi import port, ui
+from gpi import optional, port, ui
 
-from Spin import N
+from .Spin import N
 
 
 T1_ms = 40
@@ -13,8 +13,8 @@ def config():
     class out:
         inputs = {
             "a": port.ArrayReal,
-            "b": port.ArrayReal,
-            "c": port.ArrayReal,
+            "b": optional(port.ArrayReal, default=np.zeros((N, N, 1))),
+            "c": optional(port.ArrayReal, default=np.zeros((N, N, 1, 3))),
         }
         outputs = {
             "out": port.ArrayReal,
@@ -27,18 +27,18 @@ def config():
         # outputs = {
         #     "out": "VectorField3d",
         # }
-        parameters = {"d": ui.Slider}
+        parameters = {"d": ui.spinner(default=100, min=0, max=200, units="µs")}
+        cacheable = True
 
     return out
 
 
 def compute(input, parameters):
     m = input["a"]
-    g = input.get("b", np.zeros((N, N, 1)))
-    rf = input.get("c", np.zeros((N, N, 1, 3)))
+    g = input["b"]
+    rf = input["c"]
     rf = rf / np.sin(rf)
-    n = float(parameters["d"])
-    n = int((n + 1) * 100)
+    n = parameters["d"]
 
     gamma = 42.577
     usec = 0.001
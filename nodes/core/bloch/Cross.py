@@ -10,6 +10,7 @@ def config():
         }
         outputs = {"out": port.ArrayReal}
         parameters = {}
+        cacheable = True
 
     return out
 
@@ -2,7 +2,7 @@
 # import numpy as np
 import numpy as np
 from gpi import port, ui
-from Spin import N
+from .Spin import N
 
 
 def config():
@@ -14,14 +14,15 @@ def config():
             "Y": ui.Slider,
             "Z": ui.Slider,
         }
+        cacheable = True
 
     return out
 
 
 def compute(_, parameters):
-    gx = float(parameters["X"])
-    gy = float(parameters["Y"])
-    gz = float(parameters["Z"])
+    gx = parameters["X"]
+    gy = parameters["Y"]
+    gz = parameters["Z"]
     x = np.linspace(0, gx, N)
     y = np.linspace(0, gy, N)
     X, Y, Z = np.meshgrid(x, y, np.array([gz]))
@@ -13,6 +13,7 @@ def config():
             "y": ui.Slider,
             "z": ui.Slider,
         }
+        cacheable = True
 
     return out
 
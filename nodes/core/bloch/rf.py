@@ -1,6 +1,6 @@
 import numpy as np
 from gpi import port, ui
-from Spin import N
+from .Spin import N
 
 
 def config():
@@ -11,6 +11,7 @@ def config():
             "x": ui.Slider,
             "y": ui.Slider,
         }
+        cacheable = True
 
     return out
 
@@ -7,6 +7,7 @@ def config():
         inputs = {"radius": port.Real}
         outputs = {"out": port.ArrayReal}
         parameters = {}
+        cacheable = True
 
     return out
 
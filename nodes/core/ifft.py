@@ -7,6 +7,7 @@ def config():
         inputs = {"a": port.ArrayComplex}
         outputs = {"out": port.ArrayComplex}
         parameters = {}
+        cacheable = True
 
     return out
 
@@ -1,20 +1,21 @@
 import numpy as np
 from PIL import Image
 
-from gpi import node, port
+from gpi import node, port, ui
 
 
 def config():
     return node(
         {},
-        {"out": port.ArrayReal},
+        {"out": port.ArrayUnsigned8},
+        {"path": ui.file("nodes/core/data/slogan.png", ["png", "jpg", "jpeg"])},
     )
 
 
-def compute(input, _):
-    img = Image.open("nodes/core/data/slogan.png")
+def compute(input, parameters):
+    img = Image.open(parameters["path"])
 
     # just take blue channel for simplicity
-    np_img = np.array(img)[:, :, 0] / 255.0
+    np_img = np.array(img)[:, :, 0]
 
     return {"out": np_img}
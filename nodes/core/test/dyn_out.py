@@ -7,13 +7,13 @@ def config():
         inputs = {}
         outputs = {"out": port.Dynamic}
         parameters = {
-            "X": ui.Slider,
+            "X": ui.spinner(min=0, max=20),
         }
 
     return out
 
 
 def compute(_, parameters):
-    x = max(0, min(20, round(float(parameters["X"]) * 100.0)))
+    x = parameters["X"]
 
     return {"out": np.zeros(tuple(3 for _ in range(0, x)))}
@@ -1,22 +1,112 @@
-from dataclasses import dataclass, astuple
+from dataclasses import dataclass, astuple, field
 from enum import Enum
-from typing import Dict, Optional, TypeVar
+from typing import Any, Dict, List, Optional, TypeVar
 
 
 class port(str, Enum):
     Integer = "Integer"
     Real = "Real"
     Complex = "Complex"
-    ArrayComplex = "ArrayComplex"
+    ArrayInteger = "ArrayInteger"
     ArrayReal = "ArrayReal"
+    ArrayComplex = "ArrayComplex"
+    # arrays of other numpy dtypes
+    ArrayBool = "ArrayBool"  # bool
+    ArrayUnsigned8 = "ArrayUnsigned8"  # uint8
+    ArrayUnsigned16 = "ArrayUnsigned16"  # uint16
+    ArrayInteger32 = "ArrayInteger32"  # int32
+    ArrayReal32 = "ArrayReal32"  # float32
+    ArrayComplex32 = "ArrayComplex32"  # complex64
     Dynamic = "Dynamic"
     # Object = "Object" just nest dictionary definititions!
 
 
 class ui:
+    """Parameter widgets. `parameters = {"d": ui.Slider}` uses a widget's default settings, while
+    `parameters = {"d": ui.slider(min=0, max=10, default=5, units="ms")}` sets them. The value of
+    each parameter is passed to compute as a python value of the widget's type"""
+
     Slider = "Slider"
     NumberField = "NumberField"
+    Spinner = "Spinner"
+    Text = "Text"
+    File = "File"
     CheckBox = "CheckBox"
+    Vector = "Vector"
+
+    @dataclass
+    class slider:
+        """A float between `min` and `max`"""
+
+        default: float = 0.0
+        min: float = -1.0
+        max: float = 1.0
+        step: float = 0.01
+        units: str = ""
+
+    @dataclass
+    class number:
+        """A float typed in, optionally limited to `min` and `max`"""
+
+        default: float = 0.0
+        min: Optional[float] = None
+        max: Optional[float] = None
+        step: Optional[float] = None
+        units: str = ""
+
+    @dataclass
+    class spinner:
+        """An int, stepped up and down by `step`"""
+
+        default: int = 0
+        min: Optional[int] = None
+        max: Optional[int] = None
+        step: int = 1
+        units: str = ""
+
+    @dataclass
+    class dropdown:
+        """One of `options`, a str. The first option unless `default` is given"""
+
+        options: List[str]
+        default: Optional[str] = None
+
+    @dataclass
+    class text:
+        """A str"""
+
+        default: str = ""
+
+    @dataclass
+    class file:
+        """A pathlib.Path, picked from files with `extensions` (without the dot)"""
+
+        default: str = ""
+        extensions: List[str] = field(default_factory=list)
+
+    @dataclass
+    class checkbox:
+        """A bool"""
+
+        default: bool = False
+
+    @dataclass
+    class vector:
+        """A list of floats, as long as `default`"""
+
+        default: List[float] = field(default_factory=lambda: [0.0, 0.0])
+        min: Optional[float] = None
+        max: Optional[float] = None
+        step: Optional[float] = None
+        units: str = ""
+
+
+@dataclass
+class optional:
+    """An input that doesn't need to be connected. When it isn't, `default` is passed to compute in its place, or the input is left out if there is no default"""
+
+    port: Any
+    default: Any = None
 
 
 T = TypeVar("T")
@@ -26,14 +116,18 @@ T = TypeVar("T")
 class node:
     inputs: Optional[Dict[str, port]]
     outputs: Optional[Dict[str, port]]
-    paramaters: Optional[Dict[str, str]]
+    paramaters: Optional[Dict[str, Any]]
+    cacheable: bool
 
     def __init__(
         self,
         inputs: Optional[Dict[str, port]] = {},
         outputs: Optional[Dict[str, port]] = {},
-        parameters: Optional[Dict[str, str]] = {},
+        parameters: Optional[Dict[str, Any]] = {},
+        cacheable: bool = False,
     ):
+        # Outputs only depend on inputs and parameters, so they can be reused
+        self.cacheable = cacheable
         if inputs is None:
             self.inputs = {}
         else:
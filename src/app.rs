@@ -1,22 +1,25 @@
 use crate::config::Config;
 use crate::file_watch::file_watch_subscription;
-use crate::graph::{Graph, PortRef, IO};
+use crate::graph::scheduler::{Completion, ComputeJob, ComputeOutput, Dispatched, Executor};
+use crate::graph::{PortRef, IO};
+use crate::gui_node::GUINode;
 use crate::interface::add_node::add_node_tree_panel;
-use crate::interface::node_config::NodeUIWidget;
+use crate::interface::node_config::{NodeUIWidget, ParameterEdit};
 use crate::interface::theme_config::{AppThemeMessage, GuiColorMessage};
 use crate::interface::{side_bar::side_bar, SEPERATOR};
 use crate::math::{Point, Vector};
-use crate::network::Network;
-use crate::nodes::port::PortData;
-use crate::nodes::status::{NodeError, NodeStatus};
-use crate::nodes::{NodeData, NodeTemplate, RustNode};
-use crate::project::Project;
-use crate::python::py_node::PyNode;
+use crate::network::history::Edit;
+use crate::network::{resolve_py_paths, Network};
+use crate::nodes::port::{PortData, PortType};
+use crate::nodes::status::NodeStatus;
+use crate::nodes::{NodeData, NodeTemplate};
+use crate::overrides::{OverrideError, ParameterOverride};
+use crate::project::{macro_path, Project, MACRO_DIR};
+use crate::python::{module_cache, py_node::PyNode};
 use crate::style::theme::AppTheme;
 use crate::user_data::UserData;
 use crate::widget::shapes::ShapeId;
 use crate::widget::workspace::workspace;
-use crate::StableMap;
 
 use iced::advanced::graphics::core::Element;
 use iced::event::listen_with;
@@ -27,12 +30,11 @@ use iced::widget::{column, *};
 use iced::Event::Keyboard;
 use iced::Length::Fill;
 use iced::{mouse, window, Subscription, Task};
-use itertools::Itertools;
 use log::{error, info, trace, warn};
+use pyo3::Python;
 use rfd::FileDialog;
-use std::fs::read_to_string;
-use std::iter::once;
-use std::mem::discriminant;
+use std::env;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
 #[derive(Default, Clone, PartialEq)]
@@ -64,11 +66,21 @@ pub struct App {
     /// Currently held keyboard modifiers, used for shortcuts
     pub modifiers: Modifiers,
 
+    /// The rust node parameter last edited, see `ParameterEdit`
+    pub parameter_edit: Option<ParameterEdit>,
+
     pub debug: bool,
     pub show_palette_ui: bool,
 }
 impl Default for App {
     fn default() -> Self {
+        Self::new(&[]).expect("no overrides to fail")
+    }
+}
+
+impl App {
+    /// Load the most recent network, and apply any parameter overrides to it
+    pub fn new(overrides: &[ParameterOverride]) -> Result<Self, Vec<OverrideError>> {
         let config = Config::read_config();
         config.setup_environment();
         let projects = config.read_projects();
@@ -76,12 +88,19 @@ impl Default for App {
         let app_theme = Config::load_theme();
         let user_data = UserData::read_user_data();
 
-        let network = match user_data.get_recent_network_file() {
+        let mut network = match user_data.get_recent_network_file() {
             Some(recent_network) => Network::load_network(recent_network, &projects),
             None => Network::default(),
         };
+        if config.cache_outputs() {
+            network.restore_outputs();
+        }
+        network.apply_overrides(overrides)?;
+        network
+            .history
+            .configure(config.history(), network.file.as_deref());
 
-        App {
+        Ok(App {
             network,
             config,
 
@@ -91,9 +110,10 @@ impl Default for App {
             action: Default::default(),
             app_theme,
             modifiers: Default::default(),
+            parameter_edit: None,
             python_projects: projects,
             user_data,
-        }
+        })
     }
 }
 
@@ -119,14 +139,19 @@ pub enum Message {
 
     UpdateNodeTemplate(u32, NodeTemplate),
     UpdateNodeParameter(u32, String, NodeUIWidget),
+    /// Choose the path of a file parameter with a file dialog
+    PickParameterFile(u32, String),
     DeleteSelectedNodes,
+    GroupSelectedNodes,
+    OpenGroup(u32),
+    CloseGroup,
+    SaveGroupMacro(u32),
 
     QueueCompute(u32),
-    ComputeComplete(
-        u32,
-        #[debug(skip)] Result<(StableMap<String, PortData>, NodeData), NodeError>,
-    ),
+    ComputeComplete(#[debug(skip)] ComputeOutput<NodeData, PortData>),
+    CancelCompute(u32),
     ComputeAll,
+    TogglePause,
 
     //// Application
     AnimationTick,
@@ -148,6 +173,13 @@ pub enum Message {
     //// History
     Undo,
     Redo,
+
+    //// Variants
+    ForkVariant,
+    SwitchVariant(String),
+    CompareVariant(Option<String>),
+    ApplyVariant(String),
+    DeleteVariant(String),
     //// Misc
     /// Hacky way to have a message that does nothing
     NOP,
@@ -193,15 +225,11 @@ impl App {
 
             //// Port
             Message::PortStartHover(hover_port) => match &self.action {
-                Action::CreatingInputWire(input, _) => {
-                    if *input != hover_port {
-                        self.action = Action::CreatingInputWire(input.clone(), Some(hover_port))
-                    }
+                Action::CreatingInputWire(input, _) if *input != hover_port => {
+                    self.action = Action::CreatingInputWire(input.clone(), Some(hover_port))
                 }
-                Action::CreatingOutputWire(output, _) => {
-                    if *output != hover_port {
-                        self.action = Action::CreatingOutputWire(output.clone(), Some(hover_port))
-                    }
+                Action::CreatingOutputWire(output, _) if *output != hover_port => {
+                    self.action = Action::CreatingOutputWire(output.clone(), Some(hover_port))
                 }
                 _ => {}
             },
@@ -222,8 +250,13 @@ impl App {
                 let task = match &self.action.clone() {
                     Action::CreatingInputWire(input, Some(output))
                     | Action::CreatingOutputWire(output, Some(input)) => {
-                        self.network.add_edge(input, output);
-                        Task::done(Message::QueueCompute(output.node))
+                        match self.network.add_edge(input, output) {
+                            Ok(()) => self.queue_compute(output.node),
+                            Err(e) => {
+                                warn!("Connection rejected: {e}");
+                                Task::none()
+                            }
+                        }
                     }
                     _ => Task::none(),
                 };
@@ -242,7 +275,7 @@ impl App {
                     self.action =
                         self.network
                             .select_node(nx, self.modifiers, self.cursor_position);
-                    return Task::done(Message::QueueCompute(nx));
+                    return self.queue_compute(nx);
                 } else
                 //// Clicked on the canvas background
                 {
@@ -255,37 +288,105 @@ impl App {
                     );
                 }
             }
-            Message::OnCanvasUp => {
-                // TODO: push undo stack if shape has moved
-                match self.action {
-                    Action::DragNode(..) => self.action = Action::Idle,
-                    Action::DragPan(_) => self.action = Action::Idle,
-                    _ => (),
+            Message::OnCanvasUp => match self.action {
+                Action::DragNode(..) => {
+                    self.network.end_drag();
+                    self.action = Action::Idle
                 }
-            }
+                Action::DragPan(_) => self.action = Action::Idle,
+                _ => (),
+            },
 
             Message::UpdateNodeTemplate(id, new_template) => {
                 //TODO: move into Network
-                let old_template = &self.network.graph.get_node(id).template;
-                if *old_template != new_template {
-                    self.network.stash_state();
+                let Some(node) = self.network.graph.get_node(id) else {
+                    warn!("Can't update template of deleted node #{id}");
+                    return Task::none();
+                };
+                if node.template != new_template {
+                    self.network
+                        .stash_state(Edit::ChangeNode(id, node.template.name()));
                     // Now we can aquire mutable reference
-                    let old_template = &mut self.network.graph.get_mut_node(id).template;
-                    *old_template = new_template;
-                    return Task::done(Message::QueueCompute(id));
+                    if let Some(node) = self.network.graph.get_mut_node(id) {
+                        node.template = new_template;
+                    }
+                    self.prune_edges(id);
+                    return self.queue_compute(id);
                 };
             }
             Message::UpdateNodeParameter(id, name, updated_widget) => {
                 //TODO: move into Network
-                self.network.stash_state();
-                let old_template = &mut self.network.graph.get_mut_node(id).template;
-                // TODO: support all node types, not just py_node
-                if let NodeTemplate::PyNode(node) = old_template {
-                    node.parameters
-                        .as_mut()
-                        .expect("parameters must exist if they are being edited")
-                        .insert(name, updated_widget);
-                    return Task::done(Message::QueueCompute(id));
+                let Some(node) = self.network.graph.get_node(id) else {
+                    warn!("Can't update parameter of deleted node #{id}");
+                    return Task::none();
+                };
+                let new_template = match &node.template {
+                    NodeTemplate::PyNode(node) => {
+                        let mut node = node.clone();
+                        node.parameters
+                            .as_mut()
+                            .expect("parameters must exist if they are being edited")
+                            .insert(name.clone(), updated_widget.clone());
+                        NodeTemplate::PyNode(node)
+                    }
+                    NodeTemplate::RustNode(node) => {
+                        let mut node = node.clone();
+                        if let Err(e) = node.update_parameter(&name, &updated_widget) {
+                            warn!("Can't update parameter '{name}' of node #{id}: {e}");
+                            return Task::none();
+                        }
+                        self.parameter_edit = Some(ParameterEdit {
+                            node: id,
+                            name: name.clone(),
+                            widget: updated_widget.clone(),
+                        });
+                        NodeTemplate::RustNode(node)
+                    }
+                    NodeTemplate::Group(_) => return Task::none(),
+                };
+                self.network.stash_state(Edit::ChangeParameter {
+                    nx: id,
+                    name,
+                    widget: updated_widget.kind().to_string(),
+                });
+                if let Some(node) = self.network.graph.get_mut_node(id) {
+                    node.template = new_template;
+                }
+                // e.g. removing a variable from an expression removes its input
+                self.prune_edges(id);
+                return self.queue_compute(id);
+            }
+            Message::PickParameterFile(id, name) => {
+                let widget = match self.network.graph.get_node(id).map(|n| &n.template) {
+                    Some(NodeTemplate::PyNode(node)) => node
+                        .parameters
+                        .as_ref()
+                        .ok()
+                        .and_then(|parameters| parameters.get(&name))
+                        .cloned(),
+                    Some(NodeTemplate::RustNode(node)) => node.parameters().remove(&name),
+                    _ => None,
+                };
+                if let Some(NodeUIWidget::File { path, extensions }) = widget {
+                    let mut dialog = FileDialog::new();
+                    if !extensions.is_empty() {
+                        dialog = dialog.add_filter(&name, &extensions);
+                    }
+                    if let Some(dir) = Path::new(&path).parent().filter(|dir| dir.is_dir()) {
+                        dialog = dialog.set_directory(dir);
+                    }
+                    if let Some(file) = dialog.pick_file() {
+                        // Paths within the working directory are kept relative, like typed ones
+                        let file = env::current_dir()
+                            .ok()
+                            .and_then(|dir| file.strip_prefix(dir).ok().map(Path::to_path_buf))
+                            .unwrap_or(file);
+                        let widget = NodeUIWidget::File {
+                            path: file.to_string_lossy().into_owned(),
+                            extensions: extensions.clone(),
+                        };
+                        return Task::done(Message::UpdateNodeParameter(id, name, widget));
+                    }
                 }
             }
             Message::OpenAddNodeUi => self.action = Action::AddingNode,
@@ -305,9 +406,13 @@ impl App {
                     "should not be able to select a nope group while Add Node UI is not active"
                 ),
             },
-            Message::AddNode(template) => {
+            Message::AddNode(mut template) => {
                 //TODO: move into Network
-                self.network.stash_state();
+                if let NodeTemplate::Group(group) = &mut template {
+                    // Macros are saved without the location of their python nodes
+                    resolve_py_paths(&mut group.graph, &self.python_projects);
+                }
+                self.network.stash_state(Edit::AddNode(template.name()));
                 let id = self.network.graph.node(template.into());
                 self.network.selected_shapes = [id].into();
                 self.network.shapes.shape_positions.insert_before(
@@ -320,14 +425,74 @@ impl App {
             Message::DeleteSelectedNodes => {
                 //TODO: move into Network
                 if !self.network.selected_shapes.is_empty() {
-                    self.network.stash_state();
+                    self.network
+                        .stash_state(Edit::DeleteNodes(self.network.selected_shapes.len()));
                     self.network.selected_shapes.iter().for_each(|id| {
                         self.network.graph.delete_node(*id);
                         self.network.shapes.shape_positions.swap_remove(id);
                     });
+                    self.network.scheduler.remove_deleted(&self.network.graph);
                     self.network.selected_shapes = [].into();
                     //PERF: ideally, we should only execute affected nodes
-                    return Task::done(Message::ComputeAll);
+                    return self.compute_all();
+                }
+            }
+            Message::GroupSelectedNodes => {
+                if let Some(nx) = self.network.group_selected() {
+                    return self.queue_compute(nx);
+                }
+            }
+            Message::OpenGroup(nx) => {
+                self.network.open_group(nx);
+                self.action = Action::Idle;
+                return self.compute_all();
+            }
+            Message::CloseGroup => {
+                if self.network.close_group().is_some() {
+                    self.action = Action::Idle;
+                    return self.compute_all();
+                }
+            }
+            Message::SaveGroupMacro(nx) => {
+                let Some(NodeTemplate::Group(group)) =
+                    self.network.graph.get_node(nx).map(|n| &n.template)
+                else {
+                    warn!("Node #{nx} is not a group");
+                    return Task::none();
+                };
+                let Some(nodes_dir) = self.config.nodes_dir().first() else {
+                    warn!("No node directory to save macros to");
+                    return Task::none();
+                };
+                let file = macro_path(nodes_dir, &group.name);
+                if file.exists() {
+                    let result = rfd::MessageDialog::new()
+                        .set_title("Replace Macro?")
+                        .set_description(format!(
+                            "A macro is already saved as {file:?}, replace it?"
+                        ))
+                        .set_buttons(rfd::MessageButtons::YesNo)
+                        .show();
+                    if result != rfd::MessageDialogResult::Yes {
+                        return Task::none();
+                    }
+                }
+                let result = std::fs::create_dir_all(nodes_dir.join(MACRO_DIR))
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| {
+                        ron::ser::to_string_pretty(
+                            group,
+                            ron::ser::PrettyConfig::default().compact_arrays(true),
+                        )
+                        .map_err(|e| e.to_string())
+                    })
+                    .and_then(|src| std::fs::write(&file, src).map_err(|e| e.to_string()));
+                match result {
+                    Ok(()) => {
+                        info!("saved macro {file:?}");
+                        self.python_projects = self.config.read_projects();
+                    }
+                    Err(e) => error!("Could not save macro {file:?}: {e}"),
                 }
             }
 
@@ -358,6 +523,7 @@ impl App {
                     }
                 }
                 self.network = Network::default();
+                self.network.history.configure(self.config.history(), None);
                 self.reload_nodes();
             }
             Message::Load => {
@@ -384,21 +550,32 @@ impl App {
                     .pick_file();
 
                 if let Some(file) = file {
-                    self.network = ron::from_str(
-                        &read_to_string(&file)
-                            .unwrap_or_else(|e| panic!("Could not read network {file:?}\n {e}")),
-                    )
-                    .unwrap_or_else(|e| panic!("Could not parse network {file:?}\n {e}"));
-                    self.network.file = Some(file.clone());
+                    self.network = match Network::read_network(&file, &self.python_projects) {
+                        Ok(network) => network,
+                        Err(e) => {
+                            error!("{e}");
+                            return Task::none();
+                        }
+                    };
+                    if self.config.cache_outputs() {
+                        self.network.restore_outputs();
+                    }
+                    self.network
+                        .history
+                        .configure(self.config.history(), Some(&file));
                     self.user_data.set_recent_network_file(file);
                     self.reload_nodes();
-                    return Task::done(Message::ComputeAll);
+                    return self.compute_all();
                 } else {
                     info!("File not picked")
                 }
             }
             Message::Save => {
                 //TODO: move into Network
+                // Only the top level network is saved
+                if self.network.close_all_groups() {
+                    return Task::done(Message::Save).chain(Task::done(Message::ComputeAll));
+                }
                 let file = match self.network.file.clone() {
                     Some(file) => Some(file),
                     None => FileDialog::new()
@@ -419,19 +596,31 @@ impl App {
                     info!("saved network {file:?}");
                     self.network.file = Some(file.clone());
                     self.network.unsaved_changes = false;
+                    self.network
+                        .history
+                        .configure(self.config.history(), Some(&file));
+                    self.network.history.write_journal();
+                    if self.config.cache_outputs() {
+                        if let Err(e) = self.network.save_outputs() {
+                            warn!("Could not save outputs: {e}");
+                        }
+                    }
                     self.user_data.set_recent_network_file(file);
                 } else {
                     info!("File not picked")
                 }
             }
             Message::ReloadNodes => {
+                // Node sources may import files that aren't part of their fingerprint
+                self.network.scheduler.clear_memo();
+                Python::with_gil(module_cache::refresh);
                 self.reload_nodes();
-                return Task::done(Message::ComputeAll);
+                return self.compute_all();
             }
             Message::WindowOpen => {
                 if self.action == Action::InitialLoad {
                     self.action = Action::Idle;
-                    return Task::done(Message::ComputeAll);
+                    return self.compute_all();
                 }
             }
             Message::ModifiersChanged(m) => {
@@ -444,145 +633,116 @@ impl App {
 
             //// History
             Message::Undo => {
-                //TODO: move into Network
-                if let Some(prev) = self.network.undo_stack.pop() {
-                    self.network.redo_stack.push((
-                        self.network.graph.clone(),
-                        self.network.shapes.shape_positions.clone(),
-                    ));
-                    self.network.graph = prev.0;
-                    self.network.shapes.shape_positions = prev.1;
-                    return Task::done(Message::ComputeAll);
+                if self.network.undo() {
+                    return self.compute_all();
                 }
             }
             Message::Redo => {
-                //TODO: move into Network
-                if let Some(next) = self.network.redo_stack.pop() {
-                    self.network.undo_stack.push((
-                        self.network.graph.clone(),
-                        self.network.shapes.shape_positions.clone(),
-                    ));
-                    self.network.graph = next.0;
-                    self.network.shapes.shape_positions = next.1;
-                    return Task::done(Message::ComputeAll);
+                if self.network.redo() {
+                    return self.compute_all();
                 }
             }
-            Message::ComputeAll => {
-                //TODO: move into Network
-                let nodes = self.network.graph.get_roots();
-                trace!("Queuing root nodes: {nodes:?}");
-                return Task::batch(
-                    nodes
-                        .into_iter()
-                        .map(|nx| Task::done(Message::QueueCompute(nx))),
-                );
+            //// Variants
+            // Variants are whole networks, so any open group is closed first
+            Message::ForkVariant => {
+                self.network.close_all_groups();
+                let name = self.network.variants.unused_name();
+                self.network.fork_variant(name);
             }
-            Message::QueueCompute(nx) => {
-                //TODO: move into Network
-                //// Modify node status
-                {
-                    let node = self.network.graph.get_mut_node(nx);
-
-                    // Re-queue
-                    if let NodeStatus::Running(..) = node.status {
-                        trace!("Re-queue, {} #{nx}", node.template);
-                        self.network.queued_nodes.insert(nx);
-                        return Task::none();
-                    };
-
-                    node.status = NodeStatus::Running(Instant::now());
-                    trace!("Beginning compute: {} #{nx}", node.template,);
+            Message::SwitchVariant(name) => {
+                self.network.close_all_groups();
+                self.network.switch_variant(&name);
+                return self.compute_all();
+            }
+            Message::CompareVariant(name) => {
+                self.network.close_all_groups();
+                self.network.variants.comparing = name;
+            }
+            Message::ApplyVariant(name) => {
+                self.network.close_all_groups();
+                match self.network.apply_variant(&name) {
+                    Ok(()) => return self.compute_all(),
+                    Err(e) => error!("{e}"),
                 }
-
-                //// Queue compute
-                let node = self.network.graph.get_node(nx);
-                return Task::perform(
-                    Graph::async_compute(nx, node.clone(), self.network.graph.get_input_data(&nx)),
-                    move |(nx, res)| Message::ComputeComplete(nx, res),
-                );
             }
-            Message::ComputeComplete(nx, result) => {
-                //TODO: move into Network
-                match result {
-                    Ok((output, node)) => {
-                        // Assert that status is what is expected
-                        let run_time = match &node.status {
-                            NodeStatus::Idle => panic!("Node should not be idle here!"),
-                            NodeStatus::Running(start_inst) => Instant::now() - *start_inst,
-                            NodeStatus::Error(_node_error) => panic!("Node should not be Error, compute should have returned an Error result and node.status is set to Error in the match arm below"),
-                        };
-
-                        trace!("Compute complete: {} #{nx}, {run_time:.1?}", node.template,);
+            Message::DeleteVariant(name) => self.network.delete_variant(&name),
+            Message::ComputeAll => return self.compute_all(),
+            Message::QueueCompute(nx) => return self.queue_compute(nx),
+            Message::ComputeComplete(output) => {
+                let mut executor = TaskExecutor::default();
+                let Some(Completion {
+                    nx,
+                    run_time,
+                    result,
+                    dispatched,
+                }) =
+                    self.network
+                        .scheduler
+                        .complete(&mut self.network.graph, output, &mut executor)
+                else {
+                    // Cancelled or superseded by a newer compute
+                    return Task::none();
+                };
 
-                        //// Update wire
-                        self.network.graph.update_wire_data(nx, output);
+                match result {
+                    Ok(node) => {
+                        trace!("Compute complete: {} #{nx}, {run_time:.1?}", node.template);
 
                         //// Update node
-                        self.network.graph.set_node_data(
+                        // Edits queue their node straight away, superseding the running job,
+                        // so the returned template is never older than the one in the graph
+                        if let Err(e) = self.network.graph.set_node_data(
                             nx,
                             NodeData {
+                                template: node.template,
                                 status: NodeStatus::Idle,
                                 run_time: Some(run_time),
-                                // We *don't* update template here for some nodes
-                                // because that causes stuttery behaviour for
-                                // fast update scenarios like the slider of the 'constant'
-                                // node. alternatively, canceling in progress compute tasks
-                                // might address this, and may be necessary in the future.
-                                // similar to TODO: below
-                                template: match node.template {
-                                    NodeTemplate::RustNode(RustNode::Constant(_)) => {
-                                        self.network.graph.get_node(nx).template.clone()
-                                    }
-                                    NodeTemplate::PyNode(_) => {
-                                        self.network.graph.get_node(nx).template.clone()
-                                    }
-                                    _ => node.template,
-                                },
                             },
-                        );
-
-                        //// Queue children for compute
-                        let to_queue: Vec<_> = self
-                            .network
-                            .graph
-                            .outgoing_edges(&nx)
-                            .into_iter()
-                            .map(|port_ref| port_ref.node)
-                            .unique() // Don't queue a child multiple times
-                            // TODO: instead of requeing after compute is done,
-                            // potentially abort the running compute task, and restart
-                            // immediately when new input data is received
-                            .chain(
-                                once(self.network.queued_nodes.remove(&nx).then_some(nx)).flatten(),
-                            ) // Re-execute node if it got queued up in the meantime
-                            .collect();
-                        trace!("Queuing children for compute {to_queue:?}");
-                        return Task::batch(
-                            to_queue
-                                .into_iter()
-                                .map(|node| Task::done(Message::QueueCompute(node))),
-                        );
+                        ) {
+                            warn!("Ignoring compute result: {e}");
+                        }
                     }
                     Err(node_error) => {
-                        //// Update Node
-                        let node = self.network.graph.get_mut_node(nx);
-                        warn!("Compute failed {node:?},{}", node_error);
-                        node.status = NodeStatus::Error(node_error);
-                        node.run_time = None;
-
-                        //// Update Wire
-                        self.network.graph.update_wire_data(nx, [].into());
-
-                        return Task::none();
+                        if let Some(node) = self.network.graph.get_mut_node(nx) {
+                            warn!("Compute failed {node:?},{}", node_error);
+                            node.status = NodeStatus::Error(node_error);
+                            node.run_time = None;
+                        }
                     }
                 };
+                self.mark_dispatched(dispatched);
+                return executor.into_task();
+            }
+            Message::TogglePause => {
+                if self.network.scheduler.is_paused() {
+                    info!("Resuming execution");
+                    let mut executor = TaskExecutor::default();
+                    let dispatched = self
+                        .network
+                        .scheduler
+                        .resume(&mut self.network.graph, &mut executor);
+                    self.mark_dispatched(dispatched);
+                    return executor.into_task();
+                } else {
+                    info!("Pausing execution");
+                    self.network.scheduler.pause();
+                }
+            }
+            Message::CancelCompute(nx) => {
+                for nx in self.network.scheduler.cancel(&self.network.graph, nx) {
+                    if let Some(node) = self.network.graph.get_mut_node(nx) {
+                        if let NodeStatus::Running(_) = node.status {
+                            node.status = NodeStatus::Idle;
+                        }
+                    }
+                }
             }
         };
         Task::none()
     }
 
     /// App View
-    pub fn view(&self) -> Element<Message, Theme, Renderer> {
+    pub fn view(&self) -> Element<'_, Message, Theme, Renderer> {
         let content = column![
             row![
                 side_bar(self),
@@ -651,19 +811,77 @@ impl App {
         }
     }
 
+    /// Queue `nx` and its descendants. Called directly by edits, rather than through
+    /// `Message::QueueCompute`, so that a job computing the old template is superseded before
+    /// its result can be applied
+    fn queue_compute(&mut self, nx: u32) -> Task<Message> {
+        let mut executor = TaskExecutor::default();
+        let dispatched = self
+            .network
+            .scheduler
+            .queue(&mut self.network.graph, nx, &mut executor);
+        self.mark_dispatched(dispatched);
+        executor.into_task()
+    }
+
+    /// Queue every node, superseding any running jobs
+    fn compute_all(&mut self) -> Task<Message> {
+        let mut executor = TaskExecutor::default();
+        let dispatched = self
+            .network
+            .scheduler
+            .queue_all(&mut self.network.graph, &mut executor);
+        self.mark_dispatched(dispatched);
+        executor.into_task()
+    }
+
+    /// Remove the wires to ports of `nx` that no longer exist, after its ports changed
+    fn prune_edges(&mut self, nx: u32) {
+        for (from, to) in self.network.graph.prune_edges(nx) {
+            warn!(
+                "Removing wire from {}#{} to {}#{}, the port no longer exists",
+                from.name, from.node, to.name, to.node
+            );
+        }
+    }
+
+    /// Update the status of nodes the scheduler has just started or skipped
+    fn mark_dispatched(&mut self, dispatched: Dispatched) {
+        trace!("Dispatched {dispatched:?}");
+        for nx in dispatched.started {
+            if let Some(node) = self.network.graph.get_mut_node(nx) {
+                node.status = NodeStatus::Running(Instant::now());
+            }
+        }
+        for nx in dispatched.skipped {
+            let missing = self.network.graph.missing_inputs(&nx);
+            if let Some(node) = self.network.graph.get_mut_node(nx) {
+                node.status = NodeStatus::Waiting(missing);
+                node.run_time = None;
+            }
+        }
+        for nx in dispatched.cached {
+            if let Some(node) = self.network.graph.get_mut_node(nx) {
+                node.status = NodeStatus::Cached;
+            }
+        }
+    }
+
     /// Read node definitions from disk, and copies node configuration (parameters and port connections) forward.
     /// *Does not trigger the compute function of any nodes.*
     fn reload_nodes(&mut self) {
         // Update any existing nodes in the graph that could change based on file changes
         self.network.graph.nodes_ref().iter().for_each(|nx| {
-            let node = self.network.graph.get_node(*nx).clone();
-            if let NodeTemplate::PyNode(old_py_node) = node.template {
+            let template = self.network.graph.get_node(*nx).map(|n| n.template.clone());
+            if let Some(NodeTemplate::PyNode(old_py_node)) = template {
                 let PyNode {
                     name: _node_name,
                     relative_path,
                     absolute_path,
                     ports: old_ports,
                     parameters: old_parameters,
+                    timeout: _,
+                    cacheable: _,
                 } = old_py_node;
                 //// Read new node from disk
                 let mut new_py_node = PyNode::new(absolute_path, relative_path);
@@ -676,15 +894,11 @@ impl App {
                     {
                         // Only keep old values that are still present in the new parameters list
                         Ok(new_parameters
-                            .clone()
                             .into_iter()
-                            .chain(old_param.into_iter().filter(|(k, v)| {
-                                if let Some(new_v) = new_parameters.get(k) {
-                                    discriminant(v) == discriminant(new_v)
-                                } else {
-                                    false
-                                }
-                            }))
+                            .map(|(k, new_v)| match old_param.get(&k) {
+                                Some(old_v) => (k, new_v.keep_value(old_v)),
+                                None => (k, new_v),
+                            })
                             .collect())
                     } else {
                         new_py_node.parameters
@@ -736,7 +950,8 @@ impl App {
                 // Update Graph Node
                 self.network
                     .graph
-                    .set_node_data(*nx, NodeTemplate::PyNode(new_py_node).into());
+                    .set_node_data(*nx, NodeTemplate::PyNode(new_py_node).into())
+                    .expect("node is from the graph's node list");
             }
         });
         // Update list of available nodes
@@ -744,6 +959,25 @@ impl App {
     }
 }
 
+/// Runs scheduler jobs as iced tasks, results are returned as `Message::ComputeComplete`
+#[derive(Default)]
+pub struct TaskExecutor(Vec<Task<Message>>);
+
+impl TaskExecutor {
+    pub fn into_task(self) -> Task<Message> {
+        Task::batch(self.0)
+    }
+}
+
+impl Executor<NodeData, PortType, PortData> for TaskExecutor {
+    fn spawn(&mut self, job: ComputeJob<NodeData, PortType, PortData>) {
+        self.0.push(Task::perform(
+            async move { job.run() },
+            Message::ComputeComplete,
+        ));
+    }
+}
+
 pub fn theme(state: &App) -> Theme {
     state.app_theme.clone().into()
 }
@@ -773,6 +1007,8 @@ pub fn subscriptions(state: &App) -> Subscription<Message> {
                         Key::Character(smol_str) => {
                             if modifiers.control() && smol_str == "a" {
                                 Some(Message::OpenAddNodeUi)
+                            } else if modifiers.control() && smol_str == "g" {
+                                Some(Message::GroupSelectedNodes)
                             } else {
                                 None
                             }
@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+
+use crate::overrides::{read_overrides_file, OverrideError, ParameterOverride};
+
+pub const USAGE: &str = "\
+Usage:
+  foray [OPTIONS]              open the network editor
+  foray run <FILE> [OPTIONS]   execute a saved network without opening a window
+  foray help                   show this message
+
+Options:
+  --set <NODE>.<PARAMETER>=<VALUE>   set a node parameter, e.g. --set linspace.num=256
+  --overrides <FILE>                 set node parameters from a toml file";
+
+/// What to do when the application is launched
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Open the interactive editor
+    Gui,
+    /// Execute every node of a saved network, without a window
+    Run(PathBuf),
+    /// Compute python nodes for the app at `address`, see `python::worker`. Not for direct use
+    Worker {
+        address: String,
+        token: u64,
+    },
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    /// Toml files containing parameter overrides, applied in order
+    pub override_files: Vec<PathBuf>,
+    /// `--set` overrides, applied after any override files
+    pub set: Vec<ParameterOverride>,
+}
+
+impl Cli {
+    /// Parse command line arguments, *not* including the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter().peekable();
+        let command = match args.peek().map(|s| s.as_str()) {
+            Some("run") => {
+                args.next();
+                match args.next() {
+                    Some(file) if !file.starts_with("--") => Command::Run(file.into()),
+                    _ => return Err("'run' requires a network file".to_string()),
+                }
+            }
+            Some("worker") => {
+                args.next();
+                match (args.next(), args.next().and_then(|t| t.parse().ok())) {
+                    (Some(address), Some(token)) => Command::Worker { address, token },
+                    _ => return Err("'worker' requires an address and token".to_string()),
+                }
+            }
+            Some("help" | "-h" | "--help") => {
+                args.next();
+                Command::Help
+            }
+            _ => Command::Gui,
+        };
+
+        let mut cli = Cli {
+            command,
+            override_files: vec![],
+            set: vec![],
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("'{arg}' requires a value"));
+            match arg.as_str() {
+                "--set" => cli
+                    .set
+                    .push(value()?.parse().map_err(|e: OverrideError| e.to_string())?),
+                "--overrides" => cli.override_files.push(value()?.into()),
+                _ => return Err(format!("Unexpected argument '{arg}'")),
+            }
+        }
+        Ok(cli)
+    }
+
+    /// Collect all parameter overrides, reading any override files
+    pub fn overrides(&self) -> Result<Vec<ParameterOverride>, OverrideError> {
+        let mut overrides = vec![];
+        for file in &self.override_files {
+            overrides.extend(read_overrides_file(file)?);
+        }
+        overrides.extend(self.set.iter().cloned());
+        Ok(overrides)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, String> {
+        Cli::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(parse("").unwrap().command, Command::Gui);
+        assert_eq!(
+            parse("run recon.ron --set linspace.num=256 --overrides scan.toml").unwrap(),
+            Cli {
+                command: Command::Run("recon.ron".into()),
+                override_files: vec!["scan.toml".into()],
+                set: vec!["linspace.num=256".parse().unwrap()],
+            }
+        );
+        assert_eq!(
+            parse("--set constant.value=1").unwrap().command,
+            Command::Gui
+        );
+        assert_eq!(
+            parse("worker 127.0.0.1:4100 42").unwrap().command,
+            Command::Worker {
+                address: "127.0.0.1:4100".into(),
+                token: 42
+            }
+        );
+        assert!(parse("worker 127.0.0.1:4100").is_err());
+        assert!(parse("run").is_err());
+        assert!(parse("run --set a.b=1").is_err());
+        assert!(parse("--set").is_err());
+        assert!(parse("--set novalue").is_err());
+        assert!(parse("recon.ron").is_err());
+    }
+}
@@ -12,7 +12,9 @@ use pyo3::{py_run, types::PyAnyMethods, PyResult, Python};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    network::history::HistoryConfig,
     project::{python_project, rust_project},
+    python::worker::{self, WorkerConfig},
     style::theme::AppTheme,
 };
 
@@ -21,6 +23,14 @@ use crate::{
 pub struct Config {
     venv_dir: PathBuf,
     python_nodes_dir: Vec<PathBuf>,
+    #[serde(default)]
+    history: HistoryConfig,
+    /// Save computed outputs next to the network file, so they don't need to be recomputed
+    /// after reopening it
+    #[serde(default)]
+    cache_outputs: bool,
+    #[serde(default)]
+    python: WorkerConfig,
 }
 
 impl Config {
@@ -63,6 +73,9 @@ impl Config {
                 let config = Config {
                     venv_dir,
                     python_nodes_dir: vec![nodes_dir],
+                    history: HistoryConfig::default(),
+                    cache_outputs: false,
+                    python: WorkerConfig::default(),
                 };
                 let _ = std::fs::create_dir(config_dir);
                 std::fs::write(
@@ -103,6 +116,7 @@ impl Config {
     }
     pub fn setup_environment(&self) {
         self.setup_python();
+        worker::start(&self.python);
     }
 
     fn setup_python(&self) {
@@ -170,6 +184,14 @@ print("Using python virtual environment:",sys.path[0])
     pub fn nodes_dir(&self) -> &[PathBuf] {
         &self.python_nodes_dir
     }
+
+    pub fn history(&self) -> &HistoryConfig {
+        &self.history
+    }
+
+    pub fn cache_outputs(&self) -> bool {
+        self.cache_outputs
+    }
 }
 
 /// Create a new env string that has the given value prepended
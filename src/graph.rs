@@ -1,13 +1,17 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock},
 };
 
+use derive_more::derive::Display;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use crate::{nodes::status::NodeError, StableMap};
 
+pub mod memo;
+pub mod scheduler;
+
 type WireDataContainer<T> = Arc<RwLock<T>>;
 
 pub trait GraphNode<NodeData, PortType, WireData>
@@ -16,12 +20,60 @@ where
 {
     fn inputs(&self) -> StableMap<String, PortType>;
     fn outputs(&self) -> StableMap<String, PortType>;
+    /// Output types, given the type connected to each input, or the input's own type if nothing
+    /// is. For nodes whose output types follow their inputs, like arithmetic
+    fn outputs_for(&self, _inputs: &StableMap<String, PortType>) -> StableMap<String, PortType> {
+        self.outputs()
+    }
+    /// Inputs that don't need data for the node to compute, with an optional default value.
+    /// Without a default, an optional input with no data is left out of compute's inputs
+    fn optional_inputs(&self) -> StableMap<String, Option<WireData>> {
+        StableMap::new()
+    }
+    /// Hash of everything other than the inputs that affects the outputs of compute.
+    /// Nodes with equal fingerprints and inputs reuse each other's outputs.
+    /// `None`, the default, always computes
+    fn fingerprint(&self) -> Option<u64> {
+        None
+    }
+    /// Called on the copy of the node that is about to be computed, with the graph it is in.
+    /// For nodes that compute part of the graph themselves, like a parameter sweep
+    fn prepare(&mut self, _graph: &Graph<NodeData, PortType, WireData>, _nx: NodeIndex)
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData>,
+        WireData: std::fmt::Debug,
+    {
+    }
     fn compute(
         self,
         inputs: StableMap<String, WireDataContainer<WireData>>,
     ) -> Result<(StableMap<String, WireData>, NodeData), NodeError>;
 }
 
+/// Decides which port types can be joined by a wire, and how data is converted between them
+pub trait PortCompatibility<WireData>: PartialEq {
+    /// Can data from an output port of this type be used by an `input` port
+    fn is_compatible(&self, input: &Self) -> bool;
+    /// Is data implicitly converted when flowing from this type to `input`
+    fn converts(&self, input: &Self) -> bool;
+    /// Convert data from an output port of this type for an `input` port.
+    /// `None` if the data can be used as is
+    fn convert(&self, input: &Self, data: &WireData) -> Option<WireData>;
+}
+
+/// Graphs without port types accept any connection, and never convert
+impl<WireData> PortCompatibility<WireData> for () {
+    fn is_compatible(&self, _input: &Self) -> bool {
+        true
+    }
+    fn converts(&self, _input: &Self) -> bool {
+        false
+    }
+    fn convert(&self, _input: &Self, _data: &WireData) -> Option<WireData> {
+        None
+    }
+}
+
 type PortName = String;
 
 type NodeIndex = u32;
@@ -39,8 +91,43 @@ pub struct PortRef {
     pub io: IO,
 }
 
+impl PortRef {
+    pub fn new(node: NodeIndex, name: impl Into<PortName>, io: IO) -> Self {
+        PortRef {
+            node,
+            name: name.into(),
+            io,
+        }
+    }
+}
+
 type Edge = (PortRef, PortRef);
 
+/// Data converted for an input port, kept until the data or the port types change
+struct Conversion<PortType, WireData> {
+    source_node: NodeIndex,
+    source: WireDataContainer<WireData>,
+    from: PortType,
+    to: PortType,
+    converted: WireDataContainer<WireData>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Display)]
+pub enum GraphError {
+    #[display("Node #{_0} does not exist")]
+    MissingNode(NodeIndex),
+    #[display("Node #{} has no {:?} port '{}'", _0.node, _0.io, _0.name)]
+    MissingPort(PortRef),
+    #[display("Connection would create a cycle")]
+    Cycle,
+    #[display("Connection already exists")]
+    DuplicateEdge,
+    #[display("Port '{}' can't be connected to port '{}', their types are incompatible", from.name, to.name)]
+    IncompatiblePorts { from: PortRef, to: PortRef },
+}
+
+impl std::error::Error for GraphError {}
+
 #[derive(Serialize, Deserialize)]
 pub struct Graph<NodeData, PortType, WireData>
 where
@@ -52,6 +139,9 @@ where
     edges: Vec<Edge>,
     #[serde(skip, default = "default_wire_data")]
     wire_data: HashMap<(NodeIndex, PortName), WireDataContainer<WireData>>,
+    /// Converted input data, by input port, so it isn't converted again on every read
+    #[serde(skip, default = "Mutex::default")]
+    conversions: Mutex<HashMap<(NodeIndex, PortName), Conversion<PortType, WireData>>>,
     next_id: NodeIndex,
     #[serde(skip)]
     phantom: std::marker::PhantomData<PortType>,
@@ -68,6 +158,7 @@ where
             nodes: self.nodes.clone(),
             edges: self.edges.clone(),
             wire_data: Default::default(),
+            conversions: Default::default(),
             next_id: self.next_id,
             phantom: self.phantom,
         }
@@ -80,7 +171,7 @@ fn default_wire_data<K, V>() -> HashMap<K, V> {
 impl<NodeData, PortType, WireData> Graph<NodeData, PortType, WireData>
 where
     NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
-    PortType: Clone,
+    PortType: Clone + PortCompatibility<WireData>,
     WireData: std::fmt::Debug,
 {
     pub fn new() -> Self {
@@ -88,6 +179,7 @@ where
             nodes: StableMap::new(),
             edges: vec![],
             wire_data: HashMap::new(),
+            conversions: Default::default(),
             next_id: 0,
             phantom: std::marker::PhantomData,
         }
@@ -101,32 +193,36 @@ where
         id
     }
 
-    /// Remove a node and all edges associated with it
+    /// Remove a node, its output data, and all edges associated with it
     pub fn delete_node(&mut self, id: NodeIndex) {
         self.nodes.remove(&id);
+        self.clear_wire_data(id);
         self.edges
             .retain(|(from, to)| from.node != id && to.node != id)
     }
+
+    pub fn contains_node(&self, nx: NodeIndex) -> bool {
+        self.nodes.contains_key(&nx)
+    }
+
     ///Get the node value at a given index
-    ///panics if index is not valid!
-    ///Use the index returned from `add_node` to ensure it exists
-    pub fn get_node(&self, nx: NodeIndex) -> &NodeData {
-        &self.nodes[&nx]
+    ///`None` if the node doesn't exist, e.g. it has been deleted
+    pub fn get_node(&self, nx: NodeIndex) -> Option<&NodeData> {
+        self.nodes.get(&nx)
     }
 
     ///Get a mutable reference  to a node value at a given index
-    ///panics if index is not valid
-    ///Use the index returned from `add_node` to ensure it exists
-    pub fn get_mut_node(&mut self, nx: NodeIndex) -> &mut NodeData {
-        self.nodes.get_mut(&nx).unwrap()
+    ///`None` if the node doesn't exist, e.g. it has been deleted
+    pub fn get_mut_node(&mut self, nx: NodeIndex) -> Option<&mut NodeData> {
+        self.nodes.get_mut(&nx)
     }
     pub fn get_output_data(
         &self,
         nx: NodeIndex,
     ) -> StableMap<String, Option<&WireDataContainer<WireData>>> {
         self.get_node(nx)
-            .outputs()
-            .clone()
+            .map(|node| node.outputs())
+            .unwrap_or_default()
             .into_keys()
             .map(|port_name| {
                 (
@@ -136,19 +232,63 @@ where
             })
             .collect()
     }
+    /// Data for each input of `nx` that has data, converted to the input's port type if needed.
+    /// Optional inputs without data use their default value, if they have one.
+    /// Conversions are cached, so reading the same data again doesn't convert it again
     pub fn get_input_data(&self, nx: &NodeIndex) -> StableMap<String, WireDataContainer<WireData>> {
-        self.get_node(*nx)
-            .inputs()
-            .keys()
-            .filter_map(|port_name| {
-                self.get_parent(nx, port_name.clone()).map(|out_port| {
-                    self.wire_data
-                        .get(&(out_port.node, out_port.name))
-                        .map(|data| (port_name.clone(), data.clone()))
-                })
+        let Some(node) = self.get_node(*nx) else {
+            return [].into();
+        };
+        let mut defaults = node.optional_inputs();
+        let mut resolved = HashMap::new();
+        let mut conversions = self.conversions.lock().unwrap();
+        node.inputs()
+            .into_iter()
+            .filter_map(|(port_name, in_type)| {
+                let connected = self.get_parent(nx, port_name.clone()).and_then(|out_port| {
+                    let data = self
+                        .wire_data
+                        .get(&(out_port.node, out_port.name.clone()))?;
+                    let Some(out_type) = self
+                        .resolve_outputs(out_port.node, None, &mut resolved)
+                        .remove(&out_port.name)
+                    else {
+                        return Some(data.clone());
+                    };
+                    let key = (*nx, port_name.clone());
+                    if let Some(conversion) = conversions.get(&key).filter(|conversion| {
+                        Arc::ptr_eq(&conversion.source, data)
+                            && conversion.from == out_type
+                            && conversion.to == in_type
+                    }) {
+                        return Some(conversion.converted.clone());
+                    }
+                    let Some(converted) = out_type.convert(&in_type, &data.read().unwrap()) else {
+                        conversions.remove(&key);
+                        return Some(data.clone());
+                    };
+                    let converted = Arc::new(RwLock::new(converted));
+                    conversions.insert(
+                        key,
+                        Conversion {
+                            source_node: out_port.node,
+                            source: data.clone(),
+                            from: out_type,
+                            to: in_type.clone(),
+                            converted: converted.clone(),
+                        },
+                    );
+                    Some(converted)
+                });
+                let data = connected.or_else(|| {
+                    defaults
+                        .remove(&port_name)
+                        .flatten()
+                        .map(|default| Arc::new(default.into()))
+                })?;
+                Some((port_name, data))
             })
-            .collect::<Option<StableMap<_, _>>>()
-            .unwrap_or([].into())
+            .collect()
     }
     pub fn get_input_data_mapped(
         &self,
@@ -157,7 +297,10 @@ where
         StableMap<String, String>,
         StableMap<String, WireDataContainer<WireData>>,
     ) {
-        let inputs = self.get_node(*nx).inputs();
+        let inputs = self
+            .get_node(*nx)
+            .map(|node| node.inputs())
+            .unwrap_or_default();
 
         let data_with_duplicates = inputs
             .keys()
@@ -206,17 +349,60 @@ where
         &mut self,
         nx: NodeIndex,
         value: NodeData, //GenGraphNode<NodeData, PortType, WireData>,
-    ) {
-        *self.nodes.get_mut(&nx).unwrap() = value;
+    ) -> Result<(), GraphError> {
+        *self.get_mut_node(nx).ok_or(GraphError::MissingNode(nx))? = value;
+        self.prune_edges(nx);
+        Ok(())
+    }
+
+    /// Remove the edges to or from ports of `nx` that no longer exist, after its ports changed.
+    /// Returns the removed edges
+    pub fn prune_edges(&mut self, nx: NodeIndex) -> Vec<Edge> {
+        let (kept, pruned) = std::mem::take(&mut self.edges)
+            .into_iter()
+            .partition(|(from, to)| {
+                [from, to]
+                    .into_iter()
+                    .all(|port| port.node != nx || self.port_index(port).is_ok())
+            });
+        self.edges = kept;
+        pruned
     }
 
     pub fn update_wire_data(&mut self, nx: NodeIndex, outputs: StableMap<PortName, WireData>) {
+        self.clear_conversions(nx);
         for (port_name, wire_data) in outputs.into_iter() {
             self.wire_data
                 .insert((nx, port_name), Arc::new(wire_data.into()));
         }
     }
 
+    /// Put previously computed outputs back on a node, sharing the data rather than copying it
+    pub fn restore_wire_data(
+        &mut self,
+        nx: NodeIndex,
+        outputs: StableMap<PortName, WireDataContainer<WireData>>,
+    ) {
+        self.clear_wire_data(nx);
+        for (port_name, wire_data) in outputs.into_iter() {
+            self.wire_data.insert((nx, port_name), wire_data);
+        }
+    }
+
+    /// Remove all output data of a node
+    pub fn clear_wire_data(&mut self, nx: NodeIndex) {
+        self.wire_data.retain(|(node, _), _| *node != nx);
+        self.clear_conversions(nx);
+    }
+
+    /// Drop conversions of the outputs of `nx`, and of the inputs of `nx`
+    fn clear_conversions(&mut self, nx: NodeIndex) {
+        self.conversions
+            .get_mut()
+            .unwrap()
+            .retain(|(node, _), conversion| *node != nx && conversion.source_node != nx);
+    }
+
     pub fn get_wire_data(
         &self,
         nx: &NodeIndex,
@@ -225,34 +411,6 @@ where
         self.wire_data.get(&(*nx, port_name.into()))
     }
 
-    /// create a connection between two port references
-    pub fn add_edge_from_ref(&mut self, from: &PortRef, to: &PortRef) {
-        assert!(from.io == IO::Out);
-        assert!(to.io == IO::In);
-        self.connect((from.node, from.name.clone()), (to.node, to.name.clone()));
-    }
-    /// create a connection between two ports
-    pub fn connect(
-        &mut self,
-        from: (NodeIndex, impl Into<PortName>),
-        to: (NodeIndex, impl Into<PortName>),
-    ) {
-        let from = PortRef {
-            node: from.0,
-            name: from.1.into(),
-            io: IO::Out,
-        };
-        let to = PortRef {
-            node: to.0,
-            name: to.1.into(),
-            io: IO::In,
-        };
-
-        //TODO: check for compatiablity, or if the edge alread exists
-        // warn if already exists, panic/return result if incompatabible
-        self.edges.push((from, to));
-    }
-
     // remove any edges associated with the given port
     pub fn remove_edge(&mut self, port: &PortRef) {
         self.edges.retain(|(from, to)| port != from && port != to)
@@ -266,26 +424,18 @@ where
     }
 
     /// find the index of the port based on the order defined in the `GraphNode`
-    /// panics if `port` is not valid
-    pub fn port_index(&self, port: &PortRef) -> usize {
-        match port.io {
-            IO::In => self
-                .get_node(port.node)
-                .inputs()
-                .iter()
-                .position(|n| *n.0 == *port.name)
-                .unwrap_or_else(|| {
-                    panic!("PortId must have valid input node index and port id {port:?}",)
-                }),
-            IO::Out => self
-                .get_node(port.node)
-                .outputs()
-                .iter()
-                .position(|n| *n.0 == *port.name)
-                .unwrap_or_else(|| {
-                    panic!("PortId must have valid input node index and port id {port:?}",)
-                }),
-        }
+    pub fn port_index(&self, port: &PortRef) -> Result<usize, GraphError> {
+        let node = self
+            .get_node(port.node)
+            .ok_or(GraphError::MissingNode(port.node))?;
+        let ports = match port.io {
+            IO::In => node.inputs(),
+            IO::Out => node.outputs(),
+        };
+        ports
+            .keys()
+            .position(|name| *name == port.name)
+            .ok_or_else(|| GraphError::MissingPort(port.clone()))
     }
     /// Find a nodes direct parents and the associated labels
     pub fn incoming_edges(&self, nx: &NodeIndex) -> Vec<(PortRef, PortRef)> {
@@ -315,9 +465,99 @@ where
             .collect()
     }
 
+    /// A copy of the graph with only `nodes`, and the edges between them.
+    /// Nodes keep their indices, and new nodes never reuse an index from this graph
+    pub fn subgraph(&self, nodes: &HashSet<NodeIndex>) -> Self {
+        Self {
+            nodes: self
+                .nodes
+                .iter()
+                .filter(|(nx, _)| nodes.contains(nx))
+                .map(|(nx, node)| (*nx, node.clone()))
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .filter(|(from, to)| nodes.contains(&from.node) && nodes.contains(&to.node))
+                .cloned()
+                .collect(),
+            wire_data: HashMap::new(),
+            conversions: Default::default(),
+            next_id: self.next_id,
+            phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Edges that cross the boundary of `nodes`, as (incoming, outgoing)
+    pub fn boundary_edges(&self, nodes: &HashSet<NodeIndex>) -> (Vec<Edge>, Vec<Edge>) {
+        let incoming = self
+            .edges
+            .iter()
+            .filter(|(from, to)| !nodes.contains(&from.node) && nodes.contains(&to.node))
+            .cloned()
+            .collect();
+        let outgoing = self
+            .edges
+            .iter()
+            .filter(|(from, to)| nodes.contains(&from.node) && !nodes.contains(&to.node))
+            .cloned()
+            .collect();
+        (incoming, outgoing)
+    }
+
+    /// Find every node downstream of `nx`, not including `nx`
+    pub fn descendants(&self, nx: NodeIndex) -> HashSet<NodeIndex> {
+        let mut found = HashSet::new();
+        let mut to_visit = vec![nx];
+        while let Some(current) = to_visit.pop() {
+            for child in self.outgoing_edges(&current) {
+                if child.node != nx && found.insert(child.node) {
+                    to_visit.push(child.node);
+                }
+            }
+        }
+        found
+    }
+
+    /// Every edge, as (output, input)
+    pub fn edges(&self) -> impl Iterator<Item = &(PortRef, PortRef)> {
+        self.edges.iter()
+    }
+
+    /// Find every node upstream of `nx`, not including `nx`
+    pub fn ancestors(&self, nx: NodeIndex) -> HashSet<NodeIndex> {
+        let mut found = HashSet::new();
+        let mut to_visit = vec![nx];
+        while let Some(current) = to_visit.pop() {
+            for (parent, _) in self.incoming_edges(&current) {
+                if parent.node != nx && found.insert(parent.node) {
+                    to_visit.push(parent.node);
+                }
+            }
+        }
+        found
+    }
+
+    /// Names of required input ports that are unconnected, or whose connected output has no data
+    pub fn missing_inputs(&self, nx: &NodeIndex) -> Vec<PortName> {
+        let Some(node) = self.get_node(*nx) else {
+            return vec![];
+        };
+        let optional = node.optional_inputs();
+        node.inputs()
+            .into_keys()
+            .filter(|port_name| !optional.contains_key(port_name))
+            .filter(|port_name| {
+                self.get_parent(nx, port_name.clone())
+                    .and_then(|parent| self.get_wire_data(&parent.node, &parent.name))
+                    .is_none()
+            })
+            .collect()
+    }
+
     /// Topological sort using Kahn's algorithm
-    /// returns a list of NodeIndices
-    pub fn topological_sort(&self) -> Vec<NodeIndex> {
+    /// returns a list of NodeIndices, or an error if the graph has cycles
+    pub fn topological_sort(&self) -> Result<Vec<NodeIndex>, GraphError> {
         let mut sorted = vec![];
         let mut working_edges = self.edges.clone();
 
@@ -339,9 +579,9 @@ where
             }
         }
         if working_edges.is_empty() {
-            sorted
+            Ok(sorted)
         } else {
-            panic!("graph has cycles!")
+            Err(GraphError::Cycle)
         }
     }
 
@@ -437,13 +677,15 @@ where
     //        None
     //    }
     //}
+    #[allow(clippy::type_complexity)]
     pub fn get_compute(
         &self,
         nx: NodeIndex,
-    ) -> (NodeData, StableMap<String, WireDataContainer<WireData>>) {
-        let node = self.get_node(nx);
+    ) -> Option<(NodeData, StableMap<String, WireDataContainer<WireData>>)> {
+        let mut node = self.get_node(nx)?.clone();
+        node.prepare(self, nx);
         let wire_data = self.get_input_data(&nx);
-        (node.clone(), wire_data)
+        Some((node, wire_data))
     }
     #[allow(clippy::type_complexity)]
     pub fn compute_node(
@@ -458,16 +700,6 @@ where
 
         (nx, output)
     }
-    pub async fn async_compute(
-        nx: NodeIndex,
-        node: NodeData,
-        input_guarded: StableMap<String, WireDataContainer<WireData>>,
-    ) -> (
-        u32,
-        Result<(StableMap<String, WireData>, NodeData), NodeError>,
-    ) {
-        Self::compute_node(nx, node, input_guarded)
-    }
 
     /// get all nodes that have no parents
     pub fn get_roots(&self) -> Vec<NodeIndex> {
@@ -477,12 +709,139 @@ where
             .copied()
             .collect()
     }
+
+    /// create a connection between two port references
+    pub fn add_edge_from_ref(&mut self, from: &PortRef, to: &PortRef) -> Result<(), GraphError> {
+        self.check_edge(from, to)?;
+        self.edges.push((from.clone(), to.clone()));
+        Ok(())
+    }
+
+    /// create a connection between two ports
+    pub fn connect(
+        &mut self,
+        from: (NodeIndex, impl Into<PortName>),
+        to: (NodeIndex, impl Into<PortName>),
+    ) -> Result<(), GraphError> {
+        let from = PortRef::new(from.0, from.1, IO::Out);
+        let to = PortRef::new(to.0, to.1, IO::In);
+        self.add_edge_from_ref(&from, &to)
+    }
+
+    /// Check that an edge from the output port `from` to the input port `to` could be added,
+    /// without modifying the graph
+    pub fn check_edge(&self, from: &PortRef, to: &PortRef) -> Result<(), GraphError> {
+        let from_type = self.port_type(from, IO::Out)?;
+        let to_type = self.port_type(to, IO::In)?;
+
+        if self.edges.iter().any(|(f, t)| f == from && t == to) {
+            return Err(GraphError::DuplicateEdge);
+        }
+        if from.node == to.node || self.descendants(to.node).contains(&from.node) {
+            return Err(GraphError::Cycle);
+        }
+        if !from_type.is_compatible(&to_type) {
+            return Err(GraphError::IncompatiblePorts {
+                from: from.clone(),
+                to: to.clone(),
+            });
+        }
+        // Output types that follow their inputs change with the edge, and must stay compatible
+        // with the inputs they are connected to
+        let edge = (from.clone(), to.clone());
+        let mut resolved = HashMap::new();
+        let mut affected = self.descendants(to.node);
+        affected.insert(to.node);
+        for (out_port, in_port) in self
+            .edges
+            .iter()
+            .filter(|(f, _)| affected.contains(&f.node))
+        {
+            let out_type = self
+                .resolve_outputs(out_port.node, Some(&edge), &mut resolved)
+                .remove(&out_port.name);
+            let Ok(in_type) = self.port_type(in_port, IO::In) else {
+                continue;
+            };
+            if out_type.is_some_and(|out_type| !out_type.is_compatible(&in_type)) {
+                return Err(GraphError::IncompatiblePorts {
+                    from: out_port.clone(),
+                    to: in_port.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Is data implicitly converted when it flows along the edge from `from` to `to`
+    pub fn edge_converts(&self, from: &PortRef, to: &PortRef) -> bool {
+        match (self.port_type(from, IO::Out), self.port_type(to, IO::In)) {
+            (Ok(from_type), Ok(to_type)) => from_type.converts(&to_type),
+            _ => false,
+        }
+    }
+
+    /// Output types of `nx`, given the types connected to its inputs, see
+    /// `GraphNode::outputs_for`. Empty if the node doesn't exist
+    pub fn output_types(&self, nx: NodeIndex) -> StableMap<String, PortType> {
+        self.resolve_outputs(nx, None, &mut HashMap::new())
+    }
+
+    /// `output_types` of `nx` as if `extra` were an edge of the graph, replacing any edge to its
+    /// input. `resolved` holds the output types of nodes already resolved
+    fn resolve_outputs(
+        &self,
+        nx: NodeIndex,
+        extra: Option<&Edge>,
+        resolved: &mut HashMap<NodeIndex, StableMap<String, PortType>>,
+    ) -> StableMap<String, PortType> {
+        if let Some(outputs) = resolved.get(&nx) {
+            return outputs.clone();
+        }
+        let Some(node) = self.get_node(nx) else {
+            return StableMap::new();
+        };
+        let inputs = node
+            .inputs()
+            .into_iter()
+            .map(|(name, in_type)| {
+                let connected = extra
+                    .into_iter()
+                    .chain(self.edges.iter())
+                    .find(|(_, to)| to.node == nx && to.name == name)
+                    .and_then(|(from, _)| {
+                        self.resolve_outputs(from.node, extra, resolved)
+                            .remove(&from.name)
+                    });
+                (name, connected.unwrap_or(in_type))
+            })
+            .collect();
+        let outputs = node.outputs_for(&inputs);
+        resolved.insert(nx, outputs.clone());
+        outputs
+    }
+
+    /// The type of `port`, which must exist and have direction `io`
+    fn port_type(&self, port: &PortRef, io: IO) -> Result<PortType, GraphError> {
+        let node = self
+            .get_node(port.node)
+            .ok_or(GraphError::MissingNode(port.node))?;
+        let ports = match io {
+            IO::In => node.inputs(),
+            IO::Out => self.output_types(port.node),
+        };
+        ports
+            .get(&port.name)
+            .filter(|_| port.io == io)
+            .cloned()
+            .ok_or_else(|| GraphError::MissingPort(port.clone()))
+    }
 }
 
 impl<NodeData, PortType, WireData> Default for Graph<NodeData, PortType, WireData>
 where
     NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
-    PortType: Clone + Default,
+    PortType: Clone + Default + PortCompatibility<WireData>,
     WireData: std::fmt::Debug,
 {
     fn default() -> Self {
@@ -529,7 +888,15 @@ mod test {
         ) -> Result<(StableMap<String, u32>, Node), NodeError> {
             Ok(match &self {
                 Node::Identity(_node) => (
-                    [("out".to_string(), *inputs["in"].read().unwrap())].into(),
+                    [(
+                        "out".to_string(),
+                        *inputs
+                            .get("in")
+                            .ok_or(NodeError::input_error("in"))?
+                            .read()
+                            .unwrap(),
+                    )]
+                    .into(),
                     self,
                 ),
                 Node::Constant(node) => ([("out".to_string(), node.value)].into(), self),
@@ -537,6 +904,44 @@ mod test {
         }
     }
 
+    #[test]
+    fn subgraph() {
+        let mut g: Graph<Node, (), u32> = Graph::new();
+        let n0 = g.node(Node::Constant(ConstantNode { value: 1 }));
+        let n1 = g.node(Node::Identity(IdentityNode {}));
+        let n2 = g.node(Node::Identity(IdentityNode {}));
+        let n3 = g.node(Node::Identity(IdentityNode {}));
+        g.connect((n0, "out"), (n1, "in")).unwrap();
+        g.connect((n1, "out"), (n2, "in")).unwrap();
+        g.connect((n2, "out"), (n3, "in")).unwrap();
+
+        let selection = [n1, n2].into();
+        let sub = g.subgraph(&selection);
+        assert_eq!(sub.nodes_ref(), vec![n1, n2]);
+        assert_eq!(sub.incoming_edges(&n2).len(), 1);
+        assert!(sub.incoming_edges(&n1).is_empty());
+
+        let (incoming, outgoing) = g.boundary_edges(&selection);
+        assert_eq!(
+            incoming,
+            vec![(g.get_parent(&n1, "in".into()).unwrap(), sub_in(n1))]
+        );
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].1, sub_in(n3));
+
+        // New nodes in the subgraph don't collide with nodes of the original
+        let mut sub = sub;
+        assert!(!g.contains_node(sub.node(Node::Identity(IdentityNode {}))));
+    }
+
+    fn sub_in(node: NodeIndex) -> PortRef {
+        PortRef {
+            node,
+            name: "in".into(),
+            io: IO::In,
+        }
+    }
+
     #[test]
     fn sort() {
         let mut g: Graph<Node, (), u32> = Graph::new();
@@ -550,14 +955,14 @@ mod test {
         let n2 = g.node(Node::Identity(IdentityNode {}));
         let n1 = g.node(Node::Identity(IdentityNode {}));
 
-        g.connect((n1, "out"), (n3, "in"));
-        g.connect((n1, "out"), (n2, "in"));
-        g.connect((n3, "out"), (n4, "in"));
-        g.connect((n4, "out"), (n5, "in"));
-        g.connect((n5, "out"), (n6, "in"));
-        g.connect((n6, "out"), (n7, "in"));
-        g.connect((n7, "out"), (n8, "in"));
-        assert_eq!(g.topological_sort(), vec![7, 6, 5, 4, 3, 2, 1, 0]);
+        g.connect((n1, "out"), (n3, "in")).unwrap();
+        g.connect((n1, "out"), (n2, "in")).unwrap();
+        g.connect((n3, "out"), (n4, "in")).unwrap();
+        g.connect((n4, "out"), (n5, "in")).unwrap();
+        g.connect((n5, "out"), (n6, "in")).unwrap();
+        g.connect((n6, "out"), (n7, "in")).unwrap();
+        g.connect((n7, "out"), (n8, "in")).unwrap();
+        assert_eq!(g.topological_sort(), Ok(vec![7, 6, 5, 4, 3, 2, 1, 0]));
     }
 
     #[test]
@@ -572,15 +977,17 @@ mod test {
         // leave a node unconnected to check that it doesn't get a value propogated
         let n_unconnected = g.node(Node::Identity(IdentityNode {}));
 
-        g.connect((n1, "out"), (n3, "in"));
-        g.connect((n1, "out"), (n2, "in"));
-        g.connect((n3, "out"), (n4, "in"));
+        g.connect((n1, "out"), (n3, "in")).unwrap();
+        g.connect((n1, "out"), (n2, "in")).unwrap();
+        g.connect((n3, "out"), (n4, "in")).unwrap();
 
         //Propogate values
-        for nx in g.topological_sort() {
-            let (node, input_guarded) = g.get_compute(nx);
+        for nx in g.topological_sort().unwrap() {
+            let (node, input_guarded) = g.get_compute(nx).unwrap();
             let (_, output) = Graph::compute_node(nx, node, input_guarded);
-            g.update_wire_data(nx, output.unwrap().0);
+            if let Ok((output, _)) = output {
+                g.update_wire_data(nx, output);
+            }
         }
 
         assert_eq!(*g.get_wire_data(&n1, "out").unwrap().read().unwrap(), 7);
@@ -588,4 +995,52 @@ mod test {
         assert_eq!(*g.get_wire_data(&n3, "out").unwrap().read().unwrap(), 7);
         assert!(g.get_wire_data(&n_unconnected, "out").is_none());
     }
+
+    #[test]
+    fn connect_errors() {
+        let mut g: Graph<Node, (), u32> = Graph::new();
+        let n1 = g.node(Node::Constant(ConstantNode { value: 7 }));
+        let n2 = g.node(Node::Identity(IdentityNode {}));
+        let n3 = g.node(Node::Identity(IdentityNode {}));
+        g.connect((n1, "out"), (n2, "in")).unwrap();
+        g.connect((n2, "out"), (n3, "in")).unwrap();
+
+        assert_eq!(
+            g.connect((n1, "out"), (n2, "in")),
+            Err(GraphError::DuplicateEdge)
+        );
+        assert_eq!(g.connect((n3, "out"), (n2, "in")), Err(GraphError::Cycle));
+        assert_eq!(g.connect((n2, "out"), (n2, "in")), Err(GraphError::Cycle));
+        assert_eq!(
+            g.connect((n1, "out"), (99, "in")),
+            Err(GraphError::MissingNode(99))
+        );
+        assert!(matches!(
+            g.connect((n1, "out"), (n3, "missing")),
+            Err(GraphError::MissingPort(_))
+        ));
+        // Inputs can't be used as outputs
+        assert!(matches!(
+            g.add_edge_from_ref(
+                &PortRef {
+                    node: n3,
+                    name: "in".into(),
+                    io: IO::In
+                },
+                &PortRef {
+                    node: n2,
+                    name: "in".into(),
+                    io: IO::In
+                }
+            ),
+            Err(GraphError::MissingPort(_))
+        ));
+        assert_eq!(g.topological_sort(), Ok(vec![n1, n2, n3]));
+
+        // Edges to ports that no longer exist are removed with them
+        g.set_node_data(n2, Node::Constant(ConstantNode { value: 1 }))
+            .unwrap();
+        assert_eq!(g.get_parent(&n2, "in".into()), None);
+        assert_eq!(g.incoming_edges(&n3).len(), 1);
+    }
 }
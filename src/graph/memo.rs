@@ -0,0 +1,180 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::{Hash, Hasher},
+};
+
+use siphasher::sip::SipHasher13;
+
+use super::{Graph, GraphNode, NodeIndex, PortCompatibility, WireDataContainer};
+use crate::StableMap;
+
+/// Hasher for fingerprints and anything hashed into them. Fingerprints identify outputs saved to
+/// disk, so unlike `DefaultHasher` the keys are fixed and hashes don't change between builds
+pub fn fingerprint_hasher() -> SipHasher13 {
+    SipHasher13::new_with_keys(0, 0)
+}
+
+/// Number of computes whose outputs are kept by default
+pub const DEFAULT_MEMO_CAPACITY: usize = 64;
+
+/// Outputs of previous computes, keyed by a fingerprint of the node and its inputs.
+///
+/// A node's fingerprint combines `GraphNode::fingerprint` with the fingerprints of the data on
+/// each of its inputs, which are the fingerprints of the upstream nodes that produced them.
+/// Data is never hashed, so a lookup costs the same for a scalar or a large array.
+#[derive(Debug)]
+pub struct Memo<WireData> {
+    outputs: HashMap<u64, StableMap<String, WireDataContainer<WireData>>>,
+    /// Fingerprints in the order they were stored, oldest first
+    order: VecDeque<u64>,
+    capacity: usize,
+    /// Fingerprint of the data currently on each node's outputs
+    current: HashMap<NodeIndex, u64>,
+}
+
+impl<WireData> Default for Memo<WireData> {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_MEMO_CAPACITY)
+    }
+}
+
+impl<WireData> Memo<WireData> {
+    /// Keep the outputs of at most `capacity` computes. Zero disables memoization
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            outputs: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+            current: HashMap::new(),
+        }
+    }
+
+    /// Fingerprint of computing `nx` with the data currently on its inputs.
+    /// `None` if the node opts out of memoization, or an input came from a node that did
+    pub fn fingerprint<NodeData, PortType>(
+        &self,
+        graph: &Graph<NodeData, PortType, WireData>,
+        nx: NodeIndex,
+    ) -> Option<u64>
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+        WireData: std::fmt::Debug,
+    {
+        if self.capacity == 0 {
+            return None;
+        }
+        combine(graph, nx, |parent| self.current.get(&parent).copied())
+    }
+
+    /// Outputs previously stored for `fingerprint`
+    pub fn get(&self, fingerprint: u64) -> Option<&StableMap<String, WireDataContainer<WireData>>> {
+        self.outputs.get(&fingerprint)
+    }
+
+    /// Keep `outputs` for `fingerprint`, dropping the oldest entries beyond capacity
+    pub fn store(
+        &mut self,
+        fingerprint: u64,
+        outputs: StableMap<String, WireDataContainer<WireData>>,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.outputs.insert(fingerprint, outputs).is_none() {
+            self.order.push_back(fingerprint);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.outputs.remove(&oldest);
+            }
+        }
+    }
+
+    /// Record which compute produced the data on `nx`'s outputs.
+    /// `None` when the outputs can't be identified, e.g. they were cleared
+    pub fn set_current(&mut self, nx: NodeIndex, fingerprint: Option<u64>) {
+        match fingerprint {
+            Some(fingerprint) => self.current.insert(nx, fingerprint),
+            None => self.current.remove(&nx),
+        };
+    }
+
+    /// Forget which outputs are on nodes that `keep` returns false for
+    pub fn retain_nodes(&mut self, keep: impl Fn(NodeIndex) -> bool) {
+        self.current.retain(|nx, _| keep(*nx));
+    }
+
+    /// Forget all stored outputs
+    pub fn clear(&mut self) {
+        self.outputs.clear();
+        self.order.clear();
+        self.current.clear();
+    }
+}
+
+/// Fingerprint of computing `nx` once everything upstream of it is computed, the same as
+/// `Memo::fingerprint` gives after a full run. It only depends on the graph, so it can identify
+/// outputs across runs of the app
+pub fn upstream_fingerprint<NodeData, PortType, WireData>(
+    graph: &Graph<NodeData, PortType, WireData>,
+    nx: NodeIndex,
+) -> Option<u64>
+where
+    NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+    PortType: Clone + PortCompatibility<WireData>,
+    WireData: std::fmt::Debug,
+{
+    fn fingerprint<NodeData, PortType, WireData>(
+        graph: &Graph<NodeData, PortType, WireData>,
+        nx: NodeIndex,
+        known: &mut HashMap<NodeIndex, Option<u64>>,
+    ) -> Option<u64>
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+        WireData: std::fmt::Debug,
+    {
+        if let Some(fingerprint) = known.get(&nx) {
+            return *fingerprint;
+        }
+        let fingerprint = combine(graph, nx, |parent| fingerprint(graph, parent, known));
+        known.insert(nx, fingerprint);
+        fingerprint
+    }
+    fingerprint(graph, nx, &mut HashMap::new())
+}
+
+/// Hash a node's own fingerprint with the fingerprints of the data on its inputs
+fn combine<NodeData, PortType, WireData>(
+    graph: &Graph<NodeData, PortType, WireData>,
+    nx: NodeIndex,
+    mut parent_fingerprint: impl FnMut(NodeIndex) -> Option<u64>,
+) -> Option<u64>
+where
+    NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+    PortType: Clone + PortCompatibility<WireData>,
+    WireData: std::fmt::Debug,
+{
+    let mut hasher = fingerprint_hasher();
+    graph.get_node(nx)?.fingerprint()?.hash(&mut hasher);
+    let mut incoming = graph.incoming_edges(&nx);
+    incoming.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+    for (from, to) in incoming {
+        (&to.name, parent_fingerprint(from.node)?, &from.name).hash(&mut hasher);
+    }
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fingerprints_are_stable() {
+        // Saved outputs are found by fingerprint, so this must not change between builds
+        let mut hasher = fingerprint_hasher();
+        ("out", 42u64).hash(&mut hasher);
+        assert_eq!(hasher.finish(), 1188593102170389741);
+    }
+}
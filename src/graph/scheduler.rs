@@ -0,0 +1,1038 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::trace;
+
+use super::{memo::Memo, Graph, GraphNode, NodeIndex, PortCompatibility, WireDataContainer};
+use crate::{nodes::status::NodeError, StableMap};
+
+/// Shared flag used to tell a compute job that its result is no longer wanted
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A single node computation, with everything it needs to run detached from the graph
+pub struct ComputeJob<NodeData, PortType, WireData> {
+    pub nx: NodeIndex,
+    pub node: NodeData,
+    pub inputs: StableMap<String, WireDataContainer<WireData>>,
+    /// Set when the job is cancelled or superseded, a job that hasn't started yet won't run
+    pub cancel: CancelToken,
+    generation: u64,
+    phantom: PhantomData<fn() -> PortType>,
+}
+
+impl<NodeData, PortType, WireData> ComputeJob<NodeData, PortType, WireData>
+where
+    NodeData: GraphNode<NodeData, PortType, WireData>,
+    PortType: Clone,
+{
+    pub fn run(self) -> ComputeOutput<NodeData, WireData> {
+        let start = Instant::now();
+        let result = if self.cancel.is_cancelled() {
+            Err(NodeError::Cancelled)
+        } else {
+            self.node.compute(self.inputs)
+        };
+        ComputeOutput {
+            nx: self.nx,
+            generation: self.generation,
+            result,
+            run_time: start.elapsed(),
+        }
+    }
+}
+
+/// The result of running a `ComputeJob`, to be handed back to `Scheduler::complete`
+#[derive(Clone)]
+pub struct ComputeOutput<NodeData, WireData> {
+    pub nx: NodeIndex,
+    generation: u64,
+    pub result: Result<(StableMap<String, WireData>, NodeData), NodeError>,
+    pub run_time: Duration,
+}
+
+/// Runs compute jobs on behalf of a `Scheduler`.
+/// Every job's output must eventually be handed back with `Scheduler::complete`
+pub trait Executor<NodeData, PortType, WireData> {
+    fn spawn(&mut self, job: ComputeJob<NodeData, PortType, WireData>);
+}
+
+/// An executor that can wait on its jobs, needed to run a network to completion
+pub trait BlockingExecutor<NodeData, PortType, WireData>:
+    Executor<NodeData, PortType, WireData>
+{
+    /// Wait for the next job to finish, `None` if no jobs are running
+    fn wait(&mut self) -> Option<ComputeOutput<NodeData, WireData>>;
+}
+
+/// Runs each job immediately, on the calling thread
+pub struct SyncExecutor<NodeData, WireData> {
+    finished: VecDeque<ComputeOutput<NodeData, WireData>>,
+}
+
+impl<NodeData, WireData> Default for SyncExecutor<NodeData, WireData> {
+    fn default() -> Self {
+        Self {
+            finished: VecDeque::new(),
+        }
+    }
+}
+
+impl<NodeData, PortType, WireData> Executor<NodeData, PortType, WireData>
+    for SyncExecutor<NodeData, WireData>
+where
+    NodeData: GraphNode<NodeData, PortType, WireData>,
+    PortType: Clone,
+{
+    fn spawn(&mut self, job: ComputeJob<NodeData, PortType, WireData>) {
+        self.finished.push_back(job.run());
+    }
+}
+
+impl<NodeData, PortType, WireData> BlockingExecutor<NodeData, PortType, WireData>
+    for SyncExecutor<NodeData, WireData>
+where
+    NodeData: GraphNode<NodeData, PortType, WireData>,
+    PortType: Clone,
+{
+    fn wait(&mut self) -> Option<ComputeOutput<NodeData, WireData>> {
+        self.finished.pop_front()
+    }
+}
+
+/// Runs jobs on a fixed set of worker threads, so independent nodes execute in parallel
+pub struct ThreadPoolExecutor<NodeData, PortType, WireData> {
+    jobs: Option<mpsc::Sender<ComputeJob<NodeData, PortType, WireData>>>,
+    results: mpsc::Receiver<ComputeOutput<NodeData, WireData>>,
+    workers: Vec<thread::JoinHandle<()>>,
+    running: usize,
+}
+
+impl<NodeData, PortType, WireData> ThreadPoolExecutor<NodeData, PortType, WireData>
+where
+    NodeData: GraphNode<NodeData, PortType, WireData> + Send + 'static,
+    PortType: Clone + 'static,
+    WireData: Send + Sync + 'static,
+{
+    pub fn new(threads: usize) -> Self {
+        let (job_sender, job_receiver) =
+            mpsc::channel::<ComputeJob<NodeData, PortType, WireData>>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+                thread::spawn(move || loop {
+                    // Only hold the lock while waiting for a job, not while running it
+                    let job = job_receiver.lock().expect("worker panicked").recv();
+                    match job {
+                        Ok(job) => {
+                            if result_sender.send(job.run()).is_err() {
+                                break;
+                            }
+                        }
+                        // Executor has been dropped
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            jobs: Some(job_sender),
+            results,
+            workers,
+            running: 0,
+        }
+    }
+}
+
+impl<NodeData, PortType, WireData> Executor<NodeData, PortType, WireData>
+    for ThreadPoolExecutor<NodeData, PortType, WireData>
+{
+    fn spawn(&mut self, job: ComputeJob<NodeData, PortType, WireData>) {
+        self.running += 1;
+        self.jobs
+            .as_ref()
+            .expect("jobs are only taken on drop")
+            .send(job)
+            .expect("worker threads should be running");
+    }
+}
+
+impl<NodeData, PortType, WireData> BlockingExecutor<NodeData, PortType, WireData>
+    for ThreadPoolExecutor<NodeData, PortType, WireData>
+{
+    fn wait(&mut self) -> Option<ComputeOutput<NodeData, WireData>> {
+        if self.running == 0 {
+            return None;
+        }
+        let output = self.results.recv().ok();
+        self.running -= 1;
+        output
+    }
+}
+
+impl<NodeData, PortType, WireData> Drop for ThreadPoolExecutor<NodeData, PortType, WireData> {
+    fn drop(&mut self) {
+        // Closing the job channel stops the workers
+        self.jobs.take();
+        self.workers.drain(..).for_each(|w| {
+            let _ = w.join();
+        });
+    }
+}
+
+/// Nodes whose state changed as the result of a scheduler operation
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Dispatched {
+    /// Nodes that were handed to the executor
+    pub started: Vec<NodeIndex>,
+    /// Nodes that were not run because some inputs have no data
+    pub skipped: Vec<NodeIndex>,
+    /// Nodes that were not run because the outputs of an identical compute were reused
+    pub cached: Vec<NodeIndex>,
+}
+
+impl Dispatched {
+    fn extend(&mut self, other: Dispatched) {
+        self.started.extend(other.started);
+        self.skipped.extend(other.skipped);
+        self.cached.extend(other.cached);
+    }
+}
+
+/// Outcome of a finished compute job, after it has been applied to the graph
+pub struct Completion<NodeData> {
+    pub nx: NodeIndex,
+    pub run_time: Duration,
+    /// The node value returned from compute.
+    /// The caller decides whether to store it in the graph
+    pub result: Result<NodeData, NodeError>,
+    /// Nodes that became ready as a result of this completion
+    pub dispatched: Dispatched,
+}
+
+/// Decides which nodes need to be computed, and when.
+///
+/// Queuing a node marks it, and everything downstream of it, as dirty.
+/// A dirty node is dispatched to an `Executor` once none of its parents are dirty or running,
+/// so each node in a fan-out or diamond is computed once, after all of its inputs are up to date.
+/// Queuing a node that is already running supersedes the running job: it is cancelled,
+/// its result is dropped when it arrives, and the node is dispatched again once it is ready.
+/// Stale results never reach the graph, so children only ever see up to date data.
+///
+/// While paused, nothing new is dispatched: queued nodes accumulate in the dirty set,
+/// and are computed, parents first, when the scheduler is resumed.
+///
+/// Outputs are memoized: a node whose template and inputs are the same as an earlier compute
+/// reuses that compute's outputs instead of running again.
+#[derive(Debug)]
+pub struct Scheduler<WireData> {
+    /// Nodes that need to be computed, waiting on their parents
+    dirty: HashSet<NodeIndex>,
+    /// Nodes that have been handed to an executor, and haven't completed
+    running: HashMap<NodeIndex, RunningJob>,
+    paused: bool,
+    memo: Memo<WireData>,
+}
+
+impl<WireData> Default for Scheduler<WireData> {
+    fn default() -> Self {
+        Self::with_memo(Memo::default())
+    }
+}
+
+/// Generations are unique across schedulers, so a result from a replaced network
+/// can never be mistaken for a current one
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies the current job for a node, so results from older jobs can be recognised
+#[derive(Debug)]
+struct RunningJob {
+    generation: u64,
+    cancel: CancelToken,
+    /// Where the outputs are memoized, if the node can be
+    fingerprint: Option<u64>,
+}
+
+impl<WireData> Scheduler<WireData> {
+    pub fn with_memo(memo: Memo<WireData>) -> Self {
+        Self {
+            dirty: HashSet::new(),
+            running: HashMap::new(),
+            paused: false,
+            memo,
+        }
+    }
+}
+
+impl<WireData: std::fmt::Debug> Scheduler<WireData> {
+    /// Mark `nx` and its descendants for computation, and dispatch any that are ready
+    pub fn queue<NodeData, PortType, E>(
+        &mut self,
+        graph: &mut Graph<NodeData, PortType, WireData>,
+        nx: NodeIndex,
+        executor: &mut E,
+    ) -> Dispatched
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+        E: Executor<NodeData, PortType, WireData>,
+    {
+        self.mark_dirty(graph.descendants(nx).into_iter().chain([nx]));
+        self.dispatch_ready(graph, executor)
+    }
+
+    /// Mark every node in the graph for computation
+    pub fn queue_all<NodeData, PortType, E>(
+        &mut self,
+        graph: &mut Graph<NodeData, PortType, WireData>,
+        executor: &mut E,
+    ) -> Dispatched
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+        E: Executor<NodeData, PortType, WireData>,
+    {
+        self.mark_dirty(graph.nodes_ref());
+        self.dispatch_ready(graph, executor)
+    }
+
+    /// Stop computing `nx`, and don't compute any descendants that were waiting on it.
+    /// A running job is cancelled, and its result will be dropped.
+    /// Returns the nodes that no longer have pending work
+    pub fn cancel<NodeData, PortType>(
+        &mut self,
+        graph: &Graph<NodeData, PortType, WireData>,
+        nx: NodeIndex,
+    ) -> Vec<NodeIndex>
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+    {
+        let pending_before: HashSet<_> = self.pending().collect();
+        self.dirty.remove(&nx);
+        if let Some(job) = self.running.remove(&nx) {
+            job.cancel.cancel();
+        }
+        self.unqueue_descendants(graph, nx);
+
+        let pending_after: HashSet<_> = self.pending().collect();
+        let mut cancelled: Vec<_> = pending_before.difference(&pending_after).copied().collect();
+        cancelled.sort();
+        cancelled
+    }
+
+    /// Apply a finished job's output to the graph, and dispatch any nodes that are now ready.
+    /// Returns `None` if the job was cancelled or superseded, in which case its output is dropped
+    pub fn complete<NodeData, PortType, E>(
+        &mut self,
+        graph: &mut Graph<NodeData, PortType, WireData>,
+        output: ComputeOutput<NodeData, WireData>,
+        executor: &mut E,
+    ) -> Option<Completion<NodeData>>
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+        E: Executor<NodeData, PortType, WireData>,
+    {
+        let ComputeOutput {
+            nx,
+            generation,
+            result,
+            run_time,
+        } = output;
+        let fingerprint = match self.running.get(&nx) {
+            Some(job) if job.generation == generation => self.running.remove(&nx)?.fingerprint,
+            _ => {
+                trace!("Dropping stale result for #{nx}");
+                return None;
+            }
+        };
+        if !graph.contains_node(nx) {
+            trace!("Dropping result for deleted node #{nx}");
+            return None;
+        }
+
+        let result = match result {
+            Ok((outputs, node)) => {
+                graph.update_wire_data(nx, outputs);
+                if let Some(fingerprint) = fingerprint {
+                    let outputs = graph
+                        .get_output_data(nx)
+                        .into_iter()
+                        .filter_map(|(name, data)| Some((name, data?.clone())))
+                        .collect();
+                    self.memo.store(fingerprint, outputs);
+                }
+                self.memo.set_current(nx, fingerprint);
+                Ok(node)
+            }
+            Err(e) => {
+                graph.clear_wire_data(nx);
+                self.memo.set_current(nx, None);
+                // Descendants can't be computed without this node's output,
+                // unless they depend on other pending nodes
+                self.unqueue_descendants(graph, nx);
+                Err(e)
+            }
+        };
+
+        Some(Completion {
+            nx,
+            run_time,
+            result,
+            dispatched: self.dispatch_ready(graph, executor),
+        })
+    }
+
+    /// Dispatch every node, and wait for the graph to finish computing.
+    /// `on_complete` is called for each finished job, after it is applied to the graph
+    pub fn run<NodeData, PortType, E>(
+        &mut self,
+        graph: &mut Graph<NodeData, PortType, WireData>,
+        executor: &mut E,
+        mut on_complete: impl FnMut(&mut Graph<NodeData, PortType, WireData>, Completion<NodeData>),
+    ) -> Dispatched
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+        E: BlockingExecutor<NodeData, PortType, WireData>,
+    {
+        let dispatched = self.queue_all(graph, executor);
+        while let Some(output) = executor.wait() {
+            if let Some(completion) = self.complete(graph, output, executor) {
+                on_complete(graph, completion);
+            }
+        }
+        dispatched
+    }
+
+    /// Stop dispatching new jobs. Jobs that are already running are allowed to finish
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Start dispatching again, computing everything that was queued while paused
+    pub fn resume<NodeData, PortType, E>(
+        &mut self,
+        graph: &mut Graph<NodeData, PortType, WireData>,
+        executor: &mut E,
+    ) -> Dispatched
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+        E: Executor<NodeData, PortType, WireData>,
+    {
+        self.paused = false;
+        self.dispatch_ready(graph, executor)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn is_running(&self, nx: NodeIndex) -> bool {
+        self.running.contains_key(&nx)
+    }
+
+    /// Is the node waiting to be computed
+    pub fn is_queued(&self, nx: NodeIndex) -> bool {
+        self.dirty.contains(&nx)
+    }
+
+    /// true if there is no pending or running work
+    pub fn is_idle(&self) -> bool {
+        self.dirty.is_empty() && self.running.is_empty()
+    }
+
+    /// Stop all work: queued nodes are forgotten and running jobs are cancelled.
+    /// Memoized outputs are kept. Returns the nodes that were queued or running
+    pub fn cancel_all(&mut self) -> Vec<NodeIndex> {
+        let mut cancelled: Vec<_> = self.pending().collect();
+        cancelled.sort();
+        self.dirty.clear();
+        for (_, job) in self.running.drain() {
+            job.cancel.cancel();
+        }
+        cancelled
+    }
+
+    /// Forget any work for nodes that are no longer in the graph, e.g. after deleting nodes
+    /// or restoring an undo snapshot. Running jobs are cancelled, so their results can't be
+    /// applied to a new node that reuses the same id
+    pub fn remove_deleted<NodeData, PortType>(
+        &mut self,
+        graph: &Graph<NodeData, PortType, WireData>,
+    ) where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+    {
+        self.dirty.retain(|nx| graph.contains_node(*nx));
+        self.running.retain(|nx, job| {
+            let exists = graph.contains_node(*nx);
+            if !exists {
+                job.cancel.cancel();
+            }
+            exists
+        });
+        self.memo.retain_nodes(|nx| graph.contains_node(nx));
+    }
+
+    /// Use `outputs`, already on `nx`, for later computes with the same `fingerprint`.
+    /// For outputs that weren't computed by this scheduler, e.g. restored from disk
+    pub fn memoize(
+        &mut self,
+        nx: NodeIndex,
+        fingerprint: u64,
+        outputs: StableMap<String, WireDataContainer<WireData>>,
+    ) {
+        self.memo.store(fingerprint, outputs);
+        self.memo.set_current(nx, Some(fingerprint));
+    }
+
+    /// Forget all memoized outputs, so every node is computed again
+    pub fn clear_memo(&mut self) {
+        self.memo.clear();
+    }
+
+    /// Nodes that are dirty or running
+    fn pending(&self) -> impl Iterator<Item = NodeIndex> + '_ {
+        self.dirty.iter().chain(self.running.keys()).copied()
+    }
+
+    /// Mark nodes as needing computation. Any running jobs for them are now stale
+    fn mark_dirty(&mut self, nodes: impl IntoIterator<Item = NodeIndex>) {
+        for nx in nodes {
+            if let Some(job) = self.running.remove(&nx) {
+                trace!("Superseding running job for #{nx}");
+                job.cancel.cancel();
+            }
+            self.dirty.insert(nx);
+        }
+    }
+
+    /// Hand every dirty node whose parents are up to date to the executor.
+    /// Nodes missing input data are skipped, and their outputs cleared
+    fn dispatch_ready<NodeData, PortType, E>(
+        &mut self,
+        graph: &mut Graph<NodeData, PortType, WireData>,
+        executor: &mut E,
+    ) -> Dispatched
+    where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+        E: Executor<NodeData, PortType, WireData>,
+    {
+        let mut dispatched = Dispatched::default();
+        if self.paused {
+            return dispatched;
+        }
+        loop {
+            let mut ready: Vec<_> = self
+                .dirty
+                .iter()
+                .filter(|nx| {
+                    graph.incoming_edges(nx).iter().all(|(from, _to)| {
+                        !self.dirty.contains(&from.node) && !self.running.contains_key(&from.node)
+                    })
+                })
+                .copied()
+                .collect();
+            if ready.is_empty() {
+                return dispatched;
+            }
+            ready.sort();
+
+            let mut round = Dispatched::default();
+            for nx in ready {
+                self.dirty.remove(&nx);
+                if !graph.contains_node(nx) {
+                    continue;
+                }
+                if graph.missing_inputs(&nx).is_empty() {
+                    let fingerprint = self.memo.fingerprint(graph, nx);
+                    if let Some(outputs) = fingerprint.and_then(|f| self.memo.get(f)) {
+                        trace!("Reusing memoized outputs for #{nx}");
+                        graph.restore_wire_data(nx, outputs.clone());
+                        self.memo.set_current(nx, fingerprint);
+                        round.cached.push(nx);
+                        continue;
+                    }
+                    let Some((node, inputs)) = graph.get_compute(nx) else {
+                        continue;
+                    };
+                    let cancel = CancelToken::default();
+                    let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+                    self.running.insert(
+                        nx,
+                        RunningJob {
+                            generation,
+                            cancel: cancel.clone(),
+                            fingerprint,
+                        },
+                    );
+                    executor.spawn(ComputeJob {
+                        nx,
+                        node,
+                        inputs,
+                        cancel,
+                        generation,
+                        phantom: PhantomData,
+                    });
+                    round.started.push(nx);
+                } else {
+                    graph.clear_wire_data(nx);
+                    self.memo.set_current(nx, None);
+                    round.skipped.push(nx);
+                }
+            }
+            // Skipping a node, or reusing its outputs, can make its children ready,
+            // so keep going until nothing changes
+            let finished_any = !round.skipped.is_empty() || !round.cached.is_empty();
+            dispatched.extend(round);
+            if !finished_any {
+                return dispatched;
+            }
+        }
+    }
+
+    /// Remove descendants of `nx` from the dirty set, unless they are also downstream of
+    /// another node that is still pending
+    fn unqueue_descendants<NodeData, PortType>(
+        &mut self,
+        graph: &Graph<NodeData, PortType, WireData>,
+        nx: NodeIndex,
+    ) where
+        NodeData: GraphNode<NodeData, PortType, WireData> + Clone,
+        PortType: Clone + PortCompatibility<WireData>,
+    {
+        let blocked = graph.descendants(nx);
+        self.dirty.retain(|n| !blocked.contains(n));
+        let pending: Vec<_> = self.pending().collect();
+        for other in pending {
+            self.dirty
+                .extend(graph.descendants(other).intersection(&blocked).copied());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Sums all inputs, or produces a constant when it has no inputs
+    #[derive(Clone, Debug)]
+    struct Node {
+        inputs: Vec<&'static str>,
+        optional: Vec<(&'static str, Option<u32>)>,
+        value: u32,
+        memoize: bool,
+    }
+
+    fn constant(value: u32) -> Node {
+        Node {
+            inputs: vec![],
+            optional: vec![],
+            value,
+            memoize: false,
+        }
+    }
+    fn sum(inputs: &[&'static str]) -> Node {
+        Node {
+            inputs: inputs.to_vec(),
+            optional: vec![],
+            value: 0,
+            memoize: false,
+        }
+    }
+
+    impl GraphNode<Node, (), u32> for Node {
+        fn inputs(&self) -> StableMap<String, ()> {
+            self.inputs
+                .iter()
+                .chain(self.optional.iter().map(|(i, _)| i))
+                .map(|i| (i.to_string(), ()))
+                .collect()
+        }
+
+        fn optional_inputs(&self) -> StableMap<String, Option<u32>> {
+            self.optional
+                .iter()
+                .map(|(i, default)| (i.to_string(), *default))
+                .collect()
+        }
+
+        fn outputs(&self) -> StableMap<String, ()> {
+            [("out".to_string(), ())].into()
+        }
+
+        fn fingerprint(&self) -> Option<u64> {
+            self.memoize
+                .then(|| self.value as u64 + ((self.inputs.len() as u64) << 32))
+        }
+
+        fn compute(
+            self,
+            inputs: StableMap<String, WireDataContainer<u32>>,
+        ) -> Result<(StableMap<String, u32>, Node), NodeError> {
+            let out = if self.inputs.is_empty() {
+                self.value
+            } else {
+                inputs.values().map(|v| *v.read().unwrap()).sum()
+            };
+            Ok(([("out".to_string(), out)].into(), self))
+        }
+    }
+
+    /// Holds jobs until the test decides to run them
+    #[derive(Default)]
+    struct ManualExecutor(Vec<ComputeJob<Node, (), u32>>);
+    impl Executor<Node, (), u32> for ManualExecutor {
+        fn spawn(&mut self, job: ComputeJob<Node, (), u32>) {
+            self.0.push(job)
+        }
+    }
+
+    fn out(g: &Graph<Node, (), u32>, nx: NodeIndex) -> Option<u32> {
+        g.get_wire_data(&nx, "out").map(|d| *d.read().unwrap())
+    }
+
+    /// Run the whole graph, returning the order nodes completed in
+    fn run_all(
+        g: &mut Graph<Node, (), u32>,
+        executor: &mut impl BlockingExecutor<Node, (), u32>,
+    ) -> Vec<NodeIndex> {
+        let mut order = vec![];
+        Scheduler::default().run(g, executor, |g, completion| {
+            g.set_node_data(completion.nx, completion.result.unwrap())
+                .unwrap();
+            order.push(completion.nx);
+        });
+        order
+    }
+
+    #[test]
+    fn fan_out() {
+        let mut g = Graph::new();
+        let root = g.node(constant(3));
+        let children: Vec<_> = (0..4).map(|_| g.node(sum(&["a"]))).collect();
+        children
+            .iter()
+            .for_each(|c| g.connect((root, "out"), (*c, "a")).unwrap());
+
+        let order = run_all(&mut g, &mut SyncExecutor::default());
+
+        assert_eq!(order[0], root);
+        assert_eq!(order.len(), 5);
+        children
+            .iter()
+            .for_each(|c| assert_eq!(out(&g, *c), Some(3)));
+    }
+
+    #[test]
+    fn diamond() {
+        let mut g = Graph::new();
+        let top = g.node(constant(1));
+        let left = g.node(sum(&["a"]));
+        let right = g.node(sum(&["a"]));
+        let bottom = g.node(sum(&["a", "b"]));
+        g.connect((top, "out"), (left, "a")).unwrap();
+        g.connect((top, "out"), (right, "a")).unwrap();
+        g.connect((left, "out"), (bottom, "a")).unwrap();
+        g.connect((right, "out"), (bottom, "b")).unwrap();
+
+        let mut executor = ThreadPoolExecutor::new(4);
+        let order = run_all(&mut g, &mut executor);
+
+        // bottom is only computed once, after both sides
+        assert_eq!(order.iter().filter(|nx| **nx == bottom).count(), 1);
+        assert_eq!(order.last(), Some(&bottom));
+        assert_eq!(out(&g, bottom), Some(2));
+    }
+
+    #[test]
+    fn skip_missing_inputs() {
+        let mut g = Graph::new();
+        let root = g.node(constant(1));
+        let unconnected = g.node(sum(&["a", "b"]));
+        let child = g.node(sum(&["a"]));
+        g.connect((root, "out"), (unconnected, "a")).unwrap();
+        g.connect((unconnected, "out"), (child, "a")).unwrap();
+
+        let mut scheduler = Scheduler::default();
+        let mut skipped = vec![];
+        let dispatched = scheduler.run(&mut g, &mut SyncExecutor::default(), |_, completion| {
+            skipped.extend(completion.dispatched.skipped)
+        });
+        skipped.extend(dispatched.skipped);
+
+        assert_eq!(skipped, vec![unconnected, child]);
+        assert_eq!(out(&g, root), Some(1));
+        assert_eq!(out(&g, unconnected), None);
+        assert_eq!(out(&g, child), None);
+        assert!(scheduler.is_idle());
+    }
+
+    #[test]
+    fn optional_inputs() {
+        let mut g = Graph::new();
+        let root = g.node(constant(1));
+        let node = g.node(Node {
+            optional: vec![("b", Some(10)), ("c", None)],
+            ..sum(&["a"])
+        });
+        g.connect((root, "out"), (node, "a")).unwrap();
+
+        let mut scheduler = Scheduler::default();
+        let mut skipped = vec![];
+        let dispatched = scheduler.run(&mut g, &mut SyncExecutor::default(), |g, completion| {
+            g.set_node_data(completion.nx, completion.result.unwrap())
+                .unwrap();
+            skipped.extend(completion.dispatched.skipped)
+        });
+        skipped.extend(dispatched.skipped);
+
+        // "b" falls back to its default, "c" is left out
+        assert!(skipped.is_empty());
+        assert_eq!(out(&g, node), Some(11));
+        assert!(g.missing_inputs(&node).is_empty());
+
+        g.connect((root, "out"), (node, "c")).unwrap();
+        run_all(&mut g, &mut SyncExecutor::default());
+        assert_eq!(out(&g, node), Some(12));
+    }
+
+    #[test]
+    fn supersede_while_running() {
+        let mut g = Graph::new();
+        let root = g.node(constant(1));
+        let child = g.node(sum(&["a"]));
+        g.connect((root, "out"), (child, "a")).unwrap();
+
+        let mut scheduler = Scheduler::default();
+        let mut executor = ManualExecutor::default();
+
+        let dispatched = scheduler.queue(&mut g, root, &mut executor);
+        assert_eq!(dispatched.started, vec![root]);
+        assert!(scheduler.is_running(root));
+        assert!(scheduler.is_queued(child));
+
+        // Change the root while it is running, and queue it again
+        g.set_node_data(root, constant(2)).unwrap();
+        let dispatched = scheduler.queue(&mut g, root, &mut executor);
+        assert_eq!(dispatched.started, vec![root]);
+        let stale = executor.0.remove(0);
+        assert!(stale.cancel.is_cancelled());
+
+        // The stale run finishes anyway, its result is dropped and the child isn't started
+        let stale = ComputeOutput {
+            nx: root,
+            generation: stale.generation,
+            result: Ok(([("out".to_string(), 1)].into(), constant(1))),
+            run_time: Duration::ZERO,
+        };
+        assert!(scheduler.complete(&mut g, stale, &mut executor).is_none());
+        assert_eq!(out(&g, root), None);
+        assert!(scheduler.is_queued(child));
+
+        // The current run finishes, child is now dispatched
+        let job = executor.0.remove(0);
+        let completion = scheduler
+            .complete(&mut g, job.run(), &mut executor)
+            .unwrap();
+        assert_eq!(completion.dispatched.started, vec![child]);
+
+        let job = executor.0.remove(0);
+        scheduler.complete(&mut g, job.run(), &mut executor);
+
+        assert!(scheduler.is_idle());
+        assert_eq!(out(&g, child), Some(2));
+    }
+
+    #[test]
+    fn pause_resume() {
+        let mut g = Graph::new();
+        let root = g.node(constant(1));
+        let child = g.node(sum(&["a"]));
+        let unrelated = g.node(constant(5));
+        g.connect((root, "out"), (child, "a")).unwrap();
+
+        let mut scheduler = Scheduler::default();
+        let mut executor = SyncExecutor::default();
+        scheduler.pause();
+
+        // Edits while paused only mark nodes dirty
+        assert_eq!(
+            scheduler.queue(&mut g, root, &mut executor),
+            Dispatched::default()
+        );
+        g.set_node_data(root, constant(2)).unwrap();
+        scheduler.queue(&mut g, root, &mut executor);
+        assert!(scheduler.is_queued(child));
+        assert!(!scheduler.is_queued(unrelated));
+
+        let mut order = vec![];
+        let dispatched = scheduler.resume(&mut g, &mut executor);
+        order.extend(dispatched.started);
+        while let Some(output) = BlockingExecutor::wait(&mut executor) {
+            let completion = scheduler.complete(&mut g, output, &mut executor).unwrap();
+            order.extend(completion.dispatched.started);
+        }
+
+        // Only the dirty nodes are computed, once each, parents first
+        assert_eq!(order, vec![root, child]);
+        assert_eq!(out(&g, child), Some(2));
+        assert_eq!(out(&g, unrelated), None);
+        assert!(scheduler.is_idle());
+    }
+
+    #[test]
+    fn delete_during_compute() {
+        let mut g = Graph::new();
+        let root = g.node(constant(1));
+        let child = g.node(sum(&["a"]));
+        g.connect((root, "out"), (child, "a")).unwrap();
+
+        let mut scheduler = Scheduler::default();
+        let mut executor = ManualExecutor::default();
+        scheduler.queue(&mut g, root, &mut executor);
+
+        // The result arrives after the node is gone
+        g.delete_node(root);
+        let job = executor.0.remove(0);
+        assert!(scheduler
+            .complete(&mut g, job.run(), &mut executor)
+            .is_none());
+        assert_eq!(out(&g, root), None);
+
+        // The orphaned child is skipped rather than computed
+        scheduler.remove_deleted(&g);
+        let mut executor = SyncExecutor::default();
+        let dispatched = scheduler.queue(&mut g, child, &mut executor);
+        assert_eq!(dispatched.skipped, vec![child]);
+        assert!(scheduler.is_idle());
+    }
+
+    #[test]
+    fn undo_during_compute() {
+        let mut g = Graph::new();
+        let root = g.node(constant(1));
+        let snapshot = g.clone();
+        let added = g.node(constant(2));
+
+        let mut scheduler = Scheduler::default();
+        let mut executor = ManualExecutor::default();
+        scheduler.queue(&mut g, added, &mut executor);
+
+        // Undo the addition, then add a different node which reuses the id
+        g = snapshot;
+        scheduler.remove_deleted(&g);
+        let reused = g.node(constant(3));
+        assert_eq!(reused, added);
+
+        let job = executor.0.remove(0);
+        assert!(job.cancel.is_cancelled());
+        assert!(scheduler
+            .complete(&mut g, job.run(), &mut executor)
+            .is_none());
+        assert_eq!(out(&g, reused), None);
+        assert!(g.get_node(root).is_some());
+    }
+
+    #[test]
+    fn memoized() {
+        let memoize = |node: Node| Node {
+            memoize: true,
+            ..node
+        };
+        let mut g = Graph::new();
+        let root = g.node(memoize(constant(1)));
+        let child = g.node(memoize(sum(&["a"])));
+        g.connect((root, "out"), (child, "a")).unwrap();
+
+        let mut scheduler = Scheduler::default();
+        let mut executor = SyncExecutor::default();
+        let mut run = |g: &mut Graph<Node, (), u32>, nx| {
+            let mut dispatched = scheduler.queue(g, nx, &mut executor);
+            while let Some(output) = executor.wait() {
+                let completion = scheduler.complete(g, output, &mut executor).unwrap();
+                dispatched.extend(completion.dispatched);
+            }
+            dispatched
+        };
+
+        let dispatched = run(&mut g, root);
+        assert_eq!(dispatched.started, vec![root, child]);
+
+        // Changing the root computes again
+        g.set_node_data(root, memoize(constant(2))).unwrap();
+        let dispatched = run(&mut g, root);
+        assert_eq!(dispatched.started, vec![root, child]);
+        assert_eq!(out(&g, child), Some(2));
+
+        // Changing it back reuses the first outputs
+        let snapshot = g.clone();
+        g.set_node_data(root, memoize(constant(1))).unwrap();
+        let dispatched = run(&mut g, root);
+        assert_eq!(dispatched.cached, vec![root, child]);
+        assert!(dispatched.started.is_empty());
+        assert_eq!(out(&g, child), Some(1));
+
+        // Restoring a snapshot without wire data, like undo, doesn't compute anything
+        g = snapshot;
+        let dispatched = run(&mut g, root);
+        assert_eq!(dispatched.cached, vec![root, child]);
+        assert_eq!(out(&g, child), Some(2));
+    }
+
+    #[test]
+    fn cancel() {
+        let mut g = Graph::new();
+        let root = g.node(constant(1));
+        let other = g.node(constant(1));
+        let child = g.node(sum(&["a"]));
+        let both = g.node(sum(&["a", "b"]));
+        g.connect((root, "out"), (child, "a")).unwrap();
+        g.connect((root, "out"), (both, "a")).unwrap();
+        g.connect((other, "out"), (both, "b")).unwrap();
+
+        let mut scheduler = Scheduler::default();
+        let mut executor = ManualExecutor::default();
+        scheduler.queue_all(&mut g, &mut executor);
+
+        // `both` still depends on `other`, which hasn't been cancelled
+        assert_eq!(scheduler.cancel(&g, root), vec![root, child]);
+        assert!(scheduler.is_queued(both));
+
+        // A cancelled job that hasn't started doesn't compute
+        let job = executor.0.remove(0);
+        assert_eq!(job.nx, root);
+        let output = job.run();
+        assert_eq!(output.result.as_ref().err(), Some(&NodeError::Cancelled));
+        assert!(scheduler.complete(&mut g, output, &mut executor).is_none());
+        assert_eq!(out(&g, root), None);
+        assert!(!scheduler.is_queued(child));
+    }
+}
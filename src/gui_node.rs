@@ -5,7 +5,7 @@ use iced::{widget::text, Element};
 use crate::{
     app::Message,
     graph::Graph,
-    interface::node::default_node_size,
+    interface::{node::default_node_size, node_config::ParameterEdit},
     nodes::{
         port::{PortData, PortType},
         status::NodeStatus,
@@ -28,7 +28,7 @@ pub trait GUINode: derive_more::Debug {
         &self,
         _id: u32,
         _input_data: StableMap<String, PortDataContainer>,
-    ) -> (iced::Size, Element<Message>) {
+    ) -> (iced::Size, Element<'_, Message>) {
         (default_node_size(), text("default").into())
     }
 
@@ -36,6 +36,7 @@ pub trait GUINode: derive_more::Debug {
         &self,
         _id: u32,
         _input_data: StableMap<String, PortDataContainer>,
+        _edit: Option<&ParameterEdit>,
     ) -> Option<Element<'_, Message>> {
         None
     }
@@ -49,7 +50,7 @@ impl GuiGraph {
     pub fn running_nodes(&self) -> Vec<&NodeData> {
         self.nodes_ref()
             .into_iter()
-            .map(|nx| self.get_node(nx))
+            .filter_map(|nx| self.get_node(nx))
             .filter(|node| matches!(node.status, NodeStatus::Running(..)))
             .collect()
     }
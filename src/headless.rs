@@ -0,0 +1,229 @@
+use std::{fmt::Display, path::PathBuf, process::ExitCode, thread};
+
+use log::info;
+
+use crate::{
+    config::Config,
+    graph::scheduler::{Scheduler, ThreadPoolExecutor},
+    gui_node::{GUINode, GuiGraph},
+    network::Network,
+    nodes::{
+        status::{NodeError, NodeStatus},
+        NodeData,
+    },
+    overrides::ParameterOverride,
+};
+
+/// What happened when a single node was executed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Complete(std::time::Duration),
+    Failed(NodeError),
+    /// The node was not run, because these inputs had no data
+    Skipped(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeReport {
+    pub id: u32,
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+/// Per node summary of a headless run, in execution order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunReport(pub Vec<NodeReport>);
+
+impl RunReport {
+    /// true if no node returned an error
+    pub fn success(&self) -> bool {
+        !self
+            .0
+            .iter()
+            .any(|r| matches!(r.outcome, Outcome::Failed(_)))
+    }
+}
+
+impl Display for RunReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for NodeReport { id, name, outcome } in &self.0 {
+            match outcome {
+                Outcome::Complete(run_time) => {
+                    writeln!(f, "  ok      #{id} {name} ({run_time:.1?})")?
+                }
+                Outcome::Failed(e) => writeln!(f, "  FAILED  #{id} {name}\n{e}")?,
+                Outcome::Skipped(missing) => writeln!(
+                    f,
+                    "  skipped #{id} {name} (no data for {})",
+                    missing.join(", ")
+                )?,
+            }
+        }
+        let failed = self
+            .0
+            .iter()
+            .filter(|r| matches!(r.outcome, Outcome::Failed(_)))
+            .count();
+        write!(f, "{} nodes, {failed} failed", self.0.len())
+    }
+}
+
+/// Load the network at `file`, apply parameter overrides, and execute it without opening a window.
+/// Returns a failing exit code if the network could not be loaded, an override is invalid,
+/// or any node errors
+pub fn run(file: &PathBuf, overrides: &[ParameterOverride]) -> ExitCode {
+    let config = Config::read_config();
+    config.setup_environment();
+    let projects = config.read_projects();
+
+    let mut network = match Network::read_network(file, &projects) {
+        Ok(network) => network,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if config.cache_outputs() {
+        network.restore_outputs();
+    }
+
+    if let Err(errors) = network.apply_overrides(overrides) {
+        errors.iter().for_each(|e| eprintln!("{e}"));
+        return ExitCode::FAILURE;
+    }
+
+    info!("Running network {file:?}");
+    let report = execute(&mut network);
+    println!("{report}");
+
+    if report.success() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Compute every node in the network once, using the same scheduling as the editor.
+/// Nodes with inputs that have no data (unconnected, or upstream failure) are skipped
+pub fn execute(network: &mut Network) -> RunReport {
+    let name = |graph: &GuiGraph, nx: u32| {
+        graph
+            .get_node(nx)
+            .map(|node| node.template.name())
+            .unwrap_or_default()
+    };
+    let skipped = |graph: &GuiGraph, nx: u32| NodeReport {
+        id: nx,
+        name: name(graph, nx),
+        outcome: Outcome::Skipped(graph.missing_inputs(&nx)),
+    };
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut executor = ThreadPoolExecutor::new(threads);
+    let mut reports = vec![];
+    let dispatched =
+        Scheduler::default().run(&mut network.graph, &mut executor, |graph, completion| {
+            let nx = completion.nx;
+            let outcome = match completion.result {
+                Ok(node) => {
+                    graph
+                        .set_node_data(
+                            nx,
+                            NodeData {
+                                template: node.template,
+                                status: NodeStatus::Idle,
+                                run_time: Some(completion.run_time),
+                            },
+                        )
+                        .expect("nodes aren't deleted during a headless run");
+                    Outcome::Complete(completion.run_time)
+                }
+                Err(node_error) => {
+                    graph
+                        .get_mut_node(nx)
+                        .expect("nodes aren't deleted during a headless run")
+                        .status = NodeStatus::Error(node_error.clone());
+                    Outcome::Failed(node_error)
+                }
+            };
+            reports.push(NodeReport {
+                id: nx,
+                name: name(graph, nx),
+                outcome,
+            });
+            reports.extend(
+                completion
+                    .dispatched
+                    .skipped
+                    .into_iter()
+                    .map(|nx| skipped(graph, nx)),
+            );
+        });
+
+    // Nodes skipped before anything ran
+    RunReport(
+        dispatched
+            .skipped
+            .into_iter()
+            .map(|nx| skipped(&network.graph, nx))
+            .chain(reports)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use relative_path::RelativePathBuf;
+
+    use super::*;
+    use crate::{
+        nodes::{port::PortData, NodeTemplate, RustNode},
+        python::py_node::PyNode,
+    };
+
+    #[test]
+    fn report_failures() {
+        let mut network = Network::default();
+        let graph = &mut network.graph;
+        let constant = graph.node(NodeTemplate::RustNode(RustNode::Constant(0.5)).into());
+        let identity = graph.node(NodeTemplate::RustNode(RustNode::Identity).into());
+        let unconnected = graph.node(NodeTemplate::RustNode(RustNode::Identity).into());
+        // A python node whose source can't be found always fails
+        let missing = graph.node(
+            NodeTemplate::PyNode(PyNode::new(
+                "/does/not/exist.py".into(),
+                RelativePathBuf::from("exist.py"),
+            ))
+            .into(),
+        );
+        graph.connect((constant, "out"), (identity, "a")).unwrap();
+
+        let report = execute(&mut network);
+
+        assert!(!report.success());
+        let outcome = |id| {
+            report
+                .0
+                .iter()
+                .find(|r| r.id == id)
+                .map(|r| r.outcome.clone())
+                .unwrap()
+        };
+        assert!(matches!(outcome(constant), Outcome::Complete(_)));
+        assert!(matches!(outcome(identity), Outcome::Complete(_)));
+        assert_eq!(
+            outcome(unconnected),
+            Outcome::Skipped(vec!["a".to_string()])
+        );
+        assert!(matches!(outcome(missing), Outcome::Failed(_)));
+        assert!(matches!(
+            *network
+                .graph
+                .get_wire_data(&identity, "out")
+                .unwrap()
+                .read()
+                .unwrap(),
+            PortData::Real(0.5)
+        ));
+    }
+}
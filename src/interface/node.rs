@@ -28,16 +28,23 @@ pub fn default_node_size() -> iced::Size {
 }
 
 impl App {
-    pub fn node_content(&self, id: u32) -> Element<Message, Theme, Renderer> {
-        let node = self.network.graph.get_node(id);
+    pub fn node_content(&self, id: u32) -> Element<'_, Message, Theme, Renderer> {
+        let Some(node) = self.network.graph.get_node(id) else {
+            return text("").into();
+        };
         let is_selected = self.network.selected_shapes.contains(&id);
 
         let node_style = move |node: &NodeData, t: &Theme| {
             let color = match &node.status {
-                NodeStatus::Idle | NodeStatus::Running(_) => match is_selected {
+                NodeStatus::Idle | NodeStatus::Running(_) | NodeStatus::Cached => match is_selected
+                {
                     true => t.extended_palette().primary.strong.color,
                     false => t.extended_palette().secondary.strong.color,
                 },
+                NodeStatus::Waiting(_) => match is_selected {
+                    true => t.extended_palette().primary.weak.color,
+                    false => t.extended_palette().secondary.weak.color,
+                },
                 NodeStatus::Error(_node_error) => match is_selected {
                     true => t.extended_palette().danger.base.color,
                     false => t.extended_palette().danger.weak.color,
@@ -69,7 +76,12 @@ impl App {
         };
 
         //// Ports
-        let port_buttons = port_view(id, node, &self.app_theme);
+        let port_buttons = port_view(
+            id,
+            node,
+            self.network.graph.output_types(id),
+            &self.app_theme,
+        );
 
         //// Node
         let input_data = self.network.graph.get_input_data(&id);
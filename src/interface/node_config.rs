@@ -1,66 +1,732 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
 use derive_more::derive::{Debug, Display};
-use iced::Element;
 use iced::{widget::*, Alignment::Center};
+use iced::{Element, Length};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use strum::{EnumString, VariantNames};
+use strum::VariantNames;
 
 use crate::{
     app::Message,
     interface::numeric_input::{self, PartialUIValue},
+    nodes::status::NodeError,
     StableMap,
 };
 pub type NodeUIParameters = StableMap<String, NodeUIWidget>;
 
-#[derive(
-    Clone, Debug, Display, EnumString, VariantNames, Serialize, Deserialize, PartialEq, PartialOrd,
-)]
+/// The value of a parameter, as nodes receive it
+#[derive(Clone, Debug, Display, PartialEq)]
+pub enum ParameterValue {
+    Real(f64),
+    Integer(i64),
+    Bool(bool),
+    Text(String),
+    #[display("{}", _0.display())]
+    Path(PathBuf),
+    #[display("[{}]", _0.iter().join(", "))]
+    List(Vec<f64>),
+}
+
+impl ParameterValue {
+    /// Name of the value's type, as python calls it
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ParameterValue::Real(_) => f64::TYPE,
+            ParameterValue::Integer(_) => i64::TYPE,
+            ParameterValue::Bool(_) => bool::TYPE,
+            ParameterValue::Text(_) => String::TYPE,
+            ParameterValue::Path(_) => PathBuf::TYPE,
+            ParameterValue::List(_) => <Vec<f64>>::TYPE,
+        }
+    }
+}
+
+/// Types a parameter can be read as, see `Parameters::get_as`
+pub trait FromParameter: Sized {
+    /// Name of the type, as python calls it
+    const TYPE: &'static str;
+    fn from_parameter(value: &ParameterValue) -> Option<Self>;
+}
+
+impl FromParameter for f64 {
+    const TYPE: &'static str = "float";
+    fn from_parameter(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::Real(r) => Some(*r),
+            ParameterValue::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromParameter for i64 {
+    const TYPE: &'static str = "int";
+    fn from_parameter(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+impl FromParameter for bool {
+    const TYPE: &'static str = "bool";
+    fn from_parameter(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromParameter for String {
+    const TYPE: &'static str = "str";
+    fn from_parameter(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::Text(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromParameter for PathBuf {
+    const TYPE: &'static str = "path";
+    fn from_parameter(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::Path(path) => Some(path.clone()),
+            ParameterValue::Text(s) => Some(s.into()),
+            _ => None,
+        }
+    }
+}
+
+impl FromParameter for Vec<f64> {
+    const TYPE: &'static str = "list";
+    fn from_parameter(value: &ParameterValue) -> Option<Self> {
+        match value {
+            ParameterValue::List(list) => Some(list.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Typed access to a node's parameters, shared by python and rust nodes
+pub trait Parameters {
+    /// The value of parameter `name` as a `T`, or an error naming the parameter, what it holds,
+    /// and what was expected
+    fn get_as<T: FromParameter>(&self, name: &str) -> Result<T, NodeError>;
+}
+
+impl Parameters for NodeUIParameters {
+    fn get_as<T: FromParameter>(&self, name: &str) -> Result<T, NodeError> {
+        let widget = self
+            .get(name)
+            .ok_or_else(|| NodeError::Config(format!("Parameter '{name}' not found")))?;
+        let value = widget.value();
+        T::from_parameter(&value).ok_or_else(|| {
+            NodeError::Config(format!(
+                "Parameter '{name}' of the {} is {} ({value}), expected {}",
+                widget.kind(),
+                value.type_name(),
+                T::TYPE
+            ))
+        })
+    }
+}
+
+/// A rust node parameter as it is being edited. Rust nodes only keep the value of their
+/// parameters, so the app keeps the rest, such as a number that is still being typed
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterEdit {
+    pub node: u32,
+    pub name: String,
+    pub widget: NodeUIWidget,
+}
+
+/// Bounds of a numeric parameter, and the units it is shown in. Values are kept within the bounds
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[serde(default)]
+pub struct Limits<T> {
+    pub min: Option<T>,
+    pub max: Option<T>,
+    /// Increment of sliders and spinners
+    pub step: Option<T>,
+    pub units: String,
+}
+
+impl<T: PartialOrd + Copy> Limits<T> {
+    pub fn at_least(min: T) -> Self {
+        Self {
+            min: Some(min),
+            max: None,
+            step: None,
+            units: String::new(),
+        }
+    }
+
+    pub fn clamp(&self, value: T) -> T {
+        let value = match self.min {
+            Some(min) if value < min => min,
+            _ => value,
+        };
+        match self.max {
+            Some(max) if value > max => max,
+            _ => value,
+        }
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.clamp(value) == value
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Limits<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.min, &self.max) {
+            (Some(min), Some(max)) => write!(f, "from {min} to {max}"),
+            (Some(min), None) => write!(f, "of at least {min}"),
+            (None, Some(max)) => write!(f, "of at most {max}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// A node parameter, and how it is edited. Python nodes declare these with `gpi.ui`
+#[derive(Clone, Debug, Display, VariantNames, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[serde(from = "WidgetFormat", into = "WidgetFormat")]
 pub enum NodeUIWidget {
+    /// A real number between the limits, -1 to 1 unless they are set
+    #[display("{value}")]
+    Slider {
+        value: f64,
+        limits: Limits<f64>,
+        in_progress: PartialUIValue,
+    },
+    #[display("{value}")]
+    NumberField {
+        value: f64,
+        limits: Limits<f64>,
+        in_progress: PartialUIValue,
+    },
+    /// An integer, stepped up and down
+    #[display("{value}")]
+    Spinner {
+        value: i64,
+        limits: Limits<i64>,
+        in_progress: PartialUIValue,
+    },
+    /// One of a list of options
+    #[display("{value}")]
+    Dropdown { value: String, options: Vec<String> },
     #[display("{_0}")]
-    Slider(f32, #[serde(skip)] PartialUIValue),
+    Text(String),
+    /// A file path, and the extensions the file picker shows
+    #[display("{path}")]
+    File {
+        path: String,
+        extensions: Vec<String>,
+    },
     #[display("{_0}")]
-    NumberField(f32, #[serde(skip)] PartialUIValue),
     CheckBox(bool),
+    /// A fixed number of reals, e.g. a point
+    #[display("{}", value.iter().join(", "))]
+    Vector {
+        value: Vec<f64>,
+        limits: Limits<f64>,
+        in_progress: Vec<PartialUIValue>,
+    },
+}
+
+/// How widgets are saved. Sliders and number fields were saved as `Slider(0.5)` before they had
+/// limits, which ron can't read as a struct variant, so they stay tuples with the limits after
+/// the value, e.g. `Slider(0.5, (min: Some(0.0)))`
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "NodeUIWidget")]
+enum WidgetFormat {
+    Slider(f64, #[serde(default)] Limits<f64>),
+    NumberField(f64, #[serde(default)] Limits<f64>),
+    Spinner {
+        value: i64,
+        #[serde(default)]
+        limits: Limits<i64>,
+    },
+    Dropdown {
+        value: String,
+        options: Vec<String>,
+    },
+    Text(String),
+    File {
+        path: String,
+        #[serde(default)]
+        extensions: Vec<String>,
+    },
+    CheckBox(bool),
+    Vector {
+        value: Vec<f64>,
+        #[serde(default)]
+        limits: Limits<f64>,
+    },
+}
+
+impl From<WidgetFormat> for NodeUIWidget {
+    fn from(format: WidgetFormat) -> Self {
+        match format {
+            WidgetFormat::Slider(value, limits) => NodeUIWidget::Slider {
+                value,
+                limits,
+                in_progress: PartialUIValue::default(),
+            },
+            WidgetFormat::NumberField(value, limits) => NodeUIWidget::NumberField {
+                value,
+                limits,
+                in_progress: PartialUIValue::default(),
+            },
+            WidgetFormat::Spinner { value, limits } => NodeUIWidget::Spinner {
+                value,
+                limits,
+                in_progress: PartialUIValue::default(),
+            },
+            WidgetFormat::Dropdown { value, options } => NodeUIWidget::Dropdown { value, options },
+            WidgetFormat::Text(text) => NodeUIWidget::Text(text),
+            WidgetFormat::File { path, extensions } => NodeUIWidget::File { path, extensions },
+            WidgetFormat::CheckBox(checked) => NodeUIWidget::CheckBox(checked),
+            WidgetFormat::Vector { value, limits } => NodeUIWidget::Vector {
+                value,
+                limits,
+                in_progress: vec![],
+            },
+        }
+    }
+}
+
+impl From<NodeUIWidget> for WidgetFormat {
+    fn from(widget: NodeUIWidget) -> Self {
+        match widget {
+            NodeUIWidget::Slider { value, limits, .. } => WidgetFormat::Slider(value, limits),
+            NodeUIWidget::NumberField { value, limits, .. } => {
+                WidgetFormat::NumberField(value, limits)
+            }
+            NodeUIWidget::Spinner { value, limits, .. } => WidgetFormat::Spinner { value, limits },
+            NodeUIWidget::Dropdown { value, options } => WidgetFormat::Dropdown { value, options },
+            NodeUIWidget::Text(text) => WidgetFormat::Text(text),
+            NodeUIWidget::File { path, extensions } => WidgetFormat::File { path, extensions },
+            NodeUIWidget::CheckBox(checked) => WidgetFormat::CheckBox(checked),
+            NodeUIWidget::Vector { value, limits, .. } => WidgetFormat::Vector { value, limits },
+        }
+    }
+}
+
+/// Widgets named by their kind alone, as in `parameters = {"d": ui.Slider}`, with default settings
+impl FromStr for NodeUIWidget {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "Slider" => NodeUIWidget::Slider {
+                value: 0.,
+                limits: Limits {
+                    min: Some(-1.),
+                    max: Some(1.),
+                    step: Some(0.01),
+                    units: String::new(),
+                },
+                in_progress: PartialUIValue::Complete,
+            },
+            "NumberField" => NodeUIWidget::NumberField {
+                value: 0.,
+                limits: Limits::default(),
+                in_progress: PartialUIValue::Complete,
+            },
+            "Spinner" => NodeUIWidget::Spinner {
+                value: 0,
+                limits: Limits::default(),
+                in_progress: PartialUIValue::Complete,
+            },
+            "Text" => NodeUIWidget::Text(String::new()),
+            "File" => NodeUIWidget::File {
+                path: String::new(),
+                extensions: vec![],
+            },
+            "CheckBox" => NodeUIWidget::CheckBox(false),
+            "Vector" => NodeUIWidget::Vector {
+                value: vec![0., 0.],
+                limits: Limits::default(),
+                in_progress: vec![],
+            },
+            _ => return Err(()),
+        })
+    }
 }
 
 impl NodeUIWidget {
-    pub fn view<'a, F>(&'a self, update_message: F) -> Element<'a, Message>
-    where
-        F: Fn(NodeUIWidget) -> Message + Clone + 'a,
-    {
-        // Needs 2 of these for borrow checker (is there a cleaner way?)
-        let update_message_2 = update_message.clone();
+    pub fn number(value: f64, limits: Limits<f64>) -> Self {
+        NodeUIWidget::NumberField {
+            value,
+            limits,
+            in_progress: PartialUIValue::Complete,
+        }
+    }
+
+    pub fn spinner(value: i64, limits: Limits<i64>) -> Self {
+        NodeUIWidget::Spinner {
+            value,
+            limits,
+            in_progress: PartialUIValue::Complete,
+        }
+    }
+
+    pub fn vector(value: Vec<f64>, limits: Limits<f64>) -> Self {
+        NodeUIWidget::Vector {
+            value,
+            limits,
+            in_progress: vec![],
+        }
+    }
+
+    /// Name of the kind of widget, for labelling edits
+    pub fn kind(&self) -> &'static str {
         match self {
-            NodeUIWidget::Slider(v, in_progress) => row![
-                row![numeric_input::numeric_input(
-                    *v,
-                    in_progress.clone(),
-                    move |new_v, in_progress: PartialUIValue| {
-                        update_message(Self::Slider(new_v, in_progress))
-                    },
-                )]
-                .width(60.0),
-                slider(-1.0..=1.0, *v, move |new_v| {
-                    update_message_2(Self::Slider(new_v, PartialUIValue::Complete))
-                })
-                .step(0.01)
+            NodeUIWidget::Slider { .. } => "slider",
+            NodeUIWidget::NumberField { .. } => "number field",
+            NodeUIWidget::Spinner { .. } => "spinner",
+            NodeUIWidget::Dropdown { .. } => "dropdown",
+            NodeUIWidget::Text(_) => "text field",
+            NodeUIWidget::File { .. } => "file",
+            NodeUIWidget::CheckBox(_) => "checkbox",
+            NodeUIWidget::Vector { .. } => "vector",
+        }
+    }
+
+    /// The value nodes receive
+    pub fn value(&self) -> ParameterValue {
+        match self {
+            NodeUIWidget::Slider { value, .. } | NodeUIWidget::NumberField { value, .. } => {
+                ParameterValue::Real(*value)
+            }
+            NodeUIWidget::Spinner { value, .. } => ParameterValue::Integer(*value),
+            NodeUIWidget::Dropdown { value, .. } | NodeUIWidget::Text(value) => {
+                ParameterValue::Text(value.clone())
+            }
+            NodeUIWidget::File { path, .. } => ParameterValue::Path(path.into()),
+            NodeUIWidget::CheckBox(value) => ParameterValue::Bool(*value),
+            NodeUIWidget::Vector { value, .. } => ParameterValue::List(value.clone()),
+        }
+    }
+
+    /// The widget as it is declared now, keeping the value of `old` if it is the same kind of
+    /// widget and the value is still allowed
+    pub fn keep_value(&self, old: &NodeUIWidget) -> NodeUIWidget {
+        let mut widget = self.clone();
+        match (&mut widget, old) {
+            (
+                NodeUIWidget::Slider { value, limits, .. },
+                NodeUIWidget::Slider { value: old, .. },
+            )
+            | (
+                NodeUIWidget::NumberField { value, limits, .. },
+                NodeUIWidget::NumberField { value: old, .. },
+            ) => *value = limits.clamp(*old),
+            (
+                NodeUIWidget::Spinner { value, limits, .. },
+                NodeUIWidget::Spinner { value: old, .. },
+            ) => *value = limits.clamp(*old),
+            (
+                NodeUIWidget::Dropdown { value, options },
+                NodeUIWidget::Dropdown { value: old, .. },
+            ) if options.contains(old) => value.clone_from(old),
+            (NodeUIWidget::Text(value), NodeUIWidget::Text(old)) => value.clone_from(old),
+            (NodeUIWidget::File { path, .. }, NodeUIWidget::File { path: old, .. }) => {
+                path.clone_from(old)
+            }
+            (NodeUIWidget::CheckBox(value), NodeUIWidget::CheckBox(old)) => *value = *old,
+            (
+                NodeUIWidget::Vector { value, limits, .. },
+                NodeUIWidget::Vector { value: old, .. },
+            ) if value.len() == old.len() => {
+                *value = old.iter().map(|v| limits.clamp(*v)).collect()
+            }
+            _ => {}
+        }
+        widget
+    }
+
+    /// The widget with the numeric value at `index` changed. A number outside the limits is
+    /// invalid input, like text that isn't a number: the value is kept, and the text is shown as
+    /// invalid until it is corrected. Integers are rounded, and widgets without numbers are
+    /// unchanged
+    pub fn with_number(&self, index: usize, number: f64, in_progress: PartialUIValue) -> Self {
+        let invalid = |in_progress| match in_progress {
+            PartialUIValue::Complete => PartialUIValue::UnParsable(number.to_string()),
+            PartialUIValue::Parsable(text) | PartialUIValue::UnParsable(text) => {
+                PartialUIValue::UnParsable(text)
+            }
+        };
+        let mut widget = self.clone();
+        match &mut widget {
+            NodeUIWidget::Slider {
+                value,
+                limits,
+                in_progress: partial,
+            }
+            | NodeUIWidget::NumberField {
+                value,
+                limits,
+                in_progress: partial,
+            } => match limits.contains(number) {
+                true => (*value, *partial) = (number, in_progress),
+                false => *partial = invalid(in_progress),
+            },
+            NodeUIWidget::Spinner {
+                value,
+                limits,
+                in_progress: partial,
+            } => match number.round() as i64 {
+                number if limits.contains(number) => (*value, *partial) = (number, in_progress),
+                _ => *partial = invalid(in_progress),
+            },
+            NodeUIWidget::Vector {
+                value,
+                limits,
+                in_progress: partial,
+            } if index < value.len() => {
+                partial.resize(value.len(), PartialUIValue::Complete);
+                match limits.contains(number) {
+                    true => (value[index], partial[index]) = (number, in_progress),
+                    false => partial[index] = invalid(in_progress),
+                }
+            }
+            _ => {}
+        }
+        widget
+    }
+
+    /// Edit the parameter `name` of node `id`. The element doesn't borrow the widget, so it can
+    /// be made from parameters that are built for the view
+    pub fn view<'a>(&self, id: u32, name: &str) -> Element<'a, Message> {
+        let name = name.to_string();
+        let pick_file = Message::PickParameterFile(id, name.clone());
+        let update = move |widget| Message::UpdateNodeParameter(id, name.clone(), widget);
+        let units = |limits_units: &str| text(limits_units.to_string()).size(12.);
+        let number_input = |number: f64, in_progress: PartialUIValue, index: usize| {
+            let update = update.clone();
+            let widget = self.clone();
+            numeric_input::numeric_input(number, in_progress, move |number, in_progress| {
+                update(widget.with_number(index, number, in_progress))
+            })
+        };
+
+        match self {
+            NodeUIWidget::Slider {
+                value,
+                limits,
+                in_progress,
+            } => {
+                let widget = self.clone();
+                row![
+                    row![number_input(*value, in_progress.clone(), 0)].width(60.0),
+                    slider(
+                        limits.min.unwrap_or(-1.)..=limits.max.unwrap_or(1.),
+                        *value,
+                        move |number| update(widget.with_number(
+                            0,
+                            number,
+                            PartialUIValue::Complete
+                        ))
+                    )
+                    .step(limits.step.unwrap_or(0.01)),
+                    units(&limits.units),
+                ]
+                .align_y(Center)
+                .spacing(4.0)
+                .into()
+            }
+            NodeUIWidget::NumberField {
+                value,
+                limits,
+                in_progress,
+            } => row![
+                horizontal_space(),
+                row![number_input(*value, in_progress.clone(), 0)].width(60.0),
+                units(&limits.units),
             ]
             .align_y(Center)
             .spacing(4.0)
             .into(),
-            NodeUIWidget::NumberField(v, in_progress) => row![
+            NodeUIWidget::Spinner {
+                value,
+                limits,
+                in_progress,
+            } => {
+                let step = limits.step.unwrap_or(1);
+                let stepped = |by: i64| {
+                    let stepped = value.saturating_add(by);
+                    limits.contains(stepped).then(|| {
+                        update(self.with_number(0, stepped as f64, PartialUIValue::Complete))
+                    })
+                };
+                row![
+                    horizontal_space(),
+                    button(text("-").size(12.))
+                        .padding([0, 4])
+                        .style(button::secondary)
+                        .on_press_maybe(stepped(-step)),
+                    row![number_input(*value as f64, in_progress.clone(), 0)].width(50.0),
+                    button(text("+").size(12.))
+                        .padding([0, 4])
+                        .style(button::secondary)
+                        .on_press_maybe(stepped(step)),
+                    units(&limits.units),
+                ]
+                .align_y(Center)
+                .spacing(4.0)
+                .into()
+            }
+            NodeUIWidget::Dropdown { value, options } => {
+                let choices = options.clone();
+                row![
+                    horizontal_space(),
+                    pick_list(options.clone(), Some(value.clone()), move |value| {
+                        update(NodeUIWidget::Dropdown {
+                            value,
+                            options: choices.clone(),
+                        })
+                    })
+                    .text_size(12.)
+                    .padding([1, 4]),
+                ]
+                .into()
+            }
+            NodeUIWidget::Text(value) => text_input("", value)
+                .on_input(move |value| update(NodeUIWidget::Text(value)))
+                .padding([1.0, 3.0])
+                .into(),
+            NodeUIWidget::File { path, extensions } => {
+                let extensions = extensions.clone();
+                row![
+                    text_input("path", path)
+                        .on_input(move |path| update(NodeUIWidget::File {
+                            path,
+                            extensions: extensions.clone(),
+                        }))
+                        .padding([1.0, 3.0]),
+                    button(text("…").size(12.))
+                        .padding([1, 4])
+                        .style(button::secondary)
+                        .on_press(pick_file),
+                ]
+                .align_y(Center)
+                .spacing(4.0)
+                .into()
+            }
+            NodeUIWidget::CheckBox(value) => row![
                 horizontal_space(),
-                row![numeric_input::numeric_input(
-                    *v,
-                    in_progress.clone(),
-                    move |new_v, in_progress: PartialUIValue| {
-                        update_message(Self::NumberField(new_v, in_progress))
-                    },
-                )]
-                .width(60.0)
+                checkbox("", *value).on_toggle(move |value| update(NodeUIWidget::CheckBox(value)))
             ]
+            .into(),
+            NodeUIWidget::Vector {
+                value,
+                limits,
+                in_progress,
+            } => row(value
+                .iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    let in_progress = in_progress.get(i).cloned().unwrap_or_default();
+                    number_input(*v, in_progress, i)
+                })
+                .chain([units(&limits.units).into()]))
             .align_y(Center)
+            .spacing(2.0)
             .into(),
-            NodeUIWidget::CheckBox(_v) => todo!(),
         }
     }
 }
+
+/// Edit every parameter of node `id`, one row each
+pub fn parameters_view<'a>(id: u32, parameters: &NodeUIParameters) -> Element<'a, Message> {
+    column(parameters.iter().map(|(name, widget)| {
+        row![text(name.clone()), widget.view(id, name)]
+            .spacing(8.0)
+            .align_y(Center)
+            .width(Length::Fill)
+            .into()
+    }))
+    .spacing(8.)
+    .width(Length::Fill)
+    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn typed_access() {
+        let parameters = NodeUIParameters::from([
+            ("n".into(), "Spinner".parse().unwrap()),
+            ("path".into(), "File".parse().unwrap()),
+        ]);
+        assert_eq!(parameters.get_as::<i64>("n"), Ok(0));
+        assert_eq!(parameters.get_as::<f64>("n"), Ok(0.));
+        assert_eq!(parameters.get_as::<PathBuf>("path"), Ok(PathBuf::new()));
+        assert!(matches!(
+            parameters.get_as::<bool>("n"),
+            Err(NodeError::Config(e)) if e.contains("spinner is int (0), expected bool")
+        ));
+        assert!(parameters.get_as::<f64>("missing").is_err());
+    }
+
+    #[test]
+    fn legacy_widgets() {
+        use crate::{network::Network, nodes::NodeTemplate};
+
+        // A network saved before widgets had limits
+        let saved = r#"(
+            graph: (
+                nodes: {
+                    0: (template: RustNode(Constant(1.0))),
+                    1: (
+                        template: PyNode((
+                            name: "scale",
+                            relative_path: "math/scale.py",
+                            ports: Ok((inputs: {"a": ArrayReal}, outputs: {"out": ArrayReal})),
+                            parameters: Ok({
+                                "enabled": CheckBox(true),
+                                "gain": Slider(0.5),
+                                "offset": NumberField(-2.0),
+                            }),
+                        )),
+                    ),
+                },
+                edges: [],
+                next_id: 2,
+            ),
+            shapes: (
+                camera: (position: (x: 0.0, y: 0.0), zoom: 1.0),
+                shape_positions: {0: (x: 0.0, y: 0.0), 1: (x: 0.0, y: 200.0)},
+            ),
+        )"#;
+        let network: Network = ron::from_str(saved).unwrap();
+        let Some(NodeTemplate::PyNode(node)) = network.graph.get_node(1).map(|n| &n.template)
+        else {
+            panic!("expected a python node");
+        };
+        let parameters = node.parameters.as_ref().unwrap();
+        assert!(matches!(
+            &parameters["gain"],
+            NodeUIWidget::Slider { value, limits, .. } if *value == 0.5 && *limits == Limits::default()
+        ));
+        assert_eq!(parameters.get_as::<f64>("offset"), Ok(-2.));
+        assert_eq!(parameters.get_as::<bool>("enabled"), Ok(true));
+
+        // Limits are saved after the value
+        let slider = NodeUIWidget::Slider {
+            value: 0.25,
+            limits: Limits::at_least(0.),
+            in_progress: PartialUIValue::default(),
+        };
+        let saved = ron::to_string(&slider).unwrap();
+        assert!(saved.starts_with("Slider(0.25,"));
+        assert_eq!(ron::from_str::<NodeUIWidget>(&saved), Ok(slider));
+    }
+}
@@ -4,6 +4,7 @@ use iced::{
     Alignment::Center,
     Color, Element,
     Length::{Fill, Shrink},
+    Theme,
 };
 
 use crate::app::Message;
@@ -23,13 +24,14 @@ pub enum PartialUIValue {
 /// value, for example -0 cannot be immediatley saved in the data model as `0` because then "0"
 /// would be dispayed to the user, which would be frustring if trying to enter -0.1
 pub fn numeric_input<'a, F>(
-    value: f32,
+    value: f64,
     in_progress_widget_string: PartialUIValue,
     update_message: F,
 ) -> Element<'a, Message>
 where
-    F: Fn(f32, PartialUIValue) -> Message + 'a,
+    F: Fn(f64, PartialUIValue) -> Message + 'a,
 {
+    let invalid = matches!(in_progress_widget_string, PartialUIValue::UnParsable(_));
     column![text_input(
         "n/a",
         &match &in_progress_widget_string {
@@ -39,7 +41,7 @@ where
         }
     )
     .on_input(
-        move |new_value: String| if let Ok(parsed) = new_value.parse::<f32>() {
+        move |new_value: String| if let Ok(parsed) = new_value.parse::<f64>() {
             if parsed.to_string() == new_value {
                 update_message(parsed, PartialUIValue::Complete)
             } else {
@@ -49,6 +51,17 @@ where
             update_message(value, PartialUIValue::UnParsable(new_value))
         }
     )
+    .style(move |theme: &Theme, status| {
+        let style = text_input::default(theme, status);
+        if invalid {
+            text_input::Style {
+                border: style.border.color(theme.palette().danger),
+                ..style
+            }
+        } else {
+            style
+        }
+    })
     .align_x(Right)
     .padding([1.0, 3.0])]
     .height(Shrink)
@@ -78,7 +91,7 @@ pub fn styled_text_input(input: TextInput<Message>) -> Element<Message> {
     .into()
 }
 
-fn fp_round(x: f32) -> f32 {
-    let y = 10i32.pow(6) as f32;
+fn fp_round(x: f64) -> f64 {
+    let y = 10i32.pow(6) as f64;
     (x * y).round() / y
 }
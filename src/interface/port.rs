@@ -15,13 +15,16 @@ use crate::{
     nodes::{port::PortType, NodeData},
     style::theme::AppTheme,
     widget::{custom_button, pin::Pin},
+    StableMap,
 };
 
 use super::node::{INNER_NODE_HEIGHT, INNER_NODE_WIDTH, NODE_RADIUS, PORT_RADIUS};
 
+/// `outputs` are the output types of the node, given what is connected to its inputs
 pub fn port_view<'a>(
     node_id: u32,
     node_data: &NodeData,
+    outputs: StableMap<String, PortType>,
     app_theme: &'a AppTheme,
 ) -> Vec<Element<'a, Message>> {
     let port_x = |i: usize| i as f32 * (INNER_NODE_WIDTH / 4.) + NODE_RADIUS * 2.;
@@ -61,8 +64,7 @@ pub fn port_view<'a>(
             .position(point)
             .into()
         });
-    let out_port_buttons = node_data
-        .outputs()
+    let out_port_buttons = outputs
         .into_iter()
         .enumerate()
         .map(|(i, port)| (Point::new(port_x(i), INNER_NODE_HEIGHT - PORT_RADIUS), port))
@@ -120,31 +122,47 @@ fn port_color_pair(port_type: &PortType, app_theme: &AppTheme) -> (iced::Color,
             app_theme.primary.weak_color().into(),
         ),
         PortType::Complex => (color!(102, 128, 11), color!(135, 154, 57)), //green
-        PortType::ArrayInteger => (color!(175, 48, 41), color!(209, 77, 65)), //red
-        PortType::ArrayReal => (color!(32, 94, 166), color!(67, 133, 190)), //blue
-        PortType::ArrayComplex => (color!(36, 131, 123), color!(58, 169, 159)), //cyan
+        PortType::ArrayInteger
+        | PortType::ArrayInteger32
+        | PortType::ArrayUnsigned8
+        | PortType::ArrayUnsigned16 => (color!(175, 48, 41), color!(209, 77, 65)), //red
+        PortType::ArrayBool => (color!(94, 64, 157), color!(139, 126, 200)), //purple
+        PortType::ArrayReal | PortType::ArrayReal32 => (color!(32, 94, 166), color!(67, 133, 190)), //blue
+        PortType::ArrayComplex | PortType::ArrayComplex32 => {
+            (color!(36, 131, 123), color!(58, 169, 159)) //cyan
+        }
         PortType::Dynamic => (color!(175, 125, 41), color!(209, 150, 65)), //orange
         PortType::Object(_) => (color!(200, 160, 41), color!(229, 180, 65)), //yellow
     }
 }
 
-/// Stylized port text
+/// Stylized port text, followed by the numpy dtype of array ports
 fn port_text<'a>(
     port_name: String,
     port_type: &PortType,
     app_theme: &AppTheme,
 ) -> Row<'a, Message> {
+    let dtype = port_type
+        .array_dtype()
+        .map(|dtype| dtype.to_string())
+        .unwrap_or_default();
     row![
         text(port_name),
         rich_text([span(port_type.to_string())
             .background(port_color_pair(port_type, app_theme).0)
             .border(Border::default().rounded(4))
-            .padding([0, 2])])
+            .padding([0, 2])]),
+        text(dtype).size(12).color(app_theme.text.weak_color()),
     ]
+    .align_y(Center)
 }
 
 /// Display summary of port information
-fn port_tooltip(port_name: String, port_type: PortType, app_theme: &AppTheme) -> Element<Message> {
+fn port_tooltip(
+    port_name: String,
+    port_type: PortType,
+    app_theme: &AppTheme,
+) -> Element<'_, Message> {
     port_tooltip_recurse(port_name, port_type, app_theme, true)
 }
 
@@ -153,7 +171,7 @@ fn port_tooltip_recurse(
     port_type: PortType,
     app_theme: &AppTheme,
     even: bool, // Switch between 2 background colors as objects are nested
-) -> Element<Message> {
+) -> Element<'_, Message> {
     let port_type_display = match port_type {
         // Recursive case
         PortType::Object(fields) => row![
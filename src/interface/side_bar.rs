@@ -2,6 +2,8 @@ use crate::app::{App, Message};
 use crate::gui_node::GUINode;
 use crate::interface::node::format_node_output;
 use crate::interface::{debug_format, SEPERATOR};
+use crate::network::history::Edit;
+use crate::network::variants::Difference;
 use crate::style::button::{primary_icon, secondary_icon};
 use crate::style::icon::icon;
 use iced::*;
@@ -17,13 +19,24 @@ pub fn side_bar(app: &App) -> Element<'_, Message> {
     }
     fn undo_button<'a>(
         lbl: impl Into<String>,
-        enabled: bool,
+        edit: Option<&Edit>,
         message: Message,
-    ) -> Button<'a, Message> {
-        button(icon(lbl.into()))
-            .on_press_maybe(if enabled { Some(message) } else { None })
+    ) -> Element<'a, Message> {
+        let button = button(icon(lbl.into()))
+            .on_press_maybe(edit.map(|_| message))
             .padding(0.0)
-            .style(secondary_icon)
+            .style(secondary_icon);
+        match edit {
+            Some(edit) => tooltip(
+                button,
+                container(text(edit.to_string()).size(12.))
+                    .padding([1, 4])
+                    .style(container::bordered_box),
+                tooltip::Position::Bottom,
+            )
+            .into(),
+            None => button.into(),
+        }
     }
 
     //''
@@ -37,60 +50,203 @@ pub fn side_bar(app: &App) -> Element<'_, Message> {
         file_button('󰆓', Message::Save),
         file_button('󰃤', Message::ToggleDebug),
         file_button('󰏘', Message::TogglePaletteUI),
+        file_button(
+            if app.network.scheduler.is_paused() {
+                '󰐊'
+            } else {
+                '󰏤'
+            },
+            Message::TogglePause
+        ),
     ]
     .spacing(3.0);
 
     let undo = undo_button(
-        debug_format(&app.debug, '', app.network.undo_stack.len()),
-        !app.network.undo_stack.is_empty(),
+        debug_format(&app.debug, '', app.network.history.undo_len()),
+        app.network.history.next_undo(),
         Message::Undo,
     );
     let redo = undo_button(
-        debug_format(&app.debug, '', app.network.redo_stack.len()),
-        !app.network.redo_stack.is_empty(),
+        debug_format(&app.debug, '', app.network.history.redo_len()),
+        app.network.history.next_redo(),
         Message::Redo,
     );
     let action_commands = row![horizontal_space(), undo, redo].spacing(4.0);
 
     //// Config
-    let config: Element<Message> =
-        if let Some(selected_id) = app.network.selected_shapes.iter().next() {
-            let node = app.network.graph.get_node(*selected_id);
-            let input_data = app.network.graph.get_input_data(selected_id);
-            let out_port_display: Element<Message> = if app.debug {
-                column![format_node_output(
-                    node,
-                    &app.network.graph.get_output_data(*selected_id)
-                )]
-                .into()
+    let config: Element<Message> = if let Some((selected_id, node)) = app
+        .network
+        .selected_shapes
+        .iter()
+        .next()
+        .and_then(|id| Some((id, app.network.graph.get_node(*id)?)))
+    {
+        let input_data = app.network.graph.get_input_data(selected_id);
+        let scheduler = &app.network.scheduler;
+        let cancel_button: Element<Message> =
+            if scheduler.is_running(*selected_id) || scheduler.is_queued(*selected_id) {
+                button(text("cancel").size(12.))
+                    .style(button::secondary)
+                    .padding([1, 4])
+                    .on_press(Message::CancelCompute(*selected_id))
+                    .into()
             } else {
                 text("").into()
             };
-            column![
-                container(text(node.template.name().clone()).size(20.)).center_x(Fill),
-                horizontal_rule(0),
-                row![node.status.icon(), node.status.text_element().size(12.)]
-                    .align_y(Center)
-                    .spacing(4.0),
-                vertical_space().height(10.),
-                node.template
-                    .config_view(*selected_id, input_data)
-                    .unwrap_or(text("...").into()),
-                vertical_space(),
-                scrollable(out_port_display),
-                row![button(text("delete node"))
-                    .style(button::danger)
-                    .padding([1, 4])
-                    .on_press(Message::DeleteSelectedNodes)]
-            ]
-            .align_x(Center)
-            .height(Fill)
-            .spacing(5.)
-            .padding([10., 5.])
+        let out_port_display: Element<Message> = if app.debug {
+            column![format_node_output(
+                node,
+                &app.network.graph.get_output_data(*selected_id)
+            )]
             .into()
         } else {
             text("").into()
         };
+        column![
+            container(text(node.template.name().clone()).size(20.)).center_x(Fill),
+            horizontal_rule(0),
+            row![
+                node.status.icon(),
+                node.status.text_element().size(12.),
+                horizontal_space(),
+                cancel_button
+            ]
+            .align_y(Center)
+            .spacing(4.0),
+            vertical_space().height(10.),
+            node.template
+                .config_view(*selected_id, input_data, app.parameter_edit.as_ref())
+                .unwrap_or(text("...").into()),
+            vertical_space(),
+            scrollable(out_port_display),
+            row![
+                button(text("group nodes"))
+                    .style(button::secondary)
+                    .padding([1, 4])
+                    .on_press(Message::GroupSelectedNodes),
+                button(text("delete node"))
+                    .style(button::danger)
+                    .padding([1, 4])
+                    .on_press(Message::DeleteSelectedNodes)
+            ]
+            .spacing(4.)
+        ]
+        .align_x(Center)
+        .height(Fill)
+        .spacing(5.)
+        .padding([10., 5.])
+        .into()
+    } else {
+        text("").into()
+    };
+    //// Open groups
+    let group_path = app.network.group_path();
+    let groups: Element<Message> = if group_path.is_empty() {
+        column![].into()
+    } else {
+        column![
+            row![
+                text(
+                    ["network"]
+                        .into_iter()
+                        .chain(group_path)
+                        .collect::<Vec<_>>()
+                        .join(" › ")
+                )
+                .size(12.),
+                horizontal_space(),
+                button(text("close group").size(12.))
+                    .style(button::secondary)
+                    .padding([1, 4])
+                    .on_press(Message::CloseGroup)
+            ]
+            .align_y(Center)
+            .padding([2., 4.]),
+            horizontal_rule(SEPERATOR),
+        ]
+        .into()
+    };
+
+    //// Variants
+    let variants = &app.network.variants;
+    let names = variants.names();
+    let others: Vec<String> = names
+        .iter()
+        .filter(|name| *name != variants.current())
+        .cloned()
+        .collect();
+    let variant_row = row![
+        text("variant").size(12.),
+        pick_list(
+            names,
+            Some(variants.current().to_string()),
+            Message::SwitchVariant
+        )
+        .text_size(12.)
+        .padding([1, 4]),
+        horizontal_space(),
+        button(text("fork").size(12.))
+            .style(button::secondary)
+            .padding([1, 4])
+            .on_press(Message::ForkVariant),
+    ]
+    .align_y(Center)
+    .spacing(4.);
+    let comparison: Element<Message> = match &variants.comparing {
+        Some(other) => {
+            let differences = app.network.variant_diff(other);
+            let side = |s: String| text(s).size(12.).width(Fill);
+            column![
+                row![
+                    side(variants.current().to_string()),
+                    side(other.clone()),
+                    button(text("×").size(12.))
+                        .style(button::text)
+                        .padding(0)
+                        .on_press(Message::CompareVariant(None)),
+                ],
+                horizontal_rule(0),
+                scrollable(
+                    column(differences.iter().map(|d| {
+                        let (left, right) = sides(d);
+                        row![side(left), side(right)].spacing(4.).into()
+                    }))
+                    .spacing(2.)
+                )
+                .height(Length::Shrink),
+                text(if differences.is_empty() {
+                    "no differences"
+                } else {
+                    ""
+                })
+                .size(12.),
+                row![
+                    button(text("apply").size(12.))
+                        .padding([1, 4])
+                        .on_press(Message::ApplyVariant(other.clone())),
+                    button(text("delete").size(12.))
+                        .style(button::danger)
+                        .padding([1, 4])
+                        .on_press(Message::DeleteVariant(other.clone())),
+                ]
+                .spacing(4.),
+            ]
+            .spacing(4.)
+            .into()
+        }
+        None if !others.is_empty() => pick_list(others, None::<String>, |name| {
+            Message::CompareVariant(Some(name))
+        })
+        .placeholder("compare with...")
+        .text_size(12.)
+        .padding([1, 4])
+        .into(),
+        None => column![].into(),
+    };
+    let variants = column![variant_row, comparison]
+        .spacing(4.)
+        .padding([2., 4.]);
+
     container(
         column![
             row![
@@ -102,6 +258,9 @@ pub fn side_bar(app: &App) -> Element<'_, Message> {
             ]
             .padding([2., 4.]),
             horizontal_rule(SEPERATOR),
+            variants,
+            horizontal_rule(SEPERATOR),
+            groups,
             //// Config
             config
         ]
@@ -110,3 +269,33 @@ pub fn side_bar(app: &App) -> Element<'_, Message> {
     )
     .into()
 }
+
+/// A difference as it appears in each of the compared networks
+fn sides(difference: &Difference) -> (String, String) {
+    match difference {
+        Difference::Added { nx, name } => (String::new(), format!("+ {name} #{nx}")),
+        Difference::Removed { nx, name } => (format!("- {name} #{nx}"), String::new()),
+        Difference::Changed {
+            nx,
+            name,
+            parameters,
+        } if !parameters.is_empty() => {
+            let values = |value: fn(&(String, String, String)) -> &String| {
+                parameters
+                    .iter()
+                    .map(|p| format!("{}={}", p.0, value(p)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            (
+                format!("{name} #{nx}: {}", values(|p| &p.1)),
+                format!("{name} #{nx}: {}", values(|p| &p.2)),
+            )
+        }
+        Difference::Changed { nx, name, .. } => {
+            (format!("{name} #{nx}"), format!("~ {name} #{nx}"))
+        }
+        Difference::Connected(..) => (String::new(), difference.to_string()),
+        Difference::Disconnected(..) => (difference.to_string(), String::new()),
+    }
+}
@@ -8,36 +8,64 @@ use canvas::{Path, Stroke};
 use iced::widget::*;
 
 impl App {
+    /// Why the wire being dragged can't connect to the port under the cursor, if it can't
+    pub fn tentative_wire_error(&self) -> Option<GraphError> {
+        match &self.action {
+            Action::CreatingInputWire(input, Some(output))
+            | Action::CreatingOutputWire(output, Some(input)) => {
+                self.network.graph.check_edge(output, input).err()
+            }
+            _ => None,
+        }
+    }
+
     pub fn wire_curve(
         &self,
         wire_end_node: u32,
         points: &StableMap<u32, Point>,
-    ) -> Vec<(Path, Stroke)> {
+    ) -> Vec<(Path, Stroke<'_>)> {
+        // `None` for ports that don't exist, whose wires aren't drawn
         let port_position = |port: &PortRef| {
-            points[&port.node] + find_port_offset(port, self.network.graph.port_index(port)).into()
+            let port_index = self.network.graph.port_index(port).ok()?;
+            Some(*points.get(&port.node)? + find_port_offset(port, port_index).into())
         };
 
         //// Handle currently active wire
         // TODO: test nodes with multiple out ports
+        let rejected = self.tentative_wire_error().is_some();
+        let converts = match &self.action {
+            Action::CreatingInputWire(input, Some(output))
+            | Action::CreatingOutputWire(output, Some(input)) => {
+                self.network.graph.edge_converts(output, input)
+            }
+            _ => false,
+        };
+        let tentative_stroke = if rejected {
+            rejected_wire_stroke(&self.app_theme)
+        } else if converts {
+            converted_wire_stroke(&self.app_theme)
+        } else {
+            active_wire_stroke(&self.app_theme, true)
+        };
         let active_wire = match &self.action {
             Action::CreatingInputWire(input, Some(tentative_output)) => Some((
                 (port_position(input), port_position(tentative_output)),
-                active_wire_stroke(&self.app_theme, true),
+                tentative_stroke,
             )),
             Action::CreatingInputWire(input, None) => Some((
                 (
                     port_position(input),
-                    self.cursor_position + self.network.shapes.camera.position,
+                    Some(self.cursor_position + self.network.shapes.camera.position),
                 ),
                 active_wire_stroke(&self.app_theme, false),
             )),
             Action::CreatingOutputWire(output, Some(input)) => Some((
                 (port_position(input), port_position(output)),
-                active_wire_stroke(&self.app_theme, true),
+                tentative_stroke,
             )),
             Action::CreatingOutputWire(output, None) => Some((
                 (
-                    self.cursor_position + self.network.shapes.camera.position,
+                    Some(self.cursor_position + self.network.shapes.camera.position),
                     port_position(output),
                 ),
                 active_wire_stroke(&self.app_theme, false),
@@ -50,11 +78,15 @@ impl App {
         incoming_wires
             .iter()
             .map(|(from, to)| {
-                let stroke = wire_status(from, to, &self.action, &self.app_theme);
+                let converts = self.network.graph.edge_converts(from, to);
+                let stroke =
+                    wire_status(from, to, &self.action, rejected, converts, &self.app_theme);
                 ((port_position(to), port_position(from)), stroke)
             })
             //// include the active wire
             .chain(once(active_wire).flatten())
+            //// skip wires to ports that don't exist
+            .filter_map(|((from, to), stroke)| Some(((from?, to?), stroke)))
             //// build the wire curves
             .map(|((from, to), stroke)| {
                 (
@@ -77,7 +109,7 @@ impl App {
 use super::node::{INNER_NODE_HEIGHT, INNER_NODE_WIDTH, NODE_RADIUS, PORT_RADIUS};
 use crate::{
     app,
-    graph::{PortRef, IO},
+    graph::{GraphError, PortRef, IO},
 };
 use iced::Vector;
 
@@ -95,11 +127,15 @@ pub fn find_port_offset(port_ref: &PortRef, port_index: usize) -> Vector {
 
 /// Determine the status of a given *non-active* wire, and provide the corresponding color
 /// The current action determines how existing wires should be displayed, to provide
-/// context about how the current action will affect other wires
+/// context about how the current action will affect other wires.
+/// A `rejected` tentative wire won't be connected, so won't affect other wires.
+/// Wires that implicitly convert their data (`converts`) are marked with a different color
 pub fn wire_status<'a>(
     output: &PortRef,
     input: &PortRef,
     current_action: &app::Action,
+    rejected: bool,
+    converts: bool,
     theme: &'a AppTheme,
 ) -> Stroke<'a> {
     assert!(output.io == IO::Out);
@@ -107,9 +143,16 @@ pub fn wire_status<'a>(
 
     //let p = theme.extended_palette();
 
-    let default_stroke = default_wire_stroke(theme);
+    let default_stroke = if converts {
+        converted_wire_stroke(theme)
+    } else {
+        default_wire_stroke(theme)
+    };
     let maybe_delete = default_stroke.with_color(theme.danger.weak_color().into());
     let will_delete = with_dashed_stroke(maybe_delete);
+    if rejected {
+        return default_stroke;
+    }
 
     match current_action {
         app::Action::CreatingInputWire(active_input, active_output) => {
@@ -141,7 +184,7 @@ pub fn wire_status<'a>(
 }
 
 /// active wire color
-pub fn active_wire_stroke(t: &AppTheme, is_tentative_connection: bool) -> Stroke {
+pub fn active_wire_stroke(t: &AppTheme, is_tentative_connection: bool) -> Stroke<'_> {
     let stroke = default_wire_stroke(t).with_color(t.secondary.strong_color().into());
     if !is_tentative_connection {
         with_dashed_stroke(stroke)
@@ -150,6 +193,16 @@ pub fn active_wire_stroke(t: &AppTheme, is_tentative_connection: bool) -> Stroke
     }
 }
 
+/// wire whose data is converted to the input's port type, e.g. Real -> ArrayReal
+pub fn converted_wire_stroke(t: &AppTheme) -> Stroke<'_> {
+    default_wire_stroke(t).with_color(t.primary.base_color.into())
+}
+
+/// tentative wire that can't be connected, e.g. it would create a cycle
+pub fn rejected_wire_stroke(t: &AppTheme) -> Stroke<'_> {
+    with_dashed_stroke(default_wire_stroke(t).with_color(t.danger.base_color.into()))
+}
+
 fn with_dashed_stroke(stroke: Stroke) -> Stroke {
     Stroke {
         line_dash: canvas::LineDash {
@@ -160,7 +213,7 @@ fn with_dashed_stroke(stroke: Stroke) -> Stroke {
     }
 }
 
-pub fn default_wire_stroke(theme: &AppTheme) -> Stroke {
+pub fn default_wire_stroke(theme: &AppTheme) -> Stroke<'_> {
     Stroke::default()
         .with_width(3.0)
         .with_color(theme.secondary.base_color.into())
@@ -8,14 +8,17 @@ pub const MATH_FONT: Font = Font::with_name("DejaVu Math TeX Gyre");
 pub const SYMBOL_FONT: Font = Font::with_name("CaskaydiaCove Nerd Font");
 
 pub mod app;
+pub mod cli;
 pub mod config;
 pub mod file_watch;
 pub mod graph;
 pub mod gui_node;
+pub mod headless;
 pub mod interface;
 pub mod math;
 pub mod network;
 pub mod nodes;
+pub mod overrides;
 pub mod project;
 pub mod python;
 pub mod style;
@@ -1,9 +1,56 @@
+use std::process::ExitCode;
+
 use foray::app::{subscriptions, theme, title, App};
-use iced::{application, Font};
+use foray::cli::{Cli, Command, USAGE};
+use foray::headless;
+use foray::python::worker;
+use iced::{application, Font, Task};
 
-pub fn main() -> iced::Result {
+pub fn main() -> ExitCode {
     env_logger::init();
 
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let overrides = match cli.overrides() {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match cli.command {
+        Command::Gui => {
+            let app = match App::new(&overrides) {
+                Ok(app) => app,
+                Err(errors) => {
+                    errors.iter().for_each(|e| eprintln!("{e}"));
+                    return ExitCode::FAILURE;
+                }
+            };
+            match gui(app) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("{e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Run(file) => headless::run(&file, &overrides),
+        Command::Worker { address, token } => worker::serve(&address, token),
+        Command::Help => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+fn gui(app: App) -> iced::Result {
     application(title, App::update, App::view)
         .subscription(subscriptions)
         .theme(theme)
@@ -18,5 +65,5 @@ pub fn main() -> iced::Result {
         .font(include_bytes!("../data/CaskaydiaCoveNerdFont.ttf").as_slice())
         .font(include_bytes!("../data/CaskaydiaCove.ttf").as_slice())
         .default_font(Font::with_name("CaskaydiaCove"))
-        .run()
+        .run_with(move || (app, Task::none()))
 }
@@ -1,154 +1,340 @@
 use std::{collections::HashSet, fs::read_to_string, iter::once, path::PathBuf};
 
+pub mod history;
+pub mod variants;
+pub mod wire_cache;
+
 use iced::keyboard::Modifiers;
 use indexmap::IndexMap;
-use log::{error, warn};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
 
+use history::{Edit, History, Snapshot};
+use variants::Variants;
+
 use crate::{
     app::Action,
-    graph::{Graph, PortRef},
+    graph::{scheduler::Scheduler, GraphError, PortRef, IO},
     gui_node::GuiGraph,
     math::Point,
     nodes::{
-        port::{PortData, PortType},
+        group::{Collapsed, GroupNode},
+        port::PortData,
         status::NodeStatus,
-        NodeData, NodeTemplate,
+        NodeTemplate,
     },
     project::Project,
     widget::{shapes::ShapeId, workspace},
 };
 
-type UndoStash = Vec<(
-    Graph<NodeData, PortType, PortData>,
-    IndexMap<ShapeId, Point>,
-)>;
-
 #[derive(Serialize, Deserialize, Default)]
 pub struct Network {
     //// Persistant data
     pub graph: GuiGraph,
     pub shapes: workspace::State,
+    #[serde(default)]
+    pub variants: Variants,
     //// Runtime data
     #[serde(skip)]
     pub file: Option<PathBuf>,
     #[serde(skip)]
     pub selected_shapes: HashSet<ShapeId>,
-    /// Nodes that are waiting for dependencies before executing
-    /// TODO: make these cancleable
+    /// Tracks which nodes are queued or running
     #[serde(skip)]
-    pub queued_nodes: HashSet<u32>,
+    pub scheduler: Scheduler<PortData>,
     //#[serde(skip)]
     //pub compute_task_handles: HashMap<u32, iced::task::Handle>,
     #[serde(skip)]
-    pub undo_stack: UndoStash,
+    pub history: History,
+    /// Shape positions from when the current node drag started
     #[serde(skip)]
-    pub redo_stack: UndoStash,
+    drag_start: Option<IndexMap<ShapeId, Point>>,
     #[serde(skip)]
     pub unsaved_changes: bool,
+    /// Groups that are being edited, outermost first. The group being edited is in `graph`
+    #[serde(skip)]
+    open_groups: Vec<OpenGroup>,
+}
+
+/// The network a group was opened from, restored when the group is closed
+struct OpenGroup {
+    /// The group node in the outer network
+    nx: u32,
+    name: String,
+    graph: GuiGraph,
+    shapes: workspace::State,
+    selected_shapes: HashSet<ShapeId>,
+    history: History,
+    scheduler: Scheduler<PortData>,
 }
 impl Network {
+    /// Load a network from disk, falling back to an empty network if it can't be read
     pub fn load_network(path: &PathBuf, projects: &[Project]) -> Self {
-        match read_to_string(path).map(|s| ron::from_str::<Network>(&s)) {
-            Ok(Ok(mut network)) => {
-                network.file = Some(path.clone());
-                let node_ids = network.graph.nodes_ref();
-                node_ids.into_iter().for_each(|nx| {
-                    match &mut network.graph.get_mut_node(nx).template {
-                        NodeTemplate::RustNode(ref _rust_node) => {}
-                        NodeTemplate::PyNode(ref mut py_node) => {
-                            // Resolve the absolute path, given the nodes we know are
-                            // accessible.
-                            // Currently We just take the first one found, but more complex
-                            // resolution could be added
-                            let found_path = projects
-                                .iter()
-                                // Calculate potential node source path
-                                .map(|project| {
-                                    py_node
-                                        .relative_path
-                                        .to_logical_path(project.absolute_path.clone())
-                                })
-                                // Pick the first path that exists
-                                .find_map(|path| {
-                                    if path.is_file() {
-                                        Some(path.clone())
-                                    } else {
-                                        None
-                                    }
-                                });
-                            if let Some(path) = found_path {
-                                py_node.absolute_path = path.to_path_buf();
-                            } else {
-                                error!("Could not find source file for node \n{py_node}");
-                            }
-                        }
-                    }
-                });
-                network
-            }
-            Ok(Err(e)) => {
-                error!("Could not open file {e}");
-                warn!("creating default file");
-                Network::default()
-            }
+        match Self::read_network(path, projects) {
+            Ok(network) => network,
             Err(e) => {
-                error!("Could not parse file {e}");
+                error!("{e}");
                 warn!("creating default file");
                 Network::default()
             }
         }
     }
 
-    /// Add an edge from input to output, removing existing connected input edge if present
-    pub fn add_edge(&mut self, input: &PortRef, output: &PortRef) {
-        self.stash_state();
+    /// Read a network from disk, resolving python node source paths using the given `projects`.
+    /// Saved outputs aren't restored, see `restore_outputs`
+    pub fn read_network(path: &PathBuf, projects: &[Project]) -> Result<Self, String> {
+        let src = read_to_string(path).map_err(|e| format!("Could not open file {path:?}: {e}"))?;
+        let mut network = ron::from_str::<Network>(&src)
+            .map_err(|e| format!("Could not parse file {path:?}: {e}"))?;
+
+        network.file = Some(path.clone());
+        resolve_py_paths(&mut network.graph, projects);
+        // Files saved with wires left behind by removed ports
+        for nx in network.graph.nodes_ref() {
+            network.graph.prune_edges(nx);
+        }
+        Ok(network)
+    }
+
+    /// Add an edge from input to output, removing existing connected input edge if present.
+    /// The network is unchanged if the edge is invalid
+    pub fn add_edge(&mut self, input: &PortRef, output: &PortRef) -> Result<(), GraphError> {
+        self.graph.check_edge(output, input)?;
+        self.stash_state(Edit::Connect);
         self.graph.remove_edge(input);
-        self.graph.add_edge_from_ref(output, input);
+        self.graph.add_edge_from_ref(output, input)
     }
 
-    /// Stash current app state, reset the redo stack, and mark unsaved changes
-    pub fn stash_state(&mut self) {
+    /// Record the current state as the undo step for `edit`, and mark unsaved changes
+    pub fn stash_state(&mut self, edit: Edit) {
         self.unsaved_changes = true;
-        let mut graph_snap_shot = self.graph.clone();
+        let graph = &self.graph;
+        let shape_positions = &self.shapes.shape_positions;
+        self.history
+            .push(edit, || Self::snapshot(graph, shape_positions));
+    }
+
+    fn snapshot(graph: &GuiGraph, shape_positions: &IndexMap<ShapeId, Point>) -> Snapshot {
+        let mut graph = graph.clone();
         // We don't want to stash any node.status "running" values
-        let running_nodes: Vec<_> = graph_snap_shot
-            .nodes_ref()
-            .into_iter()
-            .filter(|nx| {
-                matches!(
-                    graph_snap_shot.get_node(*nx).status,
-                    NodeStatus::Running(..)
-                )
-            })
-            .collect();
-        for nx in running_nodes {
-            graph_snap_shot.get_mut_node(nx).status = NodeStatus::Idle;
+        for nx in graph.nodes_ref() {
+            if let Some(node) = graph.get_mut_node(nx) {
+                if let NodeStatus::Running(..) = node.status {
+                    node.status = NodeStatus::Idle;
+                }
+            }
+        }
+        Snapshot {
+            graph,
+            shape_positions: shape_positions.clone(),
         }
+    }
 
-        self.undo_stack
-            .push((graph_snap_shot, self.shapes.shape_positions.clone()));
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.graph = snapshot.graph;
+        self.shapes.shape_positions = snapshot.shape_positions;
+        self.scheduler.remove_deleted(&self.graph);
+        self.unsaved_changes = true;
+    }
+
+    /// Revert the most recent edit. Returns false if there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        let current = Self::snapshot(&self.graph, &self.shapes.shape_positions);
+        match self.history.undo(current) {
+            Some((edit, previous)) => {
+                info!("Undo: {edit}");
+                self.restore(previous);
+                true
+            }
+            None => false,
+        }
+    }
 
-        // Don't let the stack get too big
-        self.undo_stack.truncate(10);
+    /// Reapply the most recently undone edit. Returns false if there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        let current = Self::snapshot(&self.graph, &self.shapes.shape_positions);
+        match self.history.redo(current) {
+            Some((edit, next)) => {
+                info!("Redo: {edit}");
+                self.restore(next);
+                true
+            }
+            None => false,
+        }
+    }
 
-        self.redo_stack.clear();
+    /// Finish dragging nodes, recording an undo step if any of them moved
+    pub fn end_drag(&mut self) {
+        let Some(start) = self.drag_start.take() else {
+            return;
+        };
+        let moved = self
+            .shapes
+            .shape_positions
+            .iter()
+            .filter(|(id, position)| start.get(*id) != Some(*position))
+            .count();
+        if moved > 0 {
+            self.unsaved_changes = true;
+            let graph = &self.graph;
+            self.history
+                .push(Edit::MoveNodes(moved), || Self::snapshot(graph, &start));
+        }
     }
 
     pub fn remove_edge(&mut self, port: crate::graph::PortRef) {
-        self.stash_state();
+        self.stash_state(Edit::Disconnect);
         self.graph.remove_edge(&port);
     }
 
+    /// Replace the selected nodes with a group node containing them.
+    /// Returns the new group node, or `None` if nothing is selected
+    pub fn group_selected(&mut self) -> Option<u32> {
+        let nodes: HashSet<u32> = self
+            .selected_shapes
+            .iter()
+            .copied()
+            .filter(|nx| self.graph.contains_node(*nx))
+            .collect();
+        if nodes.is_empty() {
+            return None;
+        }
+        self.stash_state(Edit::GroupNodes(nodes.len()));
+
+        let Collapsed {
+            group,
+            inputs,
+            outputs,
+        } = GroupNode::collapse(&self.graph, &self.shapes.shape_positions, &nodes);
+        let (x, y) = nodes
+            .iter()
+            .filter_map(|nx| self.shapes.shape_positions.get(nx))
+            .fold((0., 0.), |(x, y), p| (x + p.x, y + p.y));
+        let center = Point::new(x / nodes.len() as f32, y / nodes.len() as f32);
+        for nx in &nodes {
+            self.graph.delete_node(*nx);
+            self.shapes.shape_positions.shift_remove(nx);
+        }
+        self.scheduler.remove_deleted(&self.graph);
+
+        let group_nx = self.graph.node(NodeTemplate::Group(Box::new(group)).into());
+        self.shapes
+            .shape_positions
+            .insert_before(0, group_nx, center);
+        for (from, name) in inputs {
+            let to = PortRef {
+                node: group_nx,
+                name,
+                io: IO::In,
+            };
+            if let Err(e) = self.graph.add_edge_from_ref(&from, &to) {
+                warn!("Could not connect group input: {e}");
+            }
+        }
+        for (name, to) in outputs {
+            let from = PortRef {
+                node: group_nx,
+                name,
+                io: IO::Out,
+            };
+            if let Err(e) = self.graph.add_edge_from_ref(&from, &to) {
+                warn!("Could not connect group output: {e}");
+            }
+        }
+        self.selected_shapes = [group_nx].into();
+        Some(group_nx)
+    }
+
+    /// Edit the network inside group node `nx`. The group's current inputs are available
+    /// inside it, and editing has its own undo history until the group is closed
+    pub fn open_group(&mut self, nx: u32) {
+        let Some(NodeTemplate::Group(group)) = self.graph.get_node(nx).map(|n| &n.template) else {
+            warn!("Node #{nx} is not a group");
+            return;
+        };
+        let group = group.clone();
+        let inputs = self.graph.get_input_data(&nx);
+        self.cancel_running();
+
+        let history = self.history.fresh();
+        self.open_groups.push(OpenGroup {
+            nx,
+            name: group.name.clone(),
+            graph: std::mem::replace(&mut self.graph, group.graph.clone()),
+            shapes: std::mem::replace(&mut self.shapes, group.shapes.clone()),
+            selected_shapes: std::mem::take(&mut self.selected_shapes),
+            history: std::mem::replace(&mut self.history, history),
+            scheduler: std::mem::take(&mut self.scheduler),
+        });
+        self.graph.restore_wire_data(group.input_node, inputs);
+        self.drag_start = None;
+    }
+
+    /// Stop editing the innermost open group, and return to the network it is part of.
+    /// Changes made inside the group are a single undo step of that network.
+    /// Returns the group node, if a group was open
+    pub fn close_group(&mut self) -> Option<u32> {
+        let outer = self.open_groups.pop()?;
+        self.cancel_running();
+        let graph = std::mem::replace(&mut self.graph, outer.graph);
+        let shapes = std::mem::replace(&mut self.shapes, outer.shapes);
+        self.selected_shapes = outer.selected_shapes;
+        self.history = outer.history;
+        self.scheduler = outer.scheduler;
+        self.drag_start = None;
+
+        let nx = outer.nx;
+        if let Some(NodeTemplate::Group(group)) = self.graph.get_node(nx).map(|n| &n.template) {
+            let edited = GroupNode {
+                graph: Self::snapshot(&graph, &shapes.shape_positions).graph,
+                shapes,
+                ..(**group).clone()
+            };
+            if **group != edited {
+                self.stash_state(Edit::EditGroup(outer.name));
+                if let Some(node) = self.graph.get_mut_node(nx) {
+                    node.template = NodeTemplate::Group(Box::new(edited));
+                }
+            }
+        }
+        Some(nx)
+    }
+
+    /// Close every open group, returning to the top level network.
+    /// Returns false if no group was open
+    pub fn close_all_groups(&mut self) -> bool {
+        let was_open = !self.open_groups.is_empty();
+        while self.close_group().is_some() {}
+        was_open
+    }
+
+    /// Names of the open groups, outermost first
+    pub fn group_path(&self) -> Vec<&str> {
+        self.open_groups.iter().map(|g| g.name.as_str()).collect()
+    }
+
+    /// Stop computing, e.g. before the graph is swapped out.
+    /// Outputs stay memoized, so they can be reused when computing resumes
+    fn cancel_running(&mut self) {
+        for nx in self.scheduler.cancel_all() {
+            if let Some(node) = self.graph.get_mut_node(nx) {
+                if let NodeStatus::Running(_) = node.status {
+                    node.status = NodeStatus::Idle;
+                }
+            }
+        }
+    }
+
     pub(crate) fn select_node(
         &mut self,
         nx: u32,
         modifiers: Modifiers,
         cursor_position: Point,
     ) -> Action {
+        // Duplicating is its own undo step, any other click may start moving nodes
+        self.drag_start = (!modifiers.command()).then(|| self.shapes.shape_positions.clone());
         self.selected_shapes = if modifiers.command() {
             //// Create new nodes on Command + Click
-            self.stash_state();
             let selected_shapes = if self.selected_shapes.contains(&nx) {
                 // If clicked node is already selected, copy all selected nodes,
                 self.selected_shapes.clone()
@@ -156,16 +342,17 @@ impl Network {
                 // Otherwise, only copy the clicked node
                 [nx].into()
             };
+            self.stash_state(Edit::DuplicateNodes(selected_shapes.len()));
             selected_shapes
                 .iter()
-                .map(|id| {
+                .filter_map(|id| {
                     let pos = self.shapes.shape_positions[id] + [5., 5.].into();
-                    let new_node = self.graph.get_node(*id).template.duplicate().into();
+                    let new_node = self.graph.get_node(*id)?.template.duplicate().into();
                     // *Mutably* add new node to graph
                     let new_id = self.graph.node(new_node);
                     // *Mutably* add new position
                     self.shapes.shape_positions.insert(new_id, pos);
-                    new_id
+                    Some(new_id)
                 })
                 .collect()
         } else if modifiers.shift() {
@@ -207,3 +394,32 @@ impl Network {
         Action::DragNode(offsets)
     }
 }
+
+/// Find the source file of every python node, including those in groups.
+/// Currently the first project with a matching file is used, but more complex resolution could
+/// be added
+pub fn resolve_py_paths(graph: &mut GuiGraph, projects: &[Project]) {
+    for nx in graph.nodes_ref() {
+        match graph.get_mut_node(nx).map(|node| &mut node.template) {
+            Some(NodeTemplate::RustNode(_)) | None => {}
+            Some(NodeTemplate::Group(group)) => resolve_py_paths(&mut group.graph, projects),
+            Some(NodeTemplate::PyNode(ref mut py_node)) => {
+                let found_path = projects
+                    .iter()
+                    // Calculate potential node source path
+                    .map(|project| {
+                        py_node
+                            .relative_path
+                            .to_logical_path(project.absolute_path.clone())
+                    })
+                    // Pick the first path that exists
+                    .find(|path| path.is_file());
+                if let Some(path) = found_path {
+                    py_node.absolute_path = path;
+                } else {
+                    error!("Could not find source file for node \n{py_node}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use indexmap::IndexMap;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{gui_node::GuiGraph, math::Point, widget::shapes::ShapeId};
+
+/// Continuous edits of the same parameter closer together than this are merged into one undo step
+const COALESCE_WINDOW: Duration = Duration::from_millis(750);
+
+/// Undo/redo settings, read from the `[history]` table of config.toml
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Maximum number of undo steps that are kept
+    pub depth: usize,
+    /// Save the undo history next to the network file, so it survives restarting the app
+    pub journal: bool,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            depth: 100,
+            journal: false,
+        }
+    }
+}
+
+/// The state of a network that undo/redo returns to
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub graph: GuiGraph,
+    pub shape_positions: IndexMap<ShapeId, Point>,
+}
+
+/// A user edit of the network, used to label undo steps
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Edit {
+    AddNode(String),
+    DuplicateNodes(usize),
+    DeleteNodes(usize),
+    MoveNodes(usize),
+    Connect,
+    Disconnect,
+    ChangeNode(u32, String),
+    ChangeParameter {
+        nx: u32,
+        name: String,
+        widget: String,
+    },
+    GroupNodes(usize),
+    EditGroup(String),
+    ApplyVariant(String),
+}
+
+impl Edit {
+    /// Repeated edits that should collapse into a single undo step, like dragging a slider
+    fn coalesces(&self) -> bool {
+        matches!(self, Edit::ChangeParameter { .. })
+    }
+}
+
+impl Display for Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let nodes = |n: &usize| format!("{n} node{}", if *n == 1 { "" } else { "s" });
+        match self {
+            Edit::AddNode(name) => write!(f, "Add {name}"),
+            Edit::DuplicateNodes(n) => write!(f, "Duplicate {}", nodes(n)),
+            Edit::DeleteNodes(n) => write!(f, "Delete {}", nodes(n)),
+            Edit::MoveNodes(n) => write!(f, "Move {}", nodes(n)),
+            Edit::Connect => write!(f, "Connect wire"),
+            Edit::Disconnect => write!(f, "Remove wire"),
+            Edit::ChangeNode(_, name) => write!(f, "Change {name}"),
+            Edit::ChangeParameter { name, widget, .. } => write!(f, "Change {widget} {name}"),
+            Edit::GroupNodes(n) => write!(f, "Group {}", nodes(n)),
+            Edit::EditGroup(name) => write!(f, "Edit group {name}"),
+            Edit::ApplyVariant(name) => write!(f, "Apply variant {name}"),
+        }
+    }
+}
+
+/// An undo or redo step: the edit, and the state on the other side of it
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    edit: Edit,
+    snapshot: Snapshot,
+    /// When the edit was last extended by a coalesced edit
+    #[serde(skip)]
+    last_change: Option<Instant>,
+}
+
+/// Labelled undo/redo log of network edits
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+    config: HistoryConfig,
+    /// Where the history is saved, if journaling is enabled and the network has a file
+    journal: Option<PathBuf>,
+}
+
+/// On-disk form of [`History`]
+#[derive(Serialize, Deserialize)]
+struct Journal {
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+}
+
+impl History {
+    /// Apply `config`, and restore the journal of `network_file` if the history is still empty
+    pub fn configure(&mut self, config: &HistoryConfig, network_file: Option<&Path>) {
+        self.config = config.clone();
+        self.trim();
+        self.journal = network_file
+            .filter(|_| config.journal)
+            .map(|file| file.with_extension("history.ron"));
+
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        if !self.undo.is_empty() || !self.redo.is_empty() || !journal.is_file() {
+            return;
+        }
+        match read_to_string(journal).map(|s| ron::from_str::<Journal>(&s)) {
+            Ok(Ok(Journal { undo, redo })) => {
+                info!("Restored undo history {journal:?}");
+                self.undo = undo;
+                self.redo = redo;
+                self.trim();
+            }
+            Ok(Err(e)) => error!("Could not parse undo history {journal:?}: {e}"),
+            Err(e) => error!("Could not read undo history {journal:?}: {e}"),
+        }
+    }
+
+    /// Save the history to the journal, if journaling is enabled
+    pub fn write_journal(&self) {
+        let Some(journal) = &self.journal else {
+            return;
+        };
+        let result = ron::to_string(&Journal {
+            undo: self.undo.clone(),
+            redo: self.redo.clone(),
+        })
+        .map_err(|e| e.to_string())
+        .and_then(|s| std::fs::write(journal, s).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!("Could not save undo history {journal:?}: {e}");
+        }
+    }
+
+    /// An empty history with the same settings, that isn't journaled
+    pub fn fresh(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Record `edit`, with the state from before it was made. Returns false if the edit was
+    /// merged into the previous step instead
+    pub fn push(&mut self, edit: Edit, before: impl FnOnce() -> Snapshot) -> bool {
+        self.push_at(edit, before, Instant::now())
+    }
+
+    fn push_at(&mut self, edit: Edit, before: impl FnOnce() -> Snapshot, now: Instant) -> bool {
+        if edit.coalesces() && self.redo.is_empty() {
+            if let Some(last) = self.undo.back_mut() {
+                let recent = last
+                    .last_change
+                    .is_some_and(|t| now.duration_since(t) < COALESCE_WINDOW);
+                if last.edit == edit && recent {
+                    last.last_change = Some(now);
+                    return false;
+                }
+            }
+        }
+        self.undo.push_back(Entry {
+            edit,
+            snapshot: before(),
+            last_change: Some(now),
+        });
+        self.trim();
+        self.redo.clear();
+        true
+    }
+
+    /// Step back, returning the undone edit and the state to restore
+    pub fn undo(&mut self, current: Snapshot) -> Option<(Edit, Snapshot)> {
+        let entry = self.undo.pop_back()?;
+        self.redo.push(Entry {
+            edit: entry.edit.clone(),
+            snapshot: current,
+            last_change: None,
+        });
+        Some((entry.edit, entry.snapshot))
+    }
+
+    /// Step forward, returning the redone edit and the state to restore
+    pub fn redo(&mut self, current: Snapshot) -> Option<(Edit, Snapshot)> {
+        let entry = self.redo.pop()?;
+        self.undo.push_back(Entry {
+            edit: entry.edit.clone(),
+            snapshot: current,
+            last_change: None,
+        });
+        Some((entry.edit, entry.snapshot))
+    }
+
+    /// The edit that [`History::undo`] would revert
+    pub fn next_undo(&self) -> Option<&Edit> {
+        self.undo.back().map(|entry| &entry.edit)
+    }
+
+    /// The edit that [`History::redo`] would reapply
+    pub fn next_redo(&self) -> Option<&Edit> {
+        self.redo.last().map(|entry| &entry.edit)
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Drop the oldest steps beyond the configured depth
+    fn trim(&mut self) {
+        while self.undo.len() > self.config.depth {
+            self.undo.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot(x: f32) -> Snapshot {
+        Snapshot {
+            graph: GuiGraph::new(),
+            shape_positions: [(0, Point::new(x, 0.))].into_iter().collect(),
+        }
+    }
+
+    fn x(snapshot: &Snapshot) -> f32 {
+        snapshot.shape_positions[&0].x
+    }
+
+    fn slider() -> Edit {
+        Edit::ChangeParameter {
+            nx: 0,
+            name: "d".into(),
+            widget: "slider".into(),
+        }
+    }
+
+    #[test]
+    fn depth_drops_oldest() {
+        let mut history = History::default();
+        history.configure(
+            &HistoryConfig {
+                depth: 2,
+                journal: false,
+            },
+            None,
+        );
+        for i in 0..4 {
+            history.push(Edit::MoveNodes(1), || snapshot(i as f32));
+        }
+        assert_eq!(history.undo_len(), 2);
+
+        let (_, restored) = history.undo(snapshot(4.)).unwrap();
+        assert_eq!(x(&restored), 3.);
+        let (_, restored) = history.undo(restored).unwrap();
+        assert_eq!(x(&restored), 2.);
+        assert!(history.undo(restored).is_none());
+    }
+
+    #[test]
+    fn coalesce_slider_drag() {
+        let mut history = History::default();
+        let start = Instant::now();
+        let tick = Duration::from_millis(100);
+
+        // A continuous drag is one step, that restores the value before the drag
+        assert!(history.push_at(slider(), || snapshot(0.), start));
+        for i in 1..20 {
+            assert!(!history.push_at(slider(), || snapshot(i as f32), start + tick * i));
+        }
+        assert_eq!(history.undo_len(), 1);
+        assert_eq!(history.next_undo().unwrap().to_string(), "Change slider d");
+
+        // Coming back to the slider later is a new step
+        assert!(history.push_at(slider(), || snapshot(20.), start + tick * 30));
+        assert_eq!(history.undo_len(), 2);
+
+        let (_, restored) = history.undo(snapshot(21.)).unwrap();
+        assert_eq!(x(&restored), 20.);
+        let (_, restored) = history.undo(restored).unwrap();
+        assert_eq!(x(&restored), 0.);
+
+        let (edit, restored) = history.redo(restored).unwrap();
+        assert_eq!(edit, slider());
+        assert_eq!(x(&restored), 20.);
+    }
+}
@@ -0,0 +1,418 @@
+use std::{collections::HashMap, fmt::Display};
+
+use indexmap::IndexMap;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    history::{Edit, History, Snapshot},
+    Network,
+};
+use crate::{
+    graph::PortRef,
+    gui_node::{GUINode, GuiGraph},
+};
+
+/// Name of the variant a network starts with
+pub const MAIN_VARIANT: &str = "main";
+
+/// Named alternative versions of a network, saved in the network file.
+/// The current variant is the network itself, the others are kept as snapshots
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Variants {
+    current: String,
+    /// The state the current variant was forked from, `None` for the original network
+    base: Option<Snapshot>,
+    others: IndexMap<String, Variant>,
+    /// Variant the current one is being compared with
+    #[serde(skip)]
+    pub comparing: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Variant {
+    state: Snapshot,
+    base: Option<Snapshot>,
+    /// Undo history of the variant, kept while other variants are being edited
+    #[serde(skip)]
+    history: History,
+}
+
+impl Default for Variants {
+    fn default() -> Self {
+        Self {
+            current: MAIN_VARIANT.to_string(),
+            base: None,
+            others: IndexMap::new(),
+            comparing: None,
+        }
+    }
+}
+
+impl Variants {
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Names of all variants, the original network first
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.others.keys().cloned().collect();
+        names.push(self.current.clone());
+        names.sort_by_key(|name| (name != MAIN_VARIANT, name.clone()));
+        names
+    }
+
+    /// A name that isn't used by any variant
+    pub fn unused_name(&self) -> String {
+        (1..)
+            .map(|i| format!("variant {i}"))
+            .find(|name| *name != self.current && !self.others.contains_key(name))
+            .expect("there is always an unused name")
+    }
+}
+
+/// A way two versions of a network differ, for nodes that exist in both the ids are the same
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    Added {
+        nx: u32,
+        name: String,
+    },
+    Removed {
+        nx: u32,
+        name: String,
+    },
+    /// A node's template changed, with the parameters that changed as (name, before, after)
+    Changed {
+        nx: u32,
+        name: String,
+        parameters: Vec<(String, String, String)>,
+    },
+    Connected(PortRef, PortRef),
+    Disconnected(PortRef, PortRef),
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let port = |p: &PortRef| format!("#{}.{}", p.node, p.name);
+        match self {
+            Difference::Added { nx, name } => write!(f, "+ {name} #{nx}"),
+            Difference::Removed { nx, name } => write!(f, "- {name} #{nx}"),
+            Difference::Changed {
+                nx,
+                name,
+                parameters,
+            } if !parameters.is_empty() => write!(
+                f,
+                "~ {name} #{nx}: {}",
+                parameters
+                    .iter()
+                    .map(|(p, before, after)| format!("{p} {before} → {after}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Difference::Changed { nx, name, .. } => write!(f, "~ {name} #{nx}"),
+            Difference::Connected(from, to) => write!(f, "+ {} → {}", port(from), port(to)),
+            Difference::Disconnected(from, to) => {
+                write!(f, "- {} → {}", port(from), port(to))
+            }
+        }
+    }
+}
+
+/// Changes that turn `before` into `after`
+pub fn diff(before: &GuiGraph, after: &GuiGraph) -> Vec<Difference> {
+    let name = |graph: &GuiGraph, nx| {
+        graph
+            .get_node(nx)
+            .map(|node| node.template.name())
+            .unwrap_or_default()
+    };
+    let mut differences = vec![];
+    for nx in before.nodes_ref() {
+        let Some(after_node) = after.get_node(nx) else {
+            differences.push(Difference::Removed {
+                nx,
+                name: name(before, nx),
+            });
+            continue;
+        };
+        let before_node = before.get_node(nx).expect("node is in before");
+        if before_node.template != after_node.template {
+            let before_values: HashMap<_, _> = before_node
+                .template
+                .parameter_values()
+                .into_iter()
+                .collect();
+            let parameters = after_node
+                .template
+                .parameter_values()
+                .into_iter()
+                .filter_map(|(p, value)| {
+                    let previous = before_values.get(&p)?;
+                    (*previous != value).then(|| (p, previous.clone(), value))
+                })
+                .collect();
+            differences.push(Difference::Changed {
+                nx,
+                name: after_node.template.name(),
+                parameters,
+            });
+        }
+    }
+    for nx in after.nodes_ref() {
+        if !before.contains_node(nx) {
+            differences.push(Difference::Added {
+                nx,
+                name: name(after, nx),
+            });
+        }
+    }
+
+    let before_edges: Vec<_> = before.edges().collect();
+    let after_edges: Vec<_> = after.edges().collect();
+    for (from, to) in &before_edges {
+        if !after_edges.contains(&&(from.clone(), to.clone())) {
+            differences.push(Difference::Disconnected(from.clone(), to.clone()));
+        }
+    }
+    for (from, to) in &after_edges {
+        if !before_edges.contains(&&(from.clone(), to.clone())) {
+            differences.push(Difference::Connected(from.clone(), to.clone()));
+        }
+    }
+    differences
+}
+
+impl Network {
+    /// Copy the current state into a new variant, and switch to it
+    pub fn fork_variant(&mut self, name: String) {
+        let state = self.current_snapshot();
+        let previous = std::mem::replace(&mut self.variants.current, name.clone());
+        let base = self.variants.base.replace(state.clone());
+        let fresh = self.history.fresh();
+        let history = std::mem::replace(&mut self.history, fresh);
+        self.variants.others.insert(
+            previous,
+            Variant {
+                state,
+                base,
+                history,
+            },
+        );
+        self.unsaved_changes = true;
+        info!("Forked variant {name}");
+    }
+
+    /// Make `name` the current variant, keeping the current state as a variant
+    pub fn switch_variant(&mut self, name: &str) {
+        let Some(variant) = self.variants.others.shift_remove(name) else {
+            warn!("No variant named {name}");
+            return;
+        };
+        let state = self.current_snapshot();
+        let previous = std::mem::replace(&mut self.variants.current, name.to_string());
+        let base = std::mem::replace(&mut self.variants.base, variant.base);
+        let history = std::mem::replace(&mut self.history, variant.history);
+        self.variants.others.insert(
+            previous,
+            Variant {
+                state,
+                base,
+                history,
+            },
+        );
+        if self.variants.comparing.as_deref() == Some(name) {
+            self.variants.comparing = None;
+        }
+        self.selected_shapes.clear();
+        self.restore(variant.state);
+        info!("Switched to variant {name}");
+    }
+
+    /// Forget a variant other than the current one
+    pub fn delete_variant(&mut self, name: &str) {
+        if self.variants.others.shift_remove(name).is_some() {
+            self.unsaved_changes = true;
+            if self.variants.comparing.as_deref() == Some(name) {
+                self.variants.comparing = None;
+            }
+        }
+    }
+
+    /// Changes that turn the current variant into variant `name`
+    pub fn variant_diff(&self, name: &str) -> Vec<Difference> {
+        self.variants
+            .others
+            .get(name)
+            .map(|variant| diff(&self.graph, &variant.state.graph))
+            .unwrap_or_default()
+    }
+
+    /// Make the changes from variant `name`, since it was forked, to the current variant.
+    /// Where both changed the same node, the variant's version is used
+    pub fn apply_variant(&mut self, name: &str) -> Result<(), String> {
+        let variant = self
+            .variants
+            .others
+            .get(name)
+            .ok_or(format!("No variant named {name}"))?;
+        let base = variant.base.as_ref().ok_or(format!(
+            "{name} is the original network, it has no changes to apply"
+        ))?;
+        let changes = diff(&base.graph, &variant.state.graph);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let source = variant.state.clone();
+        self.stash_state(Edit::ApplyVariant(name.to_string()));
+
+        // Nodes added in the variant get new ids here
+        let mut ids: HashMap<u32, u32> = HashMap::new();
+        for change in &changes {
+            match change {
+                Difference::Added { nx, .. } => {
+                    let node = source.graph.get_node(*nx).expect("added node exists");
+                    let new_nx = self.graph.node(node.template.clone().into());
+                    if let Some(position) = source.shape_positions.get(nx) {
+                        self.shapes.shape_positions.insert(new_nx, *position);
+                    }
+                    ids.insert(*nx, new_nx);
+                }
+                Difference::Removed { nx, .. } => {
+                    self.graph.delete_node(*nx);
+                    self.shapes.shape_positions.shift_remove(nx);
+                }
+                Difference::Changed { nx, .. } => {
+                    let template = source.graph.get_node(*nx).expect("changed node exists");
+                    if let Some(node) = self.graph.get_mut_node(*nx) {
+                        node.template = template.template.clone();
+                    }
+                }
+                Difference::Connected(..) | Difference::Disconnected(..) => {}
+            }
+        }
+        let map = |port: &PortRef| PortRef {
+            node: ids.get(&port.node).copied().unwrap_or(port.node),
+            ..port.clone()
+        };
+        for change in &changes {
+            match change {
+                // Only if the input is still connected the same way here
+                Difference::Disconnected(from, to)
+                    if self.graph.get_parent(&to.node, to.name.clone()).as_ref() == Some(from) =>
+                {
+                    self.graph.remove_edge(to);
+                }
+                Difference::Connected(from, to) => {
+                    let (from, to) = (map(from), map(to));
+                    if !self.graph.contains_node(from.node) || !self.graph.contains_node(to.node) {
+                        continue;
+                    }
+                    self.graph.remove_edge(&to);
+                    if let Err(e) = self.graph.add_edge_from_ref(&from, &to) {
+                        warn!("Could not apply wire from variant {name}: {e}");
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.scheduler.remove_deleted(&self.graph);
+        info!("Applied {} changes from variant {name}", changes.len());
+        Ok(())
+    }
+
+    fn current_snapshot(&self) -> Snapshot {
+        Self::snapshot(&self.graph, &self.shapes.shape_positions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        graph::IO,
+        nodes::{NodeTemplate, RustNode},
+    };
+
+    fn constant(network: &Network, nx: u32) -> f64 {
+        match network.graph.get_node(nx).unwrap().template {
+            NodeTemplate::RustNode(RustNode::Constant(value)) => value,
+            _ => panic!("not a constant"),
+        }
+    }
+
+    #[test]
+    fn fork_switch_apply() {
+        let mut network = Network::default();
+        let c = network
+            .graph
+            .node(NodeTemplate::RustNode(RustNode::Constant(1.)).into());
+        let sin = network
+            .graph
+            .node(NodeTemplate::RustNode(RustNode::Sin).into());
+        network
+            .graph
+            .add_edge_from_ref(
+                &PortRef::new(c, "out", IO::Out),
+                &PortRef::new(sin, "a", IO::In),
+            )
+            .unwrap();
+
+        // Change the constant, and add a node, in a fork
+        network.fork_variant("try".into());
+        network.graph.get_mut_node(c).unwrap().template =
+            NodeTemplate::RustNode(RustNode::Constant(2.));
+        let cos = network
+            .graph
+            .node(NodeTemplate::RustNode(RustNode::Cos).into());
+        network
+            .graph
+            .add_edge_from_ref(
+                &PortRef::new(c, "out", IO::Out),
+                &PortRef::new(cos, "a", IO::In),
+            )
+            .unwrap();
+
+        // Main is unchanged
+        network.switch_variant(MAIN_VARIANT);
+        assert_eq!(network.variants.current(), MAIN_VARIANT);
+        assert_eq!(constant(&network, c), 1.);
+        assert!(!network.graph.contains_node(cos));
+
+        let differences = network.variant_diff("try");
+        assert_eq!(differences.len(), 3, "{differences:?}");
+        assert_eq!(differences[0].to_string(), "~ Constant #0: value 1 → 2");
+
+        // Main has its own node, that takes the id used by the fork
+        let identity = network
+            .graph
+            .node(NodeTemplate::RustNode(RustNode::Identity).into());
+        assert_eq!(identity, cos);
+
+        network.apply_variant("try").unwrap();
+        assert_eq!(constant(&network, c), 2.);
+        assert!(network.graph.contains_node(identity));
+        let applied_cos = network
+            .graph
+            .nodes_ref()
+            .into_iter()
+            .find(|nx| {
+                matches!(
+                    network.graph.get_node(*nx).unwrap().template,
+                    NodeTemplate::RustNode(RustNode::Cos)
+                )
+            })
+            .unwrap();
+        assert_eq!(
+            network.graph.get_parent(&applied_cos, "a".into()),
+            Some(PortRef::new(c, "out", IO::Out))
+        );
+
+        // Applying is one undo step
+        assert!(network.undo());
+        assert_eq!(constant(&network, c), 1.);
+        assert!(network.apply_variant(MAIN_VARIANT).is_err());
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use log::{info, trace, warn};
+use ndarray::{ArrayD, ShapeBuilder};
+use npyz::{AutoSerialize, Deserialize as NpyDeserialize, WriterBuilder};
+use numpy::Complex64;
+use serde::{Deserialize, Serialize};
+
+use super::Network;
+use crate::{
+    graph::memo::upstream_fingerprint,
+    nodes::port::{PortData, SharedArray},
+    StableMap,
+};
+
+const MANIFEST: &str = "manifest.ron";
+
+/// Directory that computed outputs of `network_file` are saved in, e.g. `foo.cache/` for `foo.ron`
+pub fn cache_dir(network_file: &Path) -> PathBuf {
+    network_file.with_extension("cache")
+}
+
+/// Saved outputs of a single node, in `<cache dir>/<node id>/`
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    /// Fingerprint of the node and everything upstream of it when the outputs were saved
+    fingerprint: u64,
+    /// The network has changed since the outputs were saved, so they are not used
+    #[serde(default)]
+    stale: bool,
+    outputs: StableMap<String, Stored>,
+}
+
+/// `PortData` on disk. Scalars are kept in the manifest, arrays in `.npy` files next to it
+#[derive(Serialize, Deserialize)]
+enum Stored {
+    Integer(i64),
+    Real(f64),
+    Complex(f64, f64),
+    ArrayInteger(String),
+    ArrayReal(String),
+    ArrayComplex(String),
+    ArrayBool(String),
+    ArrayUnsigned8(String),
+    ArrayUnsigned16(String),
+    ArrayInteger32(String),
+    ArrayReal32(String),
+    ArrayComplex32(String),
+    Dynamic(String),
+    Object(StableMap<String, Stored>),
+}
+
+impl Network {
+    /// Save the outputs of every computed node to the cache directory next to the network file.
+    /// Nodes whose saved outputs are still up to date aren't written again
+    pub fn save_outputs(&self) -> io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let dir = cache_dir(file);
+        fs::create_dir_all(&dir)?;
+
+        let mut saved = vec![];
+        for nx in self.graph.nodes_ref() {
+            let Some(fingerprint) = upstream_fingerprint(&self.graph, nx) else {
+                continue;
+            };
+            let outputs = self.graph.get_output_data(nx);
+            if outputs.is_empty() || outputs.values().any(|data| data.is_none()) {
+                continue;
+            }
+            let node_dir = dir.join(nx.to_string());
+            saved.push(node_dir.clone());
+            if read_manifest(&node_dir).is_ok_and(|m| m.fingerprint == fingerprint && !m.stale) {
+                continue;
+            }
+
+            trace!("Saving outputs of #{nx} to {node_dir:?}");
+            if node_dir.exists() {
+                fs::remove_dir_all(&node_dir)?;
+            }
+            fs::create_dir(&node_dir)?;
+            let outputs = outputs
+                .into_iter()
+                .filter_map(|(name, data)| {
+                    let data = data?.read().ok()?;
+                    Some(store(&node_dir, &name, &data).map(|stored| (name, stored)))
+                })
+                .collect::<io::Result<_>>()?;
+            write_manifest(
+                &node_dir,
+                &Manifest {
+                    fingerprint,
+                    stale: false,
+                    outputs,
+                },
+            )?;
+        }
+
+        // Outputs of deleted nodes, or nodes that no longer have data
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() && !saved.contains(&path) {
+                fs::remove_dir_all(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Put saved outputs back on the nodes they were computed by, if those nodes and everything
+    /// upstream of them are unchanged. Restored outputs are memoized, so they aren't recomputed.
+    /// Outdated outputs are marked stale, and replaced the next time outputs are saved
+    pub fn restore_outputs(&mut self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let dir = cache_dir(file);
+        if !dir.is_dir() {
+            return;
+        }
+
+        let mut restored = 0;
+        for nx in self.graph.nodes_ref() {
+            let node_dir = dir.join(nx.to_string());
+            let Ok(mut manifest) = read_manifest(&node_dir) else {
+                continue;
+            };
+            let fingerprint = upstream_fingerprint(&self.graph, nx);
+            if manifest.stale || fingerprint != Some(manifest.fingerprint) {
+                if !manifest.stale {
+                    manifest.stale = true;
+                    if let Err(e) = write_manifest(&node_dir, &manifest) {
+                        warn!("Could not mark saved outputs of #{nx} stale: {e}");
+                    }
+                }
+                continue;
+            }
+
+            let outputs: io::Result<StableMap<_, _>> = manifest
+                .outputs
+                .into_iter()
+                .map(|(name, stored)| Ok((name, load(&node_dir, stored)?)))
+                .collect();
+            match outputs {
+                Ok(outputs) => {
+                    self.graph.update_wire_data(nx, outputs);
+                    let containers = self
+                        .graph
+                        .get_output_data(nx)
+                        .into_iter()
+                        .filter_map(|(name, data)| Some((name, data?.clone())))
+                        .collect();
+                    self.scheduler.memoize(nx, manifest.fingerprint, containers);
+                    restored += 1;
+                }
+                Err(e) => warn!("Could not restore saved outputs of #{nx}: {e}"),
+            }
+        }
+        info!("Restored saved outputs of {restored} nodes from {dir:?}");
+    }
+}
+
+fn read_manifest(node_dir: &Path) -> io::Result<Manifest> {
+    let src = fs::read_to_string(node_dir.join(MANIFEST))?;
+    ron::from_str(&src).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_manifest(node_dir: &Path, manifest: &Manifest) -> io::Result<()> {
+    let src = ron::to_string(manifest).map_err(io::Error::other)?;
+    fs::write(node_dir.join(MANIFEST), src)
+}
+
+/// Write `data`, with arrays going to `<name>.npy` in `node_dir`
+fn store(node_dir: &Path, name: &str, data: &PortData) -> io::Result<Stored> {
+    let file = format!("{name}.npy");
+    Ok(match data {
+        PortData::Integer(i) => Stored::Integer(*i),
+        PortData::Real(r) => Stored::Real(*r),
+        PortData::Complex(c) => Stored::Complex(c.re, c.im),
+        PortData::ArrayInteger(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayInteger(file)
+        }
+        PortData::ArrayReal(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayReal(file)
+        }
+        PortData::ArrayComplex(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayComplex(file)
+        }
+        PortData::ArrayBool(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayBool(file)
+        }
+        PortData::ArrayUnsigned8(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayUnsigned8(file)
+        }
+        PortData::ArrayUnsigned16(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayUnsigned16(file)
+        }
+        PortData::ArrayInteger32(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayInteger32(file)
+        }
+        PortData::ArrayReal32(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayReal32(file)
+        }
+        PortData::ArrayComplex32(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::ArrayComplex32(file)
+        }
+        PortData::Dynamic(a) => {
+            write_npy(&node_dir.join(&file), a)?;
+            Stored::Dynamic(file)
+        }
+        PortData::Object(fields) => Stored::Object(
+            fields
+                .iter()
+                .map(|(field, data)| {
+                    Ok((
+                        field.clone(),
+                        store(node_dir, &format!("{name}.{field}"), data)?,
+                    ))
+                })
+                .collect::<io::Result<_>>()?,
+        ),
+    })
+}
+
+fn load(node_dir: &Path, stored: Stored) -> io::Result<PortData> {
+    Ok(match stored {
+        Stored::Integer(i) => PortData::Integer(i),
+        Stored::Real(r) => PortData::Real(r),
+        Stored::Complex(re, im) => PortData::Complex(Complex64::new(re, im)),
+        Stored::ArrayInteger(file) => PortData::ArrayInteger(read_npy(&node_dir.join(file))?),
+        Stored::ArrayReal(file) => PortData::ArrayReal(read_npy(&node_dir.join(file))?),
+        Stored::ArrayComplex(file) => PortData::ArrayComplex(read_npy(&node_dir.join(file))?),
+        Stored::ArrayBool(file) => PortData::ArrayBool(read_npy(&node_dir.join(file))?),
+        Stored::ArrayUnsigned8(file) => PortData::ArrayUnsigned8(read_npy(&node_dir.join(file))?),
+        Stored::ArrayUnsigned16(file) => PortData::ArrayUnsigned16(read_npy(&node_dir.join(file))?),
+        Stored::ArrayInteger32(file) => PortData::ArrayInteger32(read_npy(&node_dir.join(file))?),
+        Stored::ArrayReal32(file) => PortData::ArrayReal32(read_npy(&node_dir.join(file))?),
+        Stored::ArrayComplex32(file) => PortData::ArrayComplex32(read_npy(&node_dir.join(file))?),
+        Stored::Dynamic(file) => PortData::Dynamic(read_npy(&node_dir.join(file))?),
+        Stored::Object(fields) => PortData::Object(
+            fields
+                .into_iter()
+                .map(|(field, stored)| Ok((field, load(node_dir, stored)?)))
+                .collect::<io::Result<_>>()?,
+        ),
+    })
+}
+
+fn write_npy<T: AutoSerialize + Clone>(path: &Path, array: &SharedArray<T>) -> io::Result<()> {
+    let shape: Vec<u64> = array.shape().iter().map(|&n| n as u64).collect();
+    let mut writer = npyz::WriteOptions::new()
+        .default_dtype()
+        .shape(&shape)
+        .writer(BufWriter::new(File::create(path)?))
+        .begin_nd()?;
+    // Iterating an ndarray is always in C order
+    writer.extend(array.iter().cloned())?;
+    writer.finish()
+}
+
+fn read_npy<T: NpyDeserialize + Clone>(path: &Path) -> io::Result<SharedArray<T>> {
+    let npy = npyz::NpyFile::new(BufReader::new(File::open(path)?))?;
+    let shape: Vec<usize> = npy.shape().iter().map(|&n| n as usize).collect();
+    let fortran = npy.order() == npyz::Order::Fortran;
+    let data = npy.into_vec()?;
+    ArrayD::from_shape_vec(shape.set_f(fortran), data)
+        .map(ArrayD::into_shared)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::IxDyn;
+
+    #[test]
+    fn round_trip() {
+        let dir =
+            std::env::temp_dir().join(format!("wire_cache_round_trip_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let array = ArrayD::from_shape_fn(IxDyn(&[2, 3, 4]), |i| {
+            (i[0] * 100 + i[1] * 10 + i[2]) as f64
+        });
+        let complex = array.mapv(|v| Complex64::new(v, -v));
+        let data = PortData::Object(
+            [
+                ("a".to_string(), PortData::ArrayReal(array.into_shared())),
+                (
+                    "b".to_string(),
+                    PortData::ArrayComplex(complex.into_shared()),
+                ),
+                ("c".to_string(), PortData::Complex(Complex64::new(1., 2.))),
+                ("d".to_string(), PortData::Integer(3)),
+            ]
+            .into(),
+        );
+
+        let stored = store(&dir, "out", &data).unwrap();
+        assert!(dir.join("out.a.npy").is_file());
+        let loaded = load(&dir, stored).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(format!("{loaded:?}"), format!("{data:?}"));
+    }
+}
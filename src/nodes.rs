@@ -1,31 +1,47 @@
-use std::time::Duration;
+use std::{
+    hash::{Hash, Hasher},
+    time::Duration,
+};
 
 pub mod constant;
+pub mod expression;
+pub mod group;
 pub mod linspace;
 pub mod math_nodes;
 pub mod plot;
 pub mod plot_complex;
 pub mod port;
 pub mod status;
+pub mod sweep;
 pub mod vector_field;
 
 use crate::app::Message;
-use crate::graph::GraphNode;
-use crate::gui_node::{GUINode, PortDataContainer, PortDataReference};
+use crate::graph::{memo::fingerprint_hasher, GraphNode};
+use crate::gui_node::{GUINode, GuiGraph, PortDataContainer, PortDataReference};
 use crate::interface::node::default_node_size;
+use crate::interface::node_config::{
+    parameters_view, NodeUIParameters, NodeUIWidget, ParameterEdit, Parameters,
+};
+use crate::nodes::expression::Expression;
+use crate::nodes::group::GroupNode;
 use crate::nodes::linspace::LinspaceConfig;
-use crate::nodes::math_nodes::{binary_operation, unary_operation};
+use crate::nodes::math_nodes::{
+    binary_operation, binary_port_type, complex_sinc, sinc, unary_operation, unary_port_type,
+    Operator,
+};
 use crate::nodes::plot::Plot;
 use crate::nodes::plot_complex::Plot2D;
-use crate::python::py_node::PyNode;
+use crate::python::{module_cache, py_node::PyNode};
 use crate::StableMap;
 use derive_more::derive::{Debug, Display};
 use iced::widget::text;
 use iced::{Font, Size};
+use numpy::Complex64;
 use port::{PortData, PortType};
 use serde::{Deserialize, Serialize};
 use status::{NodeError, NodeStatus};
 use strum::{EnumIter, VariantNames};
+use sweep::Sweep;
 use vector_field::VectorField;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -50,6 +66,8 @@ pub enum RustNode {
     Cos,
     Sin,
     Sinc,
+    #[display("Expression")]
+    Expression(Expression),
     #[display("Linspace")]
     Linspace(LinspaceConfig),
     #[display("Plot")]
@@ -58,6 +76,16 @@ pub enum RustNode {
     Plot2D(Plot2D),
     #[display("VectorField")]
     VectorField(VectorField),
+    #[display("Sweep")]
+    Sweep(Sweep),
+    /// The inputs of a group, as seen from inside it
+    #[strum(disabled)]
+    #[display("GroupInputs")]
+    GroupInputs(StableMap<String, PortType>),
+    /// The outputs of a group, as seen from inside it
+    #[strum(disabled)]
+    #[display("GroupOutputs")]
+    GroupOutputs(StableMap<String, PortType>),
 }
 
 #[derive(Clone, Debug, Display, Serialize, Deserialize, PartialEq, PartialOrd)]
@@ -66,6 +94,8 @@ pub enum NodeTemplate {
     RustNode(RustNode),
     #[debug("{_0:?}")]
     PyNode(PyNode),
+    #[debug("{_0:?}")]
+    Group(Box<GroupNode>),
 }
 
 impl From<NodeData> for NodeTemplate {
@@ -96,61 +126,43 @@ impl NodeTemplate {
     }
 }
 
-impl NodeData {
-    fn fallible_compute(
+impl RustNode {
+    fn compute(
         &mut self,
         inputs: StableMap<String, PortDataReference>,
-    ) -> Result<(StableMap<String, PortData>, NodeData), NodeError> {
-        Ok((
-            match &mut self.template {
-                NodeTemplate::RustNode(rust_node) => match rust_node {
-                    RustNode::Identity => [(
-                        "out".to_string(),
-                        (**inputs
-                            .get("a")
-                            .ok_or(NodeError::Input("input 'a' not found".to_string()))?)
-                        .clone(),
-                    )]
-                    .into(),
-                    RustNode::Constant(value) => {
-                        [("out".to_string(), PortData::Real(*value))].into()
-                    }
-                    RustNode::Add => binary_operation(inputs, Box::new(|a, b| a + b))?,
-                    RustNode::Subtract => binary_operation(inputs, Box::new(|a, b| a - b))?,
-                    RustNode::Multiply => binary_operation(inputs, Box::new(|a, b| a * b))?,
-
-                    RustNode::Divide => binary_operation(inputs, Box::new(|a, b| a / b))?,
-
-                    RustNode::Cos => unary_operation(inputs, Box::new(|a| a.cos()))?,
-
-                    RustNode::Sin => unary_operation(inputs, Box::new(|a| a.sin()))?,
-
-                    RustNode::Sinc => unary_operation(
-                        inputs,
-                        Box::new(|a| {
-                            a.map(|x| match x {
-                                0. => 1.,
-                                _ => x.sin() / x,
-                            })
-                        }),
-                    )?,
-
-                    RustNode::Linspace(linspace_config) => linspace_config.compute(inputs),
-                    RustNode::Plot(_) => [].into(),
-                    RustNode::Plot2D(plot_2d) => {
-                        plot_2d.input_changed(inputs);
-                        [].into()
-                    }
-                    RustNode::VectorField(_) => [].into(),
-                },
-
-                NodeTemplate::PyNode(py_node) => py_node.compute(inputs)?,
-            },
-            self.clone(),
-        ))
+    ) -> Result<StableMap<String, PortData>, NodeError> {
+        Ok(match self {
+            RustNode::Identity => [(
+                "out".to_string(),
+                (**inputs
+                    .get("a")
+                    .ok_or(NodeError::Input("input 'a' not found".to_string()))?)
+                .clone(),
+            )]
+            .into(),
+            RustNode::Constant(value) => [("out".to_string(), PortData::Real(*value))].into(),
+            RustNode::Add => binary_operation(inputs, Operator::Add)?,
+            RustNode::Subtract => binary_operation(inputs, Operator::Subtract)?,
+            RustNode::Multiply => binary_operation(inputs, Operator::Multiply)?,
+            RustNode::Divide => binary_operation(inputs, Operator::Divide)?,
+            RustNode::Cos => unary_operation(inputs, f64::cos, Complex64::cos)?,
+            RustNode::Sin => unary_operation(inputs, f64::sin, Complex64::sin)?,
+            RustNode::Sinc => unary_operation(inputs, sinc, complex_sinc)?,
+            RustNode::Expression(expression) => expression.compute(inputs)?,
+            RustNode::Linspace(linspace_config) => linspace_config.compute(inputs),
+            RustNode::Plot(_) => [].into(),
+            RustNode::Plot2D(plot_2d) => {
+                plot_2d.input_changed(inputs);
+                [].into()
+            }
+            RustNode::VectorField(_) => [].into(),
+            RustNode::Sweep(sweep) => sweep.compute(inputs)?,
+            // Data is put on the group's inputs by the group, see `GroupNode::compute`
+            RustNode::GroupInputs(_) => [].into(),
+            RustNode::GroupOutputs(_) => [].into(),
+        })
     }
-}
-impl RustNode {
+
     /// A node can produce any number of "templates" which will be used to populate the
     /// list of selectable new nodes that can be created.
     /// Notably, PyNode will produce a dynamic number of nodes,
@@ -158,16 +170,72 @@ impl RustNode {
     pub fn template_variants(&self) -> NodeData {
         NodeTemplate::RustNode(self.clone()).into()
     }
+
+    /// Configurable fields of the node, edited with the same widgets as python node parameters
+    pub fn parameters(&self) -> NodeUIParameters {
+        match self {
+            RustNode::Constant(value) => constant::parameters(*value),
+            RustNode::Expression(expression) => expression.parameters(),
+            RustNode::Linspace(linspace_config) => linspace_config.parameters(),
+            RustNode::Plot(plot) => plot.parameters(),
+            RustNode::Plot2D(plot) => plot.parameters(),
+            RustNode::VectorField(plot) => plot.parameters(),
+            RustNode::Sweep(sweep) => sweep.parameters(),
+            _ => NodeUIParameters::new(),
+        }
+    }
+
+    /// Set the configurable fields from a full set of `parameters`
+    pub fn set_parameters(&mut self, parameters: &NodeUIParameters) -> Result<(), NodeError> {
+        match self {
+            RustNode::Constant(value) => *value = parameters.get_as("value")?,
+            RustNode::Expression(expression) => expression.set_parameters(parameters)?,
+            RustNode::Linspace(linspace_config) => linspace_config.set_parameters(parameters)?,
+            RustNode::Plot(plot) => plot.set_parameters(parameters)?,
+            RustNode::Plot2D(plot) => plot.set_parameters(parameters)?,
+            RustNode::VectorField(plot) => plot.set_parameters(parameters)?,
+            RustNode::Sweep(sweep) => sweep.set_parameters(parameters)?,
+            _ => {}
+        };
+        Ok(())
+    }
+
+    /// Set parameter `name` to the value of an edited widget, kept within the parameter's limits
+    pub fn update_parameter(&mut self, name: &str, widget: &NodeUIWidget) -> Result<(), NodeError> {
+        let mut parameters = self.parameters();
+        let parameter = parameters
+            .get_mut(name)
+            .ok_or_else(|| NodeError::Config(format!("Parameter '{name}' not found")))?;
+        *parameter = parameter.keep_value(widget);
+        self.set_parameters(&parameters)
+    }
+
+    /// The node's parameters, showing an edit to one of them while its text is still being
+    /// typed. Edits the node has moved on from, e.g. through undo, are left out
+    fn parameters_with(&self, edit: Option<&ParameterEdit>) -> NodeUIParameters {
+        let mut parameters = self.parameters();
+        if let Some(edit) = edit {
+            let mut edited = self.clone();
+            if parameters.contains_key(&edit.name)
+                && edited.update_parameter(&edit.name, &edit.widget).is_ok()
+                && edited == *self
+            {
+                parameters.insert(edit.name.clone(), edit.widget.clone());
+            }
+        }
+        parameters
+    }
 }
 
 impl GraphNode<NodeData, PortType, PortData> for NodeData {
     fn inputs(&self) -> StableMap<String, PortType> {
+        // Math nodes take any numeric data, see `binary_operation`
         let binary_in = [
-            ("a".to_string(), PortType::Real),
-            ("b".to_string(), PortType::Real),
+            ("a".to_string(), PortType::Dynamic),
+            ("b".to_string(), PortType::Dynamic),
         ]
         .into();
-        let unary_in = [("a".to_string(), PortType::Real)].into();
+        let unary_in = [("a".to_string(), PortType::Dynamic)].into();
 
         match &self.template {
             NodeTemplate::RustNode(rn) => match rn {
@@ -180,6 +248,7 @@ impl GraphNode<NodeData, PortType, PortData> for NodeData {
                 RustNode::Cos => unary_in,
                 RustNode::Sin => unary_in,
                 RustNode::Sinc => unary_in,
+                RustNode::Expression(expression) => expression.inputs(),
                 RustNode::Linspace(_) => [].into(),
                 RustNode::Plot(_) => [
                     ("x".to_string(), PortType::Real),
@@ -188,30 +257,105 @@ impl GraphNode<NodeData, PortType, PortData> for NodeData {
                 .into(),
                 RustNode::Plot2D(_) => [("a".to_string(), PortType::ArrayReal)].into(),
                 RustNode::VectorField(_) => [("a".to_string(), PortType::ArrayReal)].into(),
+                RustNode::Sweep(_) => Sweep::inputs(),
+                RustNode::GroupInputs(_) => [].into(),
+                RustNode::GroupOutputs(ports) => ports.clone(),
             },
             NodeTemplate::PyNode(py_node) => py_node.ports.clone().unwrap_or_default().inputs,
+            NodeTemplate::Group(group) => group.inputs(),
+        }
+    }
+
+    fn optional_inputs(&self) -> StableMap<String, Option<PortData>> {
+        match &self.template {
+            // Without x, y is plotted against its index
+            NodeTemplate::RustNode(RustNode::Plot(_)) => [("x".to_string(), None)].into(),
+            NodeTemplate::RustNode(_) => [].into(),
+            // Python defaults are applied by the python node, see `PyNode::compute`
+            NodeTemplate::PyNode(py_node) => py_node
+                .ports
+                .as_ref()
+                .map(|ports| ports.optional.iter().map(|k| (k.clone(), None)).collect())
+                .unwrap_or_default(),
+            NodeTemplate::Group(_) => [].into(),
+        }
+    }
+
+    fn fingerprint(&self) -> Option<u64> {
+        let mut hasher = fingerprint_hasher();
+        ron::to_string(&self.template).ok()?.hash(&mut hasher);
+        match &self.template {
+            // Python nodes may read files, so only declared ones are memoized.
+            // Editing the python source, or a helper next to it, changes the outputs too
+            NodeTemplate::PyNode(py_node) if py_node.cacheable => {
+                module_cache::source_hash(&py_node.absolute_path)?.hash(&mut hasher)
+            }
+            NodeTemplate::PyNode(_) => return None,
+            // The data on a group's inputs isn't known from inside the group
+            NodeTemplate::RustNode(RustNode::GroupInputs(_)) => return None,
+            NodeTemplate::Group(group) => {
+                for nx in group.graph.nodes_ref() {
+                    if nx != group.input_node {
+                        group.graph.get_node(nx)?.fingerprint()?.hash(&mut hasher);
+                    }
+                }
+            }
+            NodeTemplate::RustNode(_) => {}
+        }
+        Some(hasher.finish())
+    }
+
+    fn prepare(&mut self, graph: &GuiGraph, nx: u32) {
+        if let NodeTemplate::RustNode(RustNode::Sweep(sweep)) = &mut self.template {
+            sweep.prepare(graph, nx);
         }
     }
 
     fn outputs(&self) -> StableMap<String, PortType> {
         let real_out = [("out".to_string(), PortType::Real)].into();
+        // The type of math nodes follows their inputs, see `outputs_for`
+        let dynamic_out = [("out".to_string(), PortType::Dynamic)].into();
         match &self.template {
             NodeTemplate::RustNode(rn) => match rn {
                 RustNode::Identity => real_out,
                 RustNode::Constant(_constant_node) => real_out,
-                RustNode::Add => real_out,
-                RustNode::Subtract => real_out,
-                RustNode::Multiply => real_out,
-                RustNode::Divide => real_out,
-                RustNode::Cos => real_out,
-                RustNode::Sin => real_out,
-                RustNode::Sinc => real_out,
+                RustNode::Add => dynamic_out,
+                RustNode::Subtract => dynamic_out,
+                RustNode::Multiply => dynamic_out,
+                RustNode::Divide => dynamic_out,
+                RustNode::Cos => dynamic_out,
+                RustNode::Sin => dynamic_out,
+                RustNode::Sinc => dynamic_out,
+                RustNode::Expression(_) => dynamic_out,
                 RustNode::Linspace(_) => real_out,
                 RustNode::Plot(_) => [].into(),
                 RustNode::Plot2D(_) => [].into(),
                 RustNode::VectorField(_) => [].into(),
+                RustNode::Sweep(_) => Sweep::outputs(),
+                RustNode::GroupInputs(ports) => ports.clone(),
+                RustNode::GroupOutputs(_) => [].into(),
             },
             NodeTemplate::PyNode(py_node) => py_node.ports.clone().unwrap_or_default().outputs,
+            NodeTemplate::Group(group) => group.outputs(),
+        }
+    }
+
+    fn outputs_for(&self, inputs: &StableMap<String, PortType>) -> StableMap<String, PortType> {
+        let binary = |operator| {
+            let (a, b) = (&inputs["a"], &inputs["b"]);
+            [("out".to_string(), binary_port_type(a, b, operator))].into()
+        };
+        let unary = || [("out".to_string(), unary_port_type(&inputs["a"]))].into();
+        match &self.template {
+            NodeTemplate::RustNode(rn) => match rn {
+                RustNode::Add => binary(Operator::Add),
+                RustNode::Subtract => binary(Operator::Subtract),
+                RustNode::Multiply => binary(Operator::Multiply),
+                RustNode::Divide => binary(Operator::Divide),
+                RustNode::Cos | RustNode::Sin | RustNode::Sinc => unary(),
+                _ => self.outputs(),
+            },
+            _ => self.outputs(),
         }
     }
 
@@ -220,12 +364,21 @@ impl GraphNode<NodeData, PortType, PortData> for NodeData {
         inputs: StableMap<String, PortDataContainer>,
     ) -> Result<(StableMap<String, PortData>, NodeData), NodeError> {
         // unpack mutex
-        let data = inputs
-            .keys()
-            .map(|k| (k.clone(), inputs[k].read().unwrap()))
-            .collect();
-
-        self.fallible_compute(data)
+        fn read(
+            inputs: &StableMap<String, PortDataContainer>,
+        ) -> StableMap<String, PortDataReference<'_>> {
+            inputs
+                .iter()
+                .map(|(k, data)| (k.clone(), data.read().unwrap()))
+                .collect()
+        }
+        let outputs = match &mut self.template {
+            NodeTemplate::RustNode(rust_node) => rust_node.compute(read(&inputs))?,
+            NodeTemplate::PyNode(py_node) => py_node.compute(read(&inputs))?,
+            // Groups share their input data with the nodes inside them, rather than copying it
+            NodeTemplate::Group(group) => group.compute(inputs)?,
+        };
+        Ok((outputs, self))
     }
 }
 
@@ -242,10 +395,14 @@ impl GUINode for NodeTemplate {
                 RustNode::Cos => "cos".to_string(),
                 RustNode::Sin => "sin".to_string(),
                 RustNode::Sinc => "sinc".to_string(),
+                RustNode::Expression(_) => "Expression".to_string(),
                 RustNode::Linspace(_linspace_config) => "Linspace".to_string(),
                 RustNode::Plot(_) => "Plot".to_string(),
                 RustNode::Plot2D(_) => "Plot 2D".to_string(),
                 RustNode::VectorField(_) => "Plot Vector Field".to_string(),
+                RustNode::Sweep(_) => "Sweep".to_string(),
+                RustNode::GroupInputs(_) => "Group Inputs".to_string(),
+                RustNode::GroupOutputs(_) => "Group Outputs".to_string(),
             },
             NodeTemplate::PyNode(py_node) => py_node
                 .absolute_path
@@ -253,6 +410,7 @@ impl GUINode for NodeTemplate {
                 .map(|s| s.to_string_lossy())
                 .unwrap_or(("NOT_FOUND").into())
                 .into(),
+            NodeTemplate::Group(group) => group.name.clone(),
         }
     }
 
@@ -260,7 +418,7 @@ impl GUINode for NodeTemplate {
         &self,
         id: u32,
         input_data: StableMap<String, PortDataContainer>,
-    ) -> (iced::Size, iced::Element<Message>) {
+    ) -> (iced::Size, iced::Element<'_, Message>) {
         let dft = default_node_size();
 
         let operation = |s| {
@@ -299,10 +457,18 @@ impl GUINode for NodeTemplate {
                 RustNode::Cos => (dft, trig("cos(α)")),
                 RustNode::Sin => (dft, trig("sin(α)")),
                 RustNode::Sinc => (dft, trig("sinc(α)")),
+                RustNode::Expression(expression) => {
+                    (Size::new(dft.width * 2., dft.height), expression.view(id))
+                }
+                RustNode::Sweep(sweep) => (Size::new(dft.width * 1.5, dft.height), sweep.view()),
 
                 _ => (dft, text(self.name()).into()),
             },
             NodeTemplate::PyNode(_) => (dft, text(self.name()).into()),
+            NodeTemplate::Group(group) => (
+                Size::new(dft.width * 1.5, dft.height),
+                text(format!("[{}]", group.name)).into(),
+            ),
         }
     }
 
@@ -310,15 +476,19 @@ impl GUINode for NodeTemplate {
         &self,
         id: u32,
         input_data: StableMap<String, PortDataContainer>,
-    ) -> Option<iced::Element<Message>> {
+        edit: Option<&ParameterEdit>,
+    ) -> Option<iced::Element<'_, Message>> {
         match &self {
-            NodeTemplate::RustNode(rn) => match rn {
-                RustNode::Plot(plot) => plot.config_view(id, input_data),
-                RustNode::Plot2D(plot) => plot.config_view(id, input_data),
-                RustNode::VectorField(plot) => plot.config_view(id, input_data),
-                _ => None,
-            },
+            NodeTemplate::RustNode(rn) => {
+                let parameters = rn.parameters_with(edit.filter(|edit| edit.node == id));
+                match rn {
+                    RustNode::Plot2D(plot) => plot.config_view(id, &parameters),
+                    _ if parameters.is_empty() => None,
+                    _ => Some(parameters_view(id, &parameters)),
+                }
+            }
             NodeTemplate::PyNode(pn) => pn.config_view(id, input_data),
+            NodeTemplate::Group(group) => Some(group.config_view(id)),
         }
     }
 }
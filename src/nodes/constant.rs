@@ -1,4 +1,7 @@
-use crate::{app::Message, nodes::NodeTemplate};
+use crate::{
+    app::Message,
+    interface::node_config::{Limits, NodeUIParameters, NodeUIWidget},
+};
 use iced::{
     widget::{column, container, slider, text},
     Alignment::Center,
@@ -6,14 +9,25 @@ use iced::{
     Length::Fill,
 };
 
-use super::RustNode;
+pub fn parameters(value: f64) -> NodeUIParameters {
+    [(
+        "value".into(),
+        NodeUIWidget::number(value, Limits::default()),
+    )]
+    .into()
+}
 
+/// The slider covers -1 to 1, other values are typed in the side bar
 pub fn view<'a>(id: u32, value: f64) -> Element<'a, Message> {
     container(
         column![
             text(format!("{value:.1}")),
             slider(-1.0..=1., value, move |value| {
-                Message::UpdateNodeTemplate(id, NodeTemplate::RustNode(RustNode::Constant(value)))
+                Message::UpdateNodeParameter(
+                    id,
+                    "value".into(),
+                    NodeUIWidget::number(value, Limits::default()),
+                )
             })
             .step(0.05)
             .width(Fill),
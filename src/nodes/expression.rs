@@ -0,0 +1,642 @@
+use std::{f64::consts, iter::Peekable, str::CharIndices};
+
+use iced::{
+    widget::{container, text},
+    Element, Font,
+    Length::Fill,
+};
+use ndarray::{arr0, ArcArray};
+use numpy::Complex64;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    math_nodes::{complex_sinc, sinc, zip_broadcast, Operator},
+    port::{PortData, PortType, SharedArray},
+    status::NodeError,
+};
+use crate::{
+    app::Message,
+    gui_node::PortDataReference,
+    interface::node_config::{NodeUIParameters, NodeUIWidget, Parameters},
+    StableMap,
+};
+
+/// A formula of the node's inputs, such as `a * cos(b) + 1`, evaluated element by element like
+/// numpy would. Every variable in the formula is an input, which can be a real or complex scalar
+/// or array, and inputs of different shapes are broadcast together.
+///
+/// Formulas have `+ - * /`, powers with `**` or `^`, imaginary numbers like `2j`, the constants
+/// `pi` and `e`, and functions of one argument:
+/// - element-wise: `sin cos tan exp log sqrt sinc abs angle conj real imag`
+/// - reductions over all elements, giving a scalar: `sum prod mean min max`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+pub struct Expression {
+    pub formula: String,
+    /// Variables of the last formula that parsed, so wires stay connected while the formula is
+    /// being typed
+    variables: Vec<String>,
+}
+
+impl Default for Expression {
+    fn default() -> Self {
+        Self::new("a * cos(b) + 1")
+    }
+}
+
+impl Expression {
+    pub fn new(formula: &str) -> Self {
+        let mut expression = Self {
+            formula: String::new(),
+            variables: vec![],
+        };
+        expression.set_formula(formula);
+        expression
+    }
+
+    /// Change the formula. The inputs only change if it parses
+    pub fn set_formula(&mut self, formula: &str) {
+        formula.clone_into(&mut self.formula);
+        if let Ok(expr) = parse(formula) {
+            let mut variables = vec![];
+            expr.variables(&mut variables);
+            variables.sort();
+            variables.dedup();
+            self.variables = variables;
+        }
+    }
+
+    pub fn inputs(&self) -> StableMap<String, PortType> {
+        self.variables
+            .iter()
+            .map(|name| (name.clone(), PortType::Dynamic))
+            .collect()
+    }
+
+    pub fn compute(
+        &self,
+        inputs: StableMap<String, PortDataReference>,
+    ) -> Result<StableMap<String, PortData>, NodeError> {
+        let expr = parse(&self.formula)?;
+        let out = expr.evaluate(&|name| {
+            let data = inputs.get(name).ok_or(NodeError::input_error(name))?;
+            match &**data {
+                PortData::Complex(_) | PortData::ArrayComplex(_) | PortData::ArrayComplex32(_) => {
+                    data.complexes().map(Value::Complex)
+                }
+                _ => data.reals().map(Value::Real),
+            }
+            .ok_or_else(|| NodeError::Input(format!("Input '{name}' must be numeric")))
+        })?;
+
+        // Scalars in, scalar out
+        let out = match out {
+            Value::Real(a) if a.ndim() == 0 => PortData::Real(a[[]]),
+            Value::Real(a) => PortData::ArrayReal(a),
+            Value::Complex(a) if a.ndim() == 0 => PortData::Complex(a[[]]),
+            Value::Complex(a) => PortData::ArrayComplex(a),
+        };
+        Ok([("out".into(), out)].into())
+    }
+
+    pub fn parameters(&self) -> NodeUIParameters {
+        [(
+            "formula".to_string(),
+            NodeUIWidget::Text(self.formula.clone()),
+        )]
+        .into()
+    }
+
+    pub fn set_parameters(&mut self, parameters: &NodeUIParameters) -> Result<(), NodeError> {
+        self.set_formula(&parameters.get_as::<String>("formula")?);
+        Ok(())
+    }
+
+    pub fn view(&self, _id: u32) -> Element<'_, Message> {
+        container(
+            text(&self.formula)
+                .font(Font::with_name("DejaVu Math TeX Gyre"))
+                .size(16),
+        )
+        .center(Fill)
+        .padding([0., 8.])
+        .into()
+    }
+}
+
+/// Data as it is computed with, promoted to complex when mixed with complex data
+#[derive(Clone, Debug)]
+enum Value {
+    Real(SharedArray<f64>),
+    Complex(SharedArray<Complex64>),
+}
+
+impl Value {
+    fn complexes(&self) -> SharedArray<Complex64> {
+        match self {
+            Value::Real(a) => a.mapv(|r| Complex64::new(r, 0.)).into_shared(),
+            Value::Complex(a) => a.clone(),
+        }
+    }
+
+    fn map(&self, real: fn(f64) -> f64, complex: fn(Complex64) -> Complex64) -> Value {
+        match self {
+            Value::Real(a) => Value::Real(a.mapv(real).into_shared()),
+            Value::Complex(a) => Value::Complex(a.mapv(complex).into_shared()),
+        }
+    }
+
+    /// A real function of the elements, whether they are real or complex
+    fn map_real(&self, real: fn(f64) -> f64, complex: fn(Complex64) -> f64) -> Value {
+        match self {
+            Value::Real(a) => Value::Real(a.mapv(real).into_shared()),
+            Value::Complex(a) => Value::Real(a.mapv(complex).into_shared()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Log,
+    Sqrt,
+    Sinc,
+    Abs,
+    Angle,
+    Conj,
+    Real,
+    Imag,
+    Sum,
+    Prod,
+    Mean,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "exp" => Function::Exp,
+            "log" => Function::Log,
+            "sqrt" => Function::Sqrt,
+            "sinc" => Function::Sinc,
+            "abs" => Function::Abs,
+            "angle" => Function::Angle,
+            "conj" => Function::Conj,
+            "real" => Function::Real,
+            "imag" => Function::Imag,
+            "sum" => Function::Sum,
+            "prod" => Function::Prod,
+            "mean" => Function::Mean,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            _ => return None,
+        })
+    }
+
+    fn apply(self, value: &Value) -> Result<Value, NodeError> {
+        Ok(match self {
+            Function::Sin => value.map(f64::sin, |c| c.sin()),
+            Function::Cos => value.map(f64::cos, |c| c.cos()),
+            Function::Tan => value.map(f64::tan, |c| c.tan()),
+            Function::Exp => value.map(f64::exp, |c| c.exp()),
+            // Negative reals give NaN, as in numpy
+            Function::Log => value.map(f64::ln, |c| c.ln()),
+            Function::Sqrt => value.map(f64::sqrt, |c| c.sqrt()),
+            Function::Sinc => value.map(sinc, complex_sinc),
+            Function::Abs => value.map_real(f64::abs, |c| c.norm()),
+            Function::Angle => value.map_real(|x| 0f64.atan2(x), |c| c.arg()),
+            Function::Conj => value.map(|x| x, |c| c.conj()),
+            Function::Real => value.map_real(|x| x, |c| c.re),
+            Function::Imag => value.map_real(|_| 0., |c| c.im),
+            Function::Sum => match value {
+                Value::Real(a) => Value::Real(arr0(a.sum()).into_dyn().into_shared()),
+                Value::Complex(a) => Value::Complex(arr0(a.sum()).into_dyn().into_shared()),
+            },
+            Function::Prod => match value {
+                Value::Real(a) => Value::Real(arr0(a.product()).into_dyn().into_shared()),
+                Value::Complex(a) => Value::Complex(arr0(a.product()).into_dyn().into_shared()),
+            },
+            // The mean of nothing is NaN, as in numpy
+            Function::Mean => match value {
+                Value::Real(a) => {
+                    let mean = a.sum() / a.len() as f64;
+                    Value::Real(arr0(mean).into_dyn().into_shared())
+                }
+                Value::Complex(a) => {
+                    let mean = a.sum() / a.len() as f64;
+                    Value::Complex(arr0(mean).into_dyn().into_shared())
+                }
+            },
+            Function::Min | Function::Max => {
+                let Value::Real(a) = value else {
+                    return Err(NodeError::Input(format!(
+                        "{self:?} of complex values is not defined"
+                    )));
+                };
+                // NaN wins, as in numpy
+                let extreme = a.iter().copied().reduce(|m, x| match self {
+                    Function::Min if x.is_nan() || x < m => x,
+                    Function::Max if x.is_nan() || x > m => x,
+                    _ => m,
+                });
+                let extreme = extreme.ok_or_else(|| {
+                    NodeError::Input(format!("{self:?} of an empty array is not defined"))
+                })?;
+                Value::Real(arr0(extreme).into_dyn().into_shared())
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f64),
+    Imaginary(f64),
+    Variable(String),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+impl Expr {
+    /// Every variable used, in order of use
+    fn variables(&self, variables: &mut Vec<String>) {
+        match self {
+            Expr::Number(_) | Expr::Imaginary(_) => {}
+            Expr::Variable(name) => variables.push(name.clone()),
+            Expr::Negate(a) | Expr::Call(_, a) => a.variables(variables),
+            Expr::Binary(_, a, b) => {
+                a.variables(variables);
+                b.variables(variables);
+            }
+        }
+    }
+
+    fn evaluate(
+        &self,
+        variable: &impl Fn(&str) -> Result<Value, NodeError>,
+    ) -> Result<Value, NodeError> {
+        Ok(match self {
+            Expr::Number(n) => Value::Real(ArcArray::from_elem(vec![], *n)),
+            Expr::Imaginary(n) => {
+                Value::Complex(ArcArray::from_elem(vec![], Complex64::new(0., *n)))
+            }
+            Expr::Variable(name) => variable(name)?,
+            Expr::Negate(a) => a.evaluate(variable)?.map(|x| -x, |c| -c),
+            Expr::Call(function, a) => function.apply(&a.evaluate(variable)?)?,
+            Expr::Binary(operator, a, b) => match (a.evaluate(variable)?, b.evaluate(variable)?) {
+                (Value::Real(a), Value::Real(b)) => Value::Real(
+                    zip_broadcast(a.view(), b.view(), |a, b| operator.real(*a, *b))?.into_shared(),
+                ),
+                (a, b) => Value::Complex(
+                    zip_broadcast(a.complexes().view(), b.complexes().view(), |a, b| {
+                        operator.complex(*a, *b)
+                    })?
+                    .into_shared(),
+                ),
+            },
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Imaginary(f64),
+    Name(String),
+    Operator(Operator),
+    Open,
+    Close,
+    Comma,
+}
+
+/// Split a formula into tokens, with the position of each
+fn tokenize(formula: &str) -> Result<Vec<(usize, Token)>, NodeError> {
+    let mut tokens = vec![];
+    let mut chars = formula.char_indices().peekable();
+    while let Some(&(at, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' | '.' => number(formula, &mut chars)?,
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&(_, c)) = chars
+                    .peek()
+                    .filter(|(_, c)| c.is_alphanumeric() || *c == '_')
+                {
+                    name.push(c);
+                    chars.next();
+                }
+                Token::Name(name)
+            }
+            _ => {
+                chars.next();
+                match c {
+                    '+' => Token::Operator(Operator::Add),
+                    '-' => Token::Operator(Operator::Subtract),
+                    '*' if chars.next_if(|(_, c)| *c == '*').is_some() => {
+                        Token::Operator(Operator::Power)
+                    }
+                    '*' => Token::Operator(Operator::Multiply),
+                    '/' => Token::Operator(Operator::Divide),
+                    '^' => Token::Operator(Operator::Power),
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    ',' => Token::Comma,
+                    _ => return Err(syntax_error(formula, at, &format!("unexpected '{c}'"))),
+                }
+            }
+        };
+        tokens.push((at, token));
+    }
+    Ok(tokens)
+}
+
+/// A number such as `2`, `1.5e-3`, or `2j`
+fn number(formula: &str, chars: &mut Peekable<CharIndices>) -> Result<Token, NodeError> {
+    let start = chars.peek().map_or(formula.len(), |(at, _)| *at);
+    let mut end = start;
+    let mut exponent = false;
+    while let Some(&(at, c)) = chars.peek() {
+        let exponent_sign = matches!(c, '+' | '-') && formula[..at].ends_with(['e', 'E']);
+        let exponent_start = matches!(c, 'e' | 'E') && !exponent;
+        if !(c.is_ascii_digit() || c == '.' || exponent_sign || exponent_start) {
+            break;
+        }
+        exponent |= exponent_start;
+        end = at + c.len_utf8();
+        chars.next();
+    }
+    let value = formula[start..end]
+        .parse()
+        .map_err(|_| syntax_error(formula, start, "invalid number"))?;
+    Ok(match chars.next_if(|(_, c)| matches!(c, 'j' | 'J')) {
+        Some(_) => Token::Imaginary(value),
+        None => Token::Number(value),
+    })
+}
+
+fn syntax_error(formula: &str, at: usize, message: &str) -> NodeError {
+    let column = formula[..at].chars().count() + 1;
+    NodeError::Syntax(format!("{message} at column {column} of '{formula}'"))
+}
+
+/// Parse a formula, see `Expression`
+fn parse(formula: &str) -> Result<Expr, NodeError> {
+    let mut parser = Parser {
+        formula,
+        tokens: tokenize(formula)?,
+        next: 0,
+    };
+    let expr = parser.sum()?;
+    match parser.tokens.get(parser.next) {
+        None => Ok(expr),
+        Some((at, _)) => Err(syntax_error(formula, *at, "unexpected input")),
+    }
+}
+
+/// Recursive descent, from the lowest precedence to the highest
+struct Parser<'a> {
+    formula: &'a str,
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn error(&self, message: &str) -> NodeError {
+        let at = self
+            .tokens
+            .get(self.next)
+            .map_or(self.formula.len(), |(at, _)| *at);
+        syntax_error(self.formula, at, message)
+    }
+
+    /// Take the next token if it is one of `operators`
+    fn operator(&mut self, operators: &[Operator]) -> Option<Operator> {
+        match self.peek() {
+            Some(&Token::Operator(operator)) if operators.contains(&operator) => {
+                self.next += 1;
+                Some(operator)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, NodeError> {
+        let mut expr = self.product()?;
+        while let Some(operator) = self.operator(&[Operator::Add, Operator::Subtract]) {
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, NodeError> {
+        let mut expr = self.unary()?;
+        while let Some(operator) = self.operator(&[Operator::Multiply, Operator::Divide]) {
+            expr = Expr::Binary(operator, Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    /// Signs bind less tightly than powers, `-a**2` is `-(a**2)`
+    fn unary(&mut self) -> Result<Expr, NodeError> {
+        match self.operator(&[Operator::Add, Operator::Subtract]) {
+            Some(Operator::Subtract) => Ok(Expr::Negate(Box::new(self.unary()?))),
+            Some(_) => self.unary(),
+            None => self.power(),
+        }
+    }
+
+    /// Powers group to the right, `a**b**c` is `a**(b**c)`
+    fn power(&mut self) -> Result<Expr, NodeError> {
+        let base = self.atom()?;
+        match self.operator(&[Operator::Power]) {
+            Some(operator) => Ok(Expr::Binary(
+                operator,
+                Box::new(base),
+                Box::new(self.unary()?),
+            )),
+            None => Ok(base),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, NodeError> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("unexpected end"))?;
+        let expr = match token {
+            Token::Number(n) => Expr::Number(n),
+            Token::Imaginary(n) => Expr::Imaginary(n),
+            Token::Open => {
+                self.next += 1;
+                let expr = self.sum()?;
+                self.close()?;
+                return Ok(expr);
+            }
+            Token::Name(name) => {
+                if self.tokens.get(self.next + 1).map(|(_, t)| t) == Some(&Token::Open) {
+                    let function = Function::from_name(&name)
+                        .ok_or_else(|| self.error(&format!("unknown function '{name}'")))?;
+                    self.next += 2;
+                    let argument = self.sum()?;
+                    if self.peek() == Some(&Token::Comma) {
+                        return Err(self.error(&format!("'{name}' takes one argument")));
+                    }
+                    self.close()?;
+                    return Ok(Expr::Call(function, Box::new(argument)));
+                }
+                match name.as_str() {
+                    "pi" => Expr::Number(consts::PI),
+                    "e" => Expr::Number(consts::E),
+                    _ => Expr::Variable(name),
+                }
+            }
+            _ => return Err(self.error("unexpected input")),
+        };
+        self.next += 1;
+        Ok(expr)
+    }
+
+    fn close(&mut self) -> Result<(), NodeError> {
+        match self.peek() {
+            Some(Token::Close) => {
+                self.next += 1;
+                Ok(())
+            }
+            _ => Err(self.error("expected ')'")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{array, ArrayD};
+
+    use super::*;
+
+    fn evaluate(formula: &str, inputs: &[(&str, PortData)]) -> Result<PortData, NodeError> {
+        let expression = Expression::new(formula);
+        let locks: Vec<_> = inputs
+            .iter()
+            .map(|(name, data)| (name.to_string(), std::sync::RwLock::new(data.clone())))
+            .collect();
+        let inputs = locks
+            .iter()
+            .map(|(name, lock)| (name.clone(), lock.read().unwrap()))
+            .collect();
+        expression
+            .compute(inputs)
+            .map(|mut outputs| outputs.remove("out").unwrap())
+    }
+
+    fn reals(a: ArrayD<f64>) -> PortData {
+        PortData::ArrayReal(a.into_shared())
+    }
+
+    #[test]
+    fn parse_formulas() {
+        let expression = Expression::new("a * cos(b) + 1 - a");
+        assert_eq!(expression.variables, vec!["a", "b"]);
+        assert_eq!(
+            parse("-a**2"),
+            parse("-(a ** (2))"),
+            "signs bind less tightly than powers"
+        );
+        assert_eq!(parse("2^3^2"), parse("2**(3**2)"));
+        assert_eq!(parse("1.5e-3j"), Ok(Expr::Imaginary(1.5e-3)));
+
+        for (formula, error) in [
+            ("a *", "unexpected end at column 4"),
+            ("(a", "expected ')' at column 3"),
+            ("a $ b", "unexpected '$' at column 3"),
+            ("foo(a)", "unknown function 'foo' at column 1"),
+            ("sum(a, b)", "'sum' takes one argument at column 6"),
+            ("a b", "unexpected input at column 3"),
+        ] {
+            assert!(
+                matches!(parse(formula), Err(NodeError::Syntax(e)) if e.starts_with(error)),
+                "{formula}: {:?}",
+                parse(formula)
+            );
+        }
+
+        // Inputs are kept while the formula doesn't parse
+        let mut expression = Expression::new("a + b");
+        expression.set_formula("a + b + ");
+        assert_eq!(expression.inputs().len(), 2);
+        expression.set_formula("x");
+        assert_eq!(expression.inputs().keys().collect::<Vec<_>>(), ["x"]);
+    }
+
+    #[test]
+    fn evaluate_formulas() {
+        let a = array![1., 2., 3.].into_dyn();
+        let column = array![[10.], [20.]].into_dyn();
+        let out = evaluate(
+            "a * b + 1",
+            &[("a", reals(a.clone())), ("b", reals(column))],
+        );
+        assert!(matches!(
+            out,
+            Ok(PortData::ArrayReal(out)) if out == array![[11., 21., 31.], [21., 41., 61.]].into_dyn()
+        ));
+
+        // Scalars in, scalar out
+        let out = evaluate("2 * pi * f", &[("f", PortData::Real(0.5))]);
+        assert!(matches!(out, Ok(PortData::Real(r)) if r == consts::PI));
+        assert!(matches!(
+            evaluate("sum(a) / max(a)", &[("a", reals(a.clone()))]),
+            Ok(PortData::Real(2.))
+        ));
+
+        // Complex data, and functions between real and complex
+        let out = evaluate(
+            "abs(exp(1j * pi * a)) + angle(conj(1j))",
+            &[("a", reals(a.clone()))],
+        );
+        let expected = a.mapv(|_| 1. - consts::FRAC_PI_2);
+        assert!(matches!(
+            out,
+            Ok(PortData::ArrayReal(out)) if out.iter().zip(&expected).all(|(o, e)| (o - e).abs() < 1e-12)
+        ));
+        let c = PortData::Complex(Complex64::new(3., 4.));
+        assert!(matches!(
+            evaluate("c * 2 + sqrt(-4 + 0j)", &[("c", c.clone())]),
+            Ok(PortData::Complex(c)) if c == Complex64::new(6., 10.)
+        ));
+        assert!(matches!(
+            evaluate("imag(c) + real(c)", &[("c", c)]),
+            Ok(PortData::Real(7.))
+        ));
+
+        // Integer inputs are computed as reals
+        let bytes = PortData::ArrayUnsigned8(array![0u8, 255].into_dyn().into_shared());
+        assert!(matches!(
+            evaluate("mean(a)", &[("a", bytes)]),
+            Ok(PortData::Real(127.5))
+        ));
+
+        assert!(matches!(
+            evaluate("a + b", &[("a", reals(a.clone())), ("b", reals(array![1., 2.].into_dyn()))]),
+            Err(NodeError::Input(e)) if e == "Shapes [3] and [2] can't be broadcast together"
+        ));
+        assert!(matches!(
+            evaluate("a + b", &[("a", reals(a))]),
+            Err(NodeError::Input(e)) if e == "Input 'b' not found"
+        ));
+    }
+}
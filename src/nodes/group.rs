@@ -0,0 +1,386 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use iced::{
+    widget::{button, column, row, text, text_input},
+    Alignment::Center,
+    Element,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::Message,
+    graph::{
+        scheduler::{Scheduler, SyncExecutor},
+        GraphNode, PortRef, IO,
+    },
+    gui_node::{GUINode, GuiGraph, PortDataContainer},
+    math::Point,
+    nodes::{
+        port::{PortData, PortType},
+        status::NodeError,
+        NodeTemplate, RustNode,
+    },
+    widget::workspace,
+    StableMap,
+};
+
+/// Horizontal distance between the group's port nodes, and the nodes inside it
+const PORT_NODE_MARGIN: f32 = 250.;
+
+/// A network inside a single node.
+/// The group's inputs are the outputs of a `RustNode::GroupInputs` node inside it,
+/// and its outputs are the inputs of a `RustNode::GroupOutputs` node
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GroupNode {
+    pub name: String,
+    pub graph: GuiGraph,
+    /// Layout of the inner nodes, used when the group is opened
+    pub shapes: workspace::State,
+    pub input_node: u32,
+    pub output_node: u32,
+}
+
+/// How the wires crossing into and out of a group were rerouted when it was created
+pub struct Collapsed {
+    pub group: GroupNode,
+    /// Outer output port → group input port name
+    pub inputs: Vec<(PortRef, String)>,
+    /// Group output port name → outer input port
+    pub outputs: Vec<(String, PortRef)>,
+}
+
+impl GroupNode {
+    /// Move `nodes` of `graph` into a new group. Wires that cross the selection become the
+    /// group's ports: one input for each outer output feeding the selection, and one output for
+    /// each inner output used outside of it. `graph` itself is not modified
+    pub fn collapse(
+        graph: &GuiGraph,
+        shape_positions: &indexmap::IndexMap<u32, Point>,
+        nodes: &HashSet<u32>,
+    ) -> Collapsed {
+        let (incoming, outgoing) = graph.boundary_edges(nodes);
+        let mut inner = graph.subgraph(nodes);
+
+        //// Inputs
+        let mut input_ports = StableMap::new();
+        let mut inputs: Vec<(PortRef, String)> = vec![];
+        let mut inner_input_edges = vec![];
+        for (from, to) in incoming {
+            let name = match inputs.iter().find(|(outer, _)| *outer == from) {
+                Some((_, name)) => name.clone(),
+                None => {
+                    let name = unique_name(&input_ports, &to.name);
+                    let port_type = port_type(&inner, &to);
+                    input_ports.insert(name.clone(), port_type);
+                    inputs.push((from, name.clone()));
+                    name
+                }
+            };
+            inner_input_edges.push((name, to));
+        }
+
+        //// Outputs
+        let mut output_ports = StableMap::new();
+        let mut outputs = vec![];
+        let mut inner_output_edges: Vec<(PortRef, String)> = vec![];
+        for (from, to) in outgoing {
+            let name = match inner_output_edges.iter().find(|(inner, _)| *inner == from) {
+                Some((_, name)) => name.clone(),
+                None => {
+                    let name = unique_name(&output_ports, &from.name);
+                    output_ports.insert(name.clone(), port_type(graph, &from));
+                    inner_output_edges.push((from, name.clone()));
+                    name
+                }
+            };
+            outputs.push((name, to));
+        }
+
+        let input_node =
+            inner.node(NodeTemplate::RustNode(RustNode::GroupInputs(input_ports)).into());
+        let output_node =
+            inner.node(NodeTemplate::RustNode(RustNode::GroupOutputs(output_ports)).into());
+        for (name, to) in inner_input_edges {
+            let from = PortRef {
+                node: input_node,
+                name,
+                io: IO::Out,
+            };
+            inner
+                .add_edge_from_ref(&from, &to)
+                .expect("group input has the type of the port it feeds");
+        }
+        for (from, name) in inner_output_edges {
+            let to = PortRef {
+                node: output_node,
+                name,
+                io: IO::In,
+            };
+            inner
+                .add_edge_from_ref(&from, &to)
+                .expect("group output has the type of the port that feeds it");
+        }
+
+        //// Layout, with the port nodes either side of the selection
+        let mut positions: indexmap::IndexMap<u32, Point> = shape_positions
+            .iter()
+            .filter(|(nx, _)| nodes.contains(nx))
+            .map(|(nx, point)| (*nx, *point))
+            .collect();
+        let (min, max) = bounds(positions.values());
+        let middle = (min.y + max.y) / 2.;
+        positions.insert(input_node, Point::new(min.x - PORT_NODE_MARGIN, middle));
+        positions.insert(output_node, Point::new(max.x + PORT_NODE_MARGIN, middle));
+
+        Collapsed {
+            group: GroupNode {
+                name: "Group".to_string(),
+                graph: inner,
+                shapes: workspace::State::new(positions),
+                input_node,
+                output_node,
+            },
+            inputs,
+            outputs,
+        }
+    }
+
+    pub fn inputs(&self) -> StableMap<String, PortType> {
+        self.graph
+            .get_node(self.input_node)
+            .map(|node| node.outputs())
+            .unwrap_or_default()
+    }
+
+    pub fn outputs(&self) -> StableMap<String, PortType> {
+        self.graph
+            .get_node(self.output_node)
+            .map(|node| node.inputs())
+            .unwrap_or_default()
+    }
+
+    /// Run the inner network with `inputs` on the group's input node,
+    /// returning the data that reaches the group's output node
+    pub fn compute(
+        &self,
+        inputs: StableMap<String, PortDataContainer>,
+    ) -> Result<StableMap<String, PortData>, NodeError> {
+        let mut graph = self.graph.clone();
+        graph.restore_wire_data(self.input_node, inputs);
+
+        let mut errors = vec![];
+        Scheduler::default().run(
+            &mut graph,
+            &mut SyncExecutor::default(),
+            |graph, completion| match completion.result {
+                Ok(node) => {
+                    let _ = graph.set_node_data(completion.nx, node);
+                }
+                Err(e) => {
+                    let name = graph
+                        .get_node(completion.nx)
+                        .map(|node| node.template.name())
+                        .unwrap_or_default();
+                    errors.push(format!("{name} #{}: {e}", completion.nx));
+                }
+            },
+        );
+        if !errors.is_empty() {
+            return Err(NodeError::Runtime(errors.join("\n")));
+        }
+
+        let missing = graph.missing_inputs(&self.output_node);
+        if !missing.is_empty() {
+            return Err(NodeError::Output(format!(
+                "No data for group outputs: {}",
+                missing.join(", ")
+            )));
+        }
+        Ok(graph
+            .get_input_data(&self.output_node)
+            .into_iter()
+            .map(|(name, data)| (name, data.read().expect("not poisoned").clone()))
+            .collect())
+    }
+
+    pub fn config_view(&self, id: u32) -> Element<'_, Message> {
+        column![
+            row![
+                text("name:"),
+                text_input("Group", &self.name).on_input(move |name| {
+                    Message::UpdateNodeTemplate(
+                        id,
+                        NodeTemplate::Group(Box::new(GroupNode {
+                            name,
+                            ..self.clone()
+                        })),
+                    )
+                })
+            ]
+            .align_y(Center)
+            .spacing(4.),
+            text(format!(
+                "{} nodes",
+                self.graph.nodes_ref().len().saturating_sub(2)
+            ))
+            .size(12.),
+            row![
+                button(text("open")).on_press(Message::OpenGroup(id)),
+                button(text("save as macro"))
+                    .style(button::secondary)
+                    .on_press(Message::SaveGroupMacro(id)),
+            ]
+            .spacing(4.)
+        ]
+        .spacing(8.)
+        .into()
+    }
+}
+
+/// `name`, or `name_1`, `name_2`... if it is already in `ports`
+fn unique_name(ports: &StableMap<String, PortType>, name: &str) -> String {
+    if !ports.contains_key(name) {
+        return name.to_string();
+    }
+    (1..)
+        .map(|i| format!("{name}_{i}"))
+        .find(|candidate| !ports.contains_key(candidate))
+        .expect("there is always an unused name")
+}
+
+fn port_type(graph: &GuiGraph, port: &PortRef) -> PortType {
+    graph
+        .get_node(port.node)
+        .and_then(|node| {
+            match port.io {
+                IO::In => node.inputs(),
+                IO::Out => graph.output_types(port.node),
+            }
+            .remove(&port.name)
+        })
+        .unwrap_or(PortType::Dynamic)
+}
+
+/// Top left and bottom right of `points`
+fn bounds<'a>(points: impl Iterator<Item = &'a Point>) -> (Point, Point) {
+    points.fold(
+        (
+            Point::new(f32::INFINITY, f32::INFINITY),
+            Point::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+        ),
+        |(min, max), p| {
+            (
+                Point::new(min.x.min(p.x), min.y.min(p.y)),
+                Point::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        },
+    )
+}
+
+impl fmt::Debug for GroupNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupNode")
+            .field("name", &self.name)
+            .field("nodes", &self.graph.nodes_ref())
+            .finish()
+    }
+}
+
+impl fmt::Display for GroupNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Groups are equal if they would serialize the same, `Graph` has no equality of its own
+impl PartialEq for GroupNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.input_node == other.input_node
+            && self.output_node == other.output_node
+            && ron::to_string(&self.graph).ok() == ron::to_string(&other.graph).ok()
+            && ron::to_string(&self.shapes.shape_positions).ok()
+                == ron::to_string(&other.shapes.shape_positions).ok()
+    }
+}
+
+/// Groups are ordered by name, for the add node tree
+impl PartialOrd for GroupNode {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.name.partial_cmp(&other.name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, RwLock};
+
+    use super::*;
+
+    #[test]
+    fn collapse_and_compute() {
+        let mut graph = GuiGraph::new();
+        let a = graph.node(NodeTemplate::RustNode(RustNode::Constant(2.)).into());
+        let b = graph.node(NodeTemplate::RustNode(RustNode::Constant(3.)).into());
+        let mul = graph.node(NodeTemplate::RustNode(RustNode::Multiply).into());
+        let id = graph.node(NodeTemplate::RustNode(RustNode::Identity).into());
+        let out = graph.node(NodeTemplate::RustNode(RustNode::Identity).into());
+        graph
+            .add_edge_from_ref(
+                &PortRef::new(a, "out", IO::Out),
+                &PortRef::new(mul, "a", IO::In),
+            )
+            .unwrap();
+        graph
+            .add_edge_from_ref(
+                &PortRef::new(b, "out", IO::Out),
+                &PortRef::new(mul, "b", IO::In),
+            )
+            .unwrap();
+        graph
+            .add_edge_from_ref(
+                &PortRef::new(mul, "out", IO::Out),
+                &PortRef::new(id, "a", IO::In),
+            )
+            .unwrap();
+        graph
+            .add_edge_from_ref(
+                &PortRef::new(id, "out", IO::Out),
+                &PortRef::new(out, "a", IO::In),
+            )
+            .unwrap();
+        let positions = graph
+            .nodes_ref()
+            .into_iter()
+            .map(|nx| (nx, Point::new(nx as f32, 0.)))
+            .collect();
+
+        let Collapsed {
+            group,
+            inputs,
+            outputs,
+        } = GroupNode::collapse(&graph, &positions, &[mul, id].into());
+        assert_eq!(group.inputs().keys().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(group.outputs().keys().collect::<Vec<_>>(), ["out"]);
+        assert_eq!(
+            inputs[0],
+            (PortRef::new(a, "out", IO::Out), "a".to_string())
+        );
+        assert_eq!(
+            outputs,
+            [("out".to_string(), PortRef::new(out, "a", IO::In))]
+        );
+
+        let data = |x| Arc::new(RwLock::new(PortData::Real(x)));
+        let computed = group
+            .compute([("a".into(), data(2.)), ("b".into(), data(3.))].into())
+            .unwrap();
+        // Scalars in, scalar out
+        let PortData::Real(product) = computed["out"] else {
+            panic!("expected a real, got {:?}", computed["out"]);
+        };
+        assert_eq!(product, 6.);
+        assert!(group.compute([].into()).is_err());
+    }
+}
@@ -1,13 +1,13 @@
-use super::PortData;
+use super::{status::NodeError, PortData};
 use crate::gui_node::PortDataReference;
-use crate::nodes::{NodeTemplate, RustNode};
+use crate::interface::node_config::{Limits, NodeUIParameters, NodeUIWidget, Parameters};
 use crate::StableMap;
 use crate::{app::Message, math::linspace};
 use iced::{
-    widget::{column, container, horizontal_rule, row, text, text_input, TextInput},
+    widget::{column, text},
     Alignment::Center,
-    Color, Element,
-    Length::{Fill, Shrink},
+    Element,
+    Length::Fill,
 };
 use ndarray::ArrayD;
 use numpy::IxDyn;
@@ -15,9 +15,9 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, PartialOrd)]
 pub struct LinspaceConfig {
-    start: f64,
-    stop: f64,
-    num: i64,
+    pub start: f64,
+    pub stop: f64,
+    pub num: i64,
 }
 
 impl Default for LinspaceConfig {
@@ -53,77 +53,40 @@ impl LinspaceConfig {
                         .map(|v| v as f64)
                         .collect::<Vec<_>>(),
                 )
-                .expect("1D arrray shape should always work"),
+                .expect("1D arrray shape should always work")
+                .into_shared(),
             ),
         )]
         .into()
     }
 
-    pub fn view(&self, id: u32) -> Element<Message> {
-        fn numeric_input(input: TextInput<Message>) -> Element<Message> {
-            column![
-                input
-                    .padding(0)
-                    .style(|t, s| {
-                        let d = text_input::default(t, s);
-                        text_input::Style {
-                            border: d.border.color(Color::TRANSPARENT),
-                            background: iced::Background::Color(Color::TRANSPARENT),
-                            ..d
-                        }
-                    })
-                    .align_x(Center),
-                container(horizontal_rule(0)).padding(4.).height(1),
-            ]
-            .height(Shrink)
-            .width(Fill)
-            .align_x(Center)
-            .into()
-        }
-        let start_input = numeric_input(text_input("0", &self.start.to_string()).on_input(
-            move |value| {
-                Message::UpdateNodeTemplate(
-                    id,
-                    NodeTemplate::RustNode(RustNode::Linspace(LinspaceConfig {
-                        start: value.parse().unwrap_or(0.),
-                        ..*self
-                    })),
-                )
-            },
-        ));
-        let stop_input = numeric_input(text_input("10", &self.stop.to_string()).on_input(
-            move |value| {
-                Message::UpdateNodeTemplate(
-                    id,
-                    NodeTemplate::RustNode(RustNode::Linspace(LinspaceConfig {
-                        stop: value.parse().unwrap_or(0.),
-                        ..*self
-                    })),
-                )
-            },
-        ));
-        let num_input = numeric_input(text_input("100", &self.num.to_string()).on_input(
-            move |value| {
-                Message::UpdateNodeTemplate(
-                    id,
-                    NodeTemplate::RustNode(RustNode::Linspace(LinspaceConfig {
-                        num: value.parse().unwrap_or(0),
-                        ..*self
-                    })),
-                )
-            },
-        ));
+    pub fn parameters(&self) -> NodeUIParameters {
+        [
+            ("start", NodeUIWidget::number(self.start, Limits::default())),
+            ("stop", NodeUIWidget::number(self.stop, Limits::default())),
+            ("num", NodeUIWidget::spinner(self.num, Limits::at_least(1))),
+        ]
+        .map(|(name, widget)| (name.to_string(), widget))
+        .into()
+    }
+
+    pub fn set_parameters(&mut self, parameters: &NodeUIParameters) -> Result<(), NodeError> {
+        *self = Self::new(
+            parameters.get_as("start")?,
+            parameters.get_as("stop")?,
+            parameters.get_as("num")?,
+        );
+        Ok(())
+    }
 
+    /// The range is edited in the side bar
+    pub fn view(&self, _id: u32) -> Element<'_, Message> {
         column![
-            row![start_input, text(".."), stop_input,]
-                .width(Fill)
-                .align_y(Center)
-                .padding(5.)
-                .spacing(2.),
-            row![text("#"), num_input]
-                .align_y(Center)
-                .padding([0.0, 20.0])
+            text(format!("{} .. {}", self.start, self.stop)),
+            text(format!("# {}", self.num)).size(12.),
         ]
+        .width(Fill)
+        .align_x(Center)
         .into()
     }
 }
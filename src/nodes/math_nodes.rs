@@ -1,41 +1,723 @@
+use std::any::Any;
+
 use crate::{gui_node::PortDataReference, nodes::NodeError, StableMap};
-use ndarray::ArrayD;
-use numpy::IxDyn;
+use ndarray::{ArcArray, ArrayD, ArrayViewD, IxDyn, Zip};
+use numpy::{Complex32, Complex64};
+
+use super::port::{DType, PortData, PortType, SharedArray};
+
+/// Arithmetic of two values, for each kind of element
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+}
+
+impl Operator {
+    /// Whether the result for integers is an integer. Division is always real, as with `/` in
+    /// numpy
+    pub fn keeps_integers(self) -> bool {
+        self != Operator::Divide
+    }
+
+    pub fn real(self, a: f64, b: f64) -> f64 {
+        match self {
+            Operator::Add => a + b,
+            Operator::Subtract => a - b,
+            Operator::Multiply => a * b,
+            Operator::Divide => a / b,
+            Operator::Power => a.powf(b),
+        }
+    }
+
+    pub fn complex(self, a: Complex64, b: Complex64) -> Complex64 {
+        match self {
+            Operator::Add => a + b,
+            Operator::Subtract => a - b,
+            Operator::Multiply => a * b,
+            Operator::Divide => a / b,
+            // Real powers are exact at 0, where complex ones aren't defined
+            Operator::Power if b.im == 0. => a.powf(b.re),
+            Operator::Power => a.powc(b),
+        }
+    }
+}
+
+pub fn sinc(x: f64) -> f64 {
+    if x == 0. {
+        1.
+    } else {
+        x.sin() / x
+    }
+}
+
+pub fn complex_sinc(z: Complex64) -> Complex64 {
+    if z == Complex64::ZERO {
+        Complex64::ONE
+    } else {
+        z.sin() / z
+    }
+}
+
+/// Shape that arrays of shapes `a` and `b` broadcast to, as in numpy. Shapes are aligned at
+/// their last axis, and axes of length 1 are stretched to match the other shape
+pub fn broadcast_shape(a: &[usize], b: &[usize]) -> Result<Vec<usize>, NodeError> {
+    let ndim = a.len().max(b.len());
+    // Missing leading axes have length 1
+    let axis =
+        |shape: &[usize], i: usize| (i + shape.len()).checked_sub(ndim).map_or(1, |i| shape[i]);
+    (0..ndim)
+        .map(|i| match (axis(a, i), axis(b, i)) {
+            (n, m) if n == m || m == 1 => Ok(n),
+            (1, m) => Ok(m),
+            _ => Err(NodeError::Input(format!(
+                "Shapes {a:?} and {b:?} can't be broadcast together"
+            ))),
+        })
+        .collect()
+}
+
+/// `f` of each pair of elements of `a` and `b`, broadcast to a common shape
+pub fn zip_broadcast<A, B, C>(
+    a: ArrayViewD<A>,
+    b: ArrayViewD<B>,
+    f: impl Fn(&A, &B) -> C,
+) -> Result<ArrayD<C>, NodeError> {
+    let shape = broadcast_shape(a.shape(), b.shape())?;
+    let a = a
+        .broadcast(shape.clone())
+        .expect("a broadcasts to the common shape");
+    let b = b
+        .broadcast(shape)
+        .expect("b broadcasts to the common shape");
+    Ok(Zip::from(&a).and(&b).map_collect(f))
+}
+
+/// Elements of numeric data, as the widest type of their kind
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Integer(i64),
+    Real(f64),
+    Complex(Complex64),
+}
+
+/// Element types of numeric data
+trait Element: Copy + Send + Sync + 'static {
+    fn scalar(self) -> Scalar;
+    /// Convert from another element type. Only used for conversions that keep every value, and
+    /// for scalars
+    fn from_scalar(scalar: Scalar) -> Self;
+    fn port_data(array: SharedArray<Self>) -> PortData;
+}
+
+/// Element types math nodes compute with, every one but bool
+trait Arithmetic: Element {
+    /// `a <operator> b`. Integer overflow wraps around, as in numpy
+    fn operate(operator: Operator, a: Self, b: Self) -> Self;
+    /// Integers can't be raised to negative powers
+    fn is_negative_integer(self) -> bool {
+        false
+    }
+}
+
+impl Element for bool {
+    fn scalar(self) -> Scalar {
+        Scalar::Integer(self.into())
+    }
+    fn from_scalar(scalar: Scalar) -> Self {
+        scalar != Scalar::Integer(0)
+    }
+    fn port_data(array: SharedArray<Self>) -> PortData {
+        PortData::ArrayBool(array)
+    }
+}
+
+macro_rules! integer_elements {
+    ($($element:ty => $variant:ident),*) => {$(
+        impl Element for $element {
+            fn scalar(self) -> Scalar {
+                Scalar::Integer(self.into())
+            }
+            fn from_scalar(scalar: Scalar) -> Self {
+                match scalar {
+                    Scalar::Integer(i) => i as $element,
+                    Scalar::Real(r) => r as $element,
+                    Scalar::Complex(c) => c.re as $element,
+                }
+            }
+            fn port_data(array: SharedArray<Self>) -> PortData {
+                PortData::$variant(array)
+            }
+        }
+
+        impl Arithmetic for $element {
+            fn operate(operator: Operator, a: Self, b: Self) -> Self {
+                match operator {
+                    Operator::Add => a.wrapping_add(b),
+                    Operator::Subtract => a.wrapping_sub(b),
+                    Operator::Multiply => a.wrapping_mul(b),
+                    Operator::Divide => unreachable!("integer division is real"),
+                    Operator::Power => a.wrapping_pow(u32::try_from(b).unwrap_or(u32::MAX)),
+                }
+            }
+            #[allow(unused_comparisons)]
+            fn is_negative_integer(self) -> bool {
+                self < 0
+            }
+        }
+    )*};
+}
+
+integer_elements!(u8 => ArrayUnsigned8, u16 => ArrayUnsigned16, i32 => ArrayInteger32, i64 => ArrayInteger);
+
+impl Element for f32 {
+    fn scalar(self) -> Scalar {
+        Scalar::Real(self.into())
+    }
+    fn from_scalar(scalar: Scalar) -> Self {
+        match scalar {
+            Scalar::Integer(i) => i as f32,
+            Scalar::Real(r) => r as f32,
+            Scalar::Complex(c) => c.re as f32,
+        }
+    }
+    fn port_data(array: SharedArray<Self>) -> PortData {
+        PortData::ArrayReal32(array)
+    }
+}
+
+impl Arithmetic for f32 {
+    fn operate(operator: Operator, a: Self, b: Self) -> Self {
+        operator.real(a.into(), b.into()) as f32
+    }
+}
+
+impl Element for f64 {
+    fn scalar(self) -> Scalar {
+        Scalar::Real(self)
+    }
+    fn from_scalar(scalar: Scalar) -> Self {
+        match scalar {
+            Scalar::Integer(i) => i as f64,
+            Scalar::Real(r) => r,
+            Scalar::Complex(c) => c.re,
+        }
+    }
+    fn port_data(array: SharedArray<Self>) -> PortData {
+        PortData::ArrayReal(array)
+    }
+}
+
+impl Arithmetic for f64 {
+    fn operate(operator: Operator, a: Self, b: Self) -> Self {
+        operator.real(a, b)
+    }
+}
+
+impl Element for Complex32 {
+    fn scalar(self) -> Scalar {
+        Scalar::Complex(Complex64::new(self.re.into(), self.im.into()))
+    }
+    fn from_scalar(scalar: Scalar) -> Self {
+        let c = Complex64::from_scalar(scalar);
+        Complex32::new(c.re as f32, c.im as f32)
+    }
+    fn port_data(array: SharedArray<Self>) -> PortData {
+        PortData::ArrayComplex32(array)
+    }
+}
+
+impl Arithmetic for Complex32 {
+    fn operate(operator: Operator, a: Self, b: Self) -> Self {
+        let c = |c: Self| Complex64::new(c.re.into(), c.im.into());
+        Self::from_scalar(Scalar::Complex(operator.complex(c(a), c(b))))
+    }
+}
+
+impl Element for Complex64 {
+    fn scalar(self) -> Scalar {
+        Scalar::Complex(self)
+    }
+    fn from_scalar(scalar: Scalar) -> Self {
+        match scalar {
+            Scalar::Integer(i) => Complex64::new(i as f64, 0.),
+            Scalar::Real(r) => Complex64::new(r, 0.),
+            Scalar::Complex(c) => c,
+        }
+    }
+    fn port_data(array: SharedArray<Self>) -> PortData {
+        PortData::ArrayComplex(array)
+    }
+}
+
+impl Arithmetic for Complex64 {
+    fn operate(operator: Operator, a: Self, b: Self) -> Self {
+        operator.complex(a, b)
+    }
+}
+
+/// Run `$body` with `$element` the element type computed with for `$dtype`. Booleans are
+/// computed as int64
+macro_rules! with_element {
+    ($dtype:expr, $element:ident => $body:expr) => {
+        match $dtype {
+            DType::UInt8 => {
+                type $element = u8;
+                $body
+            }
+            DType::UInt16 => {
+                type $element = u16;
+                $body
+            }
+            DType::Int32 => {
+                type $element = i32;
+                $body
+            }
+            DType::Bool | DType::Int64 => {
+                type $element = i64;
+                $body
+            }
+            DType::Float32 => {
+                type $element = f32;
+                $body
+            }
+            DType::Float64 => {
+                type $element = f64;
+                $body
+            }
+            DType::Complex64 => {
+                type $element = Complex32;
+                $body
+            }
+            DType::Complex128 => {
+                type $element = Complex64;
+                $body
+            }
+        }
+    };
+}
+
+/// Element types from narrowest to widest
+const DTYPES: [DType; 9] = [
+    DType::Bool,
+    DType::UInt8,
+    DType::UInt16,
+    DType::Int32,
+    DType::Int64,
+    DType::Float32,
+    DType::Float64,
+    DType::Complex64,
+    DType::Complex128,
+];
+
+/// Bool, integer, real or complex
+fn kind(dtype: DType) -> u8 {
+    match dtype {
+        DType::Bool => 0,
+        DType::UInt8 | DType::UInt16 | DType::Int32 | DType::Int64 => 1,
+        DType::Float32 | DType::Float64 => 2,
+        DType::Complex64 | DType::Complex128 => 3,
+    }
+}
+
+/// Narrowest element type both `a` and `b` convert to without losing values
+fn common(a: DType, b: DType) -> DType {
+    DTYPES
+        .into_iter()
+        .find(|&dtype| a.can_cast(dtype) && b.can_cast(dtype))
+        .expect("every element type converts to complex128")
+}
+
+/// A numeric input
+struct Input<'a> {
+    name: &'a str,
+    data: &'a PortData,
+    dtype: DType,
+    is_array: bool,
+}
+
+impl<'a> Input<'a> {
+    /// Input `name`, whatever its element type
+    fn new(
+        inputs: &'a StableMap<String, PortDataReference>,
+        name: &'a str,
+    ) -> Result<Self, NodeError> {
+        let data = &**inputs.get(name).ok_or(NodeError::input_error(name))?;
+        let (dtype, is_array) = data
+            .numeric_kind()
+            .ok_or_else(|| NodeError::Input(format!("Input '{name}' must be numeric")))?;
+        Ok(Input {
+            name,
+            data,
+            dtype,
+            is_array,
+        })
+    }
+
+    /// Elements as `T`, which is only a different type if it holds every value. Scalars are 0
+    /// dimensional, and are an error if they don't fit `T`
+    fn elements<T: Element>(&self) -> Result<SharedArray<T>, NodeError> {
+        fn convert<S: Element, T: Element>(array: &SharedArray<S>) -> SharedArray<T> {
+            match (array as &dyn Any).downcast_ref::<SharedArray<T>>() {
+                Some(same) => same.clone(),
+                None => array.mapv(|s| T::from_scalar(s.scalar())).into_shared(),
+            }
+        }
+        let scalar = |scalar: Scalar| {
+            let element = T::from_scalar(scalar);
+            match (scalar, element.scalar()) {
+                (Scalar::Integer(i), Scalar::Integer(converted)) if i != converted => {
+                    Err(NodeError::Input(format!(
+                        "Input '{}' is {i}, which doesn't fit the element type of the other input",
+                        self.name
+                    )))
+                }
+                _ => Ok(ArcArray::from_elem(IxDyn(&[]), element)),
+            }
+        };
+        Ok(match self.data {
+            PortData::Integer(i) => scalar(Scalar::Integer(*i))?,
+            PortData::Real(r) => scalar(Scalar::Real(*r))?,
+            PortData::Complex(c) => scalar(Scalar::Complex(*c))?,
+            PortData::ArrayInteger(a) => convert(a),
+            PortData::ArrayReal(a) | PortData::Dynamic(a) => convert(a),
+            PortData::ArrayComplex(a) => convert(a),
+            PortData::ArrayBool(a) => convert(a),
+            PortData::ArrayUnsigned8(a) => convert(a),
+            PortData::ArrayUnsigned16(a) => convert(a),
+            PortData::ArrayInteger32(a) => convert(a),
+            PortData::ArrayReal32(a) => convert(a),
+            PortData::ArrayComplex32(a) => convert(a),
+            PortData::Object(_) => unreachable!("objects aren't numeric"),
+        })
+    }
+}
+
+/// Element type of `a <operator> b`, from the element type of each input and whether it is an
+/// array, as numpy decides it: the narrowest type both inputs convert to. Scalars are weak, as
+/// python's numbers are in numpy, so they take the type of an array of the same kind: a uint8
+/// array times 2 is uint8, while times 0.5 it is float64.
+/// Booleans are computed as int64, and division of integers is float64
+fn binary_dtype(a: (DType, bool), b: (DType, bool), operator: Operator) -> DType {
+    let dtype = match (a, b) {
+        ((scalar, false), (array, true)) | ((array, true), (scalar, false)) => {
+            match (scalar, array) {
+                (scalar, array) if kind(array) > 0 && kind(scalar) <= kind(array) => array,
+                (DType::Complex128, DType::Float32) => DType::Complex64,
+                (scalar, array) => common(scalar, array),
+            }
+        }
+        ((a, _), (b, _)) => common(a, b),
+    };
+    match kind(dtype) {
+        0 | 1 if !operator.keeps_integers() => DType::Float64,
+        0 => DType::Int64,
+        _ => dtype,
+    }
+}
+
+/// Element type of a unary operation of `dtype`. Integers are computed as reals, float32 for
+/// the ones that fit it, as in numpy
+fn unary_dtype(dtype: DType) -> DType {
+    match dtype {
+        DType::Bool | DType::UInt8 | DType::UInt16 => DType::Float32,
+        DType::Int32 | DType::Int64 => DType::Float64,
+        dtype => dtype,
+    }
+}
+
+/// Port type of a numeric result, an array if any input is
+fn port_type(dtype: DType, is_array: bool) -> PortType {
+    match (dtype, is_array) {
+        (DType::Bool | DType::UInt8 | DType::UInt16 | DType::Int32 | DType::Int64, false) => {
+            PortType::Integer
+        }
+        (DType::Float32 | DType::Float64, false) => PortType::Real,
+        (DType::Complex64 | DType::Complex128, false) => PortType::Complex,
+        (DType::Bool, true) => PortType::ArrayBool,
+        (DType::UInt8, true) => PortType::ArrayUnsigned8,
+        (DType::UInt16, true) => PortType::ArrayUnsigned16,
+        (DType::Int32, true) => PortType::ArrayInteger32,
+        (DType::Int64, true) => PortType::ArrayInteger,
+        (DType::Float32, true) => PortType::ArrayReal32,
+        (DType::Float64, true) => PortType::ArrayReal,
+        (DType::Complex64, true) => PortType::ArrayComplex32,
+        (DType::Complex128, true) => PortType::ArrayComplex,
+    }
+}
 
-use super::port::PortData;
+/// Output type of `binary_operation` for inputs of types `a` and `b`. `Dynamic` unless both
+/// are numeric
+pub fn binary_port_type(a: &PortType, b: &PortType, operator: Operator) -> PortType {
+    match (a.numeric_kind(), b.numeric_kind()) {
+        (Some(a), Some(b)) => port_type(binary_dtype(a, b, operator), a.1 || b.1),
+        _ => PortType::Dynamic,
+    }
+}
+
+/// Output type of `unary_operation` for an input of type `a`. `Dynamic` unless it is numeric
+pub fn unary_port_type(a: &PortType) -> PortType {
+    match a.numeric_kind() {
+        Some((dtype, is_array)) => port_type(unary_dtype(dtype), is_array),
+        None => PortType::Dynamic,
+    }
+}
 
-#[allow(clippy::type_complexity)]
+/// Output data of the computed elements. Scalar inputs give a scalar
+fn output<T: Element>(array: ArrayD<T>, scalar: bool) -> PortData {
+    match array.first().map(|element| element.scalar()) {
+        Some(Scalar::Integer(i)) if scalar => PortData::Integer(i),
+        Some(Scalar::Real(r)) if scalar => PortData::Real(r),
+        Some(Scalar::Complex(c)) if scalar => PortData::Complex(c),
+        _ => T::port_data(array.into_shared()),
+    }
+}
+
+/// `a <operator> b` of inputs `a` and `b`, broadcast together. The element type of the output
+/// only depends on the types of the inputs, see `binary_dtype`, so the output type of the node
+/// does too, see `binary_port_type`
 pub fn binary_operation(
     inputs: StableMap<String, PortDataReference>,
-    f: Box<dyn Fn(&ArrayD<f64>, &ArrayD<f64>) -> ArrayD<f64>>,
+    operator: Operator,
 ) -> Result<StableMap<String, PortData>, NodeError> {
-    let a = inputs.get("a").ok_or(NodeError::input_error("a"))?;
-    let b = inputs.get("b").ok_or(NodeError::input_error("b"))?;
-
-    let out = match (&**a, &**b) {
-        (PortData::ArrayReal(a), PortData::ArrayReal(b)) => f(a, b),
-        (PortData::Real(a), PortData::Real(b)) => f(
-            &ArrayD::from_shape_simple_fn(IxDyn(&[1]), || *a),
-            &ArrayD::from_shape_simple_fn(IxDyn(&[1]), || *b),
-        ),
-        (PortData::ArrayReal(a), PortData::Real(b)) => {
-            f(a, &ArrayD::from_shape_simple_fn(IxDyn(&[1]), || *b))
-        }
-        _ => panic!("bad inputs!"),
-    };
+    fn compute<T: Arithmetic>(
+        a: &Input,
+        b: &Input,
+        operator: Operator,
+    ) -> Result<PortData, NodeError> {
+        let (a_elements, b_elements) = (a.elements::<T>()?, b.elements::<T>()?);
+        if operator == Operator::Power && b_elements.iter().any(|e| e.is_negative_integer()) {
+            return Err(NodeError::Input(
+                "Integers can't be raised to negative powers".into(),
+            ));
+        }
+        let out = zip_broadcast(a_elements.view(), b_elements.view(), |a, b| {
+            T::operate(operator, *a, *b)
+        })?;
+        Ok(output(out, !a.is_array && !b.is_array))
+    }
 
-    Ok([("out".into(), PortData::ArrayReal(out))].into())
+    let a = Input::new(&inputs, "a")?;
+    let b = Input::new(&inputs, "b")?;
+    let dtype = binary_dtype((a.dtype, a.is_array), (b.dtype, b.is_array), operator);
+    let out = with_element!(dtype, T => compute::<T>(&a, &b, operator)?);
+    Ok([("out".into(), out)].into())
 }
-#[allow(clippy::type_complexity)]
+
+/// `f` of each element of input `a`, see `unary_dtype`
 pub fn unary_operation(
     inputs: StableMap<String, PortDataReference>,
-    f: Box<dyn Fn(&ArrayD<f64>) -> ArrayD<f64>>,
+    real: fn(f64) -> f64,
+    complex: fn(Complex64) -> Complex64,
 ) -> Result<StableMap<String, PortData>, NodeError> {
-    let out = match &**inputs.get("a").ok_or(NodeError::input_error("a"))? {
-        PortData::ArrayReal(a) => f(a),
-        PortData::Real(a) => f(&ArrayD::from_shape_simple_fn(IxDyn(&[1]), || *a)),
-        _ => panic!("bad inputs!"),
-    };
+    fn compute<T: Element>(
+        a: &Input,
+        real: fn(f64) -> f64,
+        complex: fn(Complex64) -> Complex64,
+    ) -> Result<PortData, NodeError> {
+        let out = a.elements::<T>()?.mapv(|element| {
+            T::from_scalar(match element.scalar() {
+                Scalar::Integer(i) => Scalar::Real(real(i as f64)),
+                Scalar::Real(r) => Scalar::Real(real(r)),
+                Scalar::Complex(c) => Scalar::Complex(complex(c)),
+            })
+        });
+        Ok(output(out, !a.is_array))
+    }
+
+    let a = Input::new(&inputs, "a")?;
+    let out = with_element!(unary_dtype(a.dtype), T => compute::<T>(&a, real, complex)?);
+    Ok([("out".into(), out)].into())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::graph::{PortRef, IO};
+    use ndarray::array;
+    use std::sync::RwLock;
+
+    use super::*;
+
+    fn binary(a: PortData, b: PortData, operator: Operator) -> Result<PortData, NodeError> {
+        let (a, b) = (RwLock::new(a), RwLock::new(b));
+        let inputs = [
+            ("a".into(), a.read().unwrap()),
+            ("b".into(), b.read().unwrap()),
+        ]
+        .into();
+        binary_operation(inputs, operator).map(|mut outputs| outputs.remove("out").unwrap())
+    }
+
+    #[test]
+    fn broadcast_binary_operations() {
+        let column = PortData::ArrayReal(array![[1.], [2.]].into_dyn().into_shared());
+        let row = PortData::ArrayReal(array![10., 20., 30.].into_dyn().into_shared());
+        let PortData::ArrayReal(sum) = binary(column.clone(), row.clone(), Operator::Add).unwrap()
+        else {
+            panic!("expected a real array");
+        };
+        assert_eq!(sum, array![[11., 21., 31.], [12., 22., 32.]].into_dyn());
+
+        // Scalars broadcast on either side, and stay scalars on their own
+        let PortData::ArrayReal(difference) =
+            binary(PortData::Real(1.), row.clone(), Operator::Subtract).unwrap()
+        else {
+            panic!("expected a real array");
+        };
+        assert_eq!(difference, array![-9., -19., -29.].into_dyn());
+        assert!(matches!(
+            binary(PortData::Real(2.), PortData::Real(3.), Operator::Power),
+            Ok(PortData::Real(8.))
+        ));
+
+        let Err(NodeError::Input(message)) = binary(
+            row,
+            PortData::ArrayReal(array![1., 2.].into_dyn().into_shared()),
+            Operator::Multiply,
+        ) else {
+            panic!("expected an input error");
+        };
+        assert_eq!(message, "Shapes [3] and [2] can't be broadcast together");
+        assert!(matches!(
+            binary(PortData::Object([].into()), column, Operator::Add),
+            Err(NodeError::Input(message)) if message == "Input 'a' must be numeric"
+        ));
+    }
+
+    #[test]
+    fn element_types() {
+        let integers = PortData::ArrayInteger(array![1, 2, 3].into_dyn().into_shared());
+        let PortData::ArrayInteger(product) =
+            binary(integers.clone(), PortData::Integer(2), Operator::Multiply).unwrap()
+        else {
+            panic!("expected an integer array");
+        };
+        assert_eq!(product, array![2, 4, 6].into_dyn());
+        // Division of integers is real, and their powers are integers whatever the values
+        assert!(matches!(
+            binary(PortData::Integer(1), PortData::Integer(2), Operator::Divide),
+            Ok(PortData::Real(0.5))
+        ));
+        assert!(matches!(
+            binary(PortData::Integer(2), PortData::Integer(3), Operator::Power),
+            Ok(PortData::Integer(8))
+        ));
+        assert!(matches!(
+            binary(PortData::Integer(2), PortData::Integer(-1), Operator::Power),
+            Err(NodeError::Input(_))
+        ));
+
+        let i = Complex64::new(0., 1.);
+        let PortData::ArrayComplex(rotated) =
+            binary(integers, PortData::Complex(i), Operator::Multiply).unwrap()
+        else {
+            panic!("expected a complex array");
+        };
+        assert_eq!(rotated, array![i, 2. * i, 3. * i].into_dyn());
+
+        let a = RwLock::new(PortData::Complex(Complex64::ZERO));
+        let out =
+            unary_operation([("a".into(), a.read().unwrap())].into(), sinc, complex_sinc).unwrap();
+        assert!(matches!(out["out"], PortData::Complex(z) if z == Complex64::ONE));
+    }
+
+    #[test]
+    fn narrow_element_types() {
+        // Integer scalars take the type of the array, and wrap around like it
+        let bytes = PortData::ArrayUnsigned8(array![1u8, 2, 200].into_dyn().into_shared());
+        let PortData::ArrayUnsigned8(doubled) =
+            binary(bytes.clone(), PortData::Integer(2), Operator::Multiply).unwrap()
+        else {
+            panic!("expected a uint8 array");
+        };
+        assert_eq!(doubled, array![2u8, 4, 144].into_dyn());
+        assert!(matches!(
+            binary(bytes.clone(), PortData::Integer(256), Operator::Add),
+            Err(NodeError::Input(_))
+        ));
+        // Real scalars and wider arrays widen it
+        assert!(matches!(
+            binary(bytes.clone(), PortData::Real(2.5), Operator::Multiply),
+            Ok(PortData::ArrayReal(_))
+        ));
+        let integers = PortData::ArrayInteger(array![1, 2, 3].into_dyn().into_shared());
+        assert!(matches!(
+            binary(bytes.clone(), integers, Operator::Add),
+            Ok(PortData::ArrayInteger(_))
+        ));
+        assert!(matches!(
+            binary(bytes, PortData::Integer(2), Operator::Divide),
+            Ok(PortData::ArrayReal(_))
+        ));
+
+        let singles = PortData::ArrayReal32(array![0f32, 1.5].into_dyn().into_shared());
+        let PortData::ArrayReal32(sum) =
+            binary(singles.clone(), PortData::Real(0.25), Operator::Add).unwrap()
+        else {
+            panic!("expected a float32 array");
+        };
+        assert_eq!(sum, array![0.25f32, 1.75].into_dyn());
+        assert!(matches!(
+            binary(
+                singles.clone(),
+                PortData::Complex(Complex64::ONE),
+                Operator::Add
+            ),
+            Ok(PortData::ArrayComplex32(_))
+        ));
+        let a = RwLock::new(singles);
+        let out = unary_operation([("a".into(), a.read().unwrap())].into(), f64::cos, |z| {
+            z.cos()
+        })
+        .unwrap();
+        assert!(matches!(&out["out"], PortData::ArrayReal32(cos) if cos[0] == 1.));
+    }
+
+    #[test]
+    fn output_types_follow_inputs() {
+        use crate::{
+            graph::GraphError,
+            gui_node::GuiGraph,
+            nodes::{NodeData, NodeTemplate, RustNode},
+        };
+
+        let ports = |ports: &[(&str, PortType)]| {
+            ports
+                .iter()
+                .map(|(name, port_type)| (name.to_string(), port_type.clone()))
+                .collect()
+        };
+        let node = |node: RustNode| NodeData::from(NodeTemplate::RustNode(node));
+        let mut graph = GuiGraph::new();
+        let sources = graph.node(node(RustNode::GroupInputs(ports(&[
+            ("bytes", PortType::ArrayUnsigned8),
+            ("n", PortType::Integer),
+        ]))));
+        let sinks = graph.node(node(RustNode::GroupOutputs(ports(&[
+            ("real", PortType::Real),
+            ("array", PortType::ArrayReal),
+        ]))));
+        let add = graph.node(node(RustNode::Add));
+        let cos = graph.node(node(RustNode::Cos));
+
+        // Unconnected inputs could be anything
+        assert_eq!(graph.output_types(add)["out"], PortType::Dynamic);
+        graph.connect((add, "out"), (cos, "a")).unwrap();
+        graph.connect((sources, "n"), (add, "b")).unwrap();
+        graph.connect((cos, "out"), (sinks, "real")).unwrap();
+        // An array would reach the real input downstream
+        assert!(matches!(
+            graph.connect((sources, "bytes"), (add, "a")),
+            Err(GraphError::IncompatiblePorts { to, .. }) if to.name == "real"
+        ));
 
-    Ok([("out".into(), PortData::ArrayReal(out))].into())
+        graph.remove_edge(&PortRef::new(sinks, "real", IO::In));
+        graph.connect((sources, "bytes"), (add, "a")).unwrap();
+        assert_eq!(graph.output_types(add)["out"], PortType::ArrayUnsigned8);
+        assert_eq!(graph.output_types(cos)["out"], PortType::ArrayReal32);
+        graph.connect((cos, "out"), (sinks, "array")).unwrap();
+    }
 }
@@ -1,18 +1,14 @@
-use super::{PortData, RustNode};
+use super::status::NodeError;
 use crate::app::Message;
 use crate::gui_node::PortDataContainer;
 use crate::interface::node::{INNER_NODE_HEIGHT, INNER_NODE_WIDTH, NODE_BORDER_WIDTH};
+use crate::interface::node_config::{Limits, NodeUIParameters, NodeUIWidget, Parameters};
 use crate::math::{linspace_delta, Vector};
-use crate::nodes::NodeTemplate;
 use crate::StableMap;
 use iced::widget::canvas::{Path, Stroke};
-use iced::widget::{container, horizontal_space, row, text, text_input};
-use iced::Alignment::Center;
+use iced::widget::container;
 use iced::{mouse, Point};
-use iced::{
-    widget::{canvas, column},
-    Element,
-};
+use iced::{widget::canvas, Element};
 use iced::{Rectangle, Renderer, Theme};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
@@ -61,30 +57,19 @@ impl Plot {
         _id: u32,
         input_data: StableMap<String, PortDataContainer>,
     ) -> Element<'a, Message> {
-        let (x, y) =
-            if let (Some(x_port), Some(y_port)) = (input_data.get("x"), input_data.get("y")) {
-                if let (PortData::ArrayReal(x), PortData::ArrayReal(y)) = (
-                    x_port.read().unwrap().clone(),
-                    y_port.read().unwrap().clone(),
-                ) {
-                    (
-                        x.into_raw_vec_and_offset()
-                            .0
-                            .into_iter()
-                            .map(|f| f as f32)
-                            .collect(),
-                        y.into_raw_vec_and_offset()
-                            .0
-                            .into_iter()
-                            .map(|f| f as f32)
-                            .collect(),
-                    )
-                } else {
-                    panic!("unsuported plot types ")
-                }
-            } else {
-                (vec![], vec![])
-            };
+        let values = |port: &PortDataContainer| match port.read().unwrap().reals() {
+            Some(a) => a.iter().map(|&f| f as f32).collect::<Vec<_>>(),
+            None => panic!("unsuported plot types "),
+        };
+        let (x, y) = match (input_data.get("x"), input_data.get("y")) {
+            (Some(x_port), Some(y_port)) => (values(x_port), values(y_port)),
+            // x is optional, plot against index
+            (None, Some(y_port)) => {
+                let y = values(y_port);
+                ((0..y.len()).map(|i| i as f32).collect(), y)
+            }
+            _ => (vec![], vec![]),
+        };
         container(
             canvas(PlotCanvas {
                 x,
@@ -98,64 +83,55 @@ impl Plot {
         .into()
     }
 
-    pub fn config_view(
-        &self,
-        id: u32,
-        _input_data: StableMap<String, PortDataContainer>,
-    ) -> Option<Element<'_, Message>> {
-        let center = self.rect.center;
-        let width = self.rect.width;
-        let height = self.rect.height;
-        let message = move |rect| {
-            Message::UpdateNodeTemplate(id, NodeTemplate::RustNode(RustNode::Plot(Plot { rect })))
+    pub fn parameters(&self) -> NodeUIParameters {
+        rect_parameters(self.rect.center, self.rect.width, self.rect.height)
+    }
+
+    pub fn set_parameters(&mut self, parameters: &NodeUIParameters) -> Result<(), NodeError> {
+        let (center, width, height) = rect_from_parameters(parameters)?;
+        self.rect = Rect {
+            center,
+            width,
+            height,
         };
-        Some(
-            column![
-                row![
-                    text("center:"),
-                    horizontal_space(),
-                    text("x"),
-                    text_input("0", &center.x.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.center.x = value.parse().unwrap_or(0.);
-                        message(n)
-                    }),
-                    text("y"),
-                    text_input("0", &center.y.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.center.y = value.parse().unwrap_or(0.);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center)
-                .spacing(4.),
-                row![
-                    text("width:"),
-                    horizontal_space(),
-                    text_input("0", &width.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.width = value.parse().unwrap_or(1.0f32).max(0.001);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center),
-                row![
-                    text("height:"),
-                    horizontal_space(),
-                    text_input("0", &height.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.height = value.parse().unwrap_or(1.0f32).max(0.001);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center)
-            ]
-            .spacing(5.0)
-            .into(),
-        )
+        Ok(())
     }
 }
 
+/// Parameters for the plotted region, shared by the plot nodes
+pub fn rect_parameters(center: Vector, width: f32, height: f32) -> NodeUIParameters {
+    let size = |value: f32| NodeUIWidget::number(value.into(), Limits::at_least(0.001));
+    [
+        (
+            "center",
+            NodeUIWidget::vector(vec![center.x.into(), center.y.into()], Limits::default()),
+        ),
+        ("width", size(width)),
+        ("height", size(height)),
+    ]
+    .map(|(name, widget)| (name.to_string(), widget))
+    .into()
+}
+
+/// Center, width and height of the plotted region from `rect_parameters`
+pub fn rect_from_parameters(
+    parameters: &NodeUIParameters,
+) -> Result<(Vector, f32, f32), NodeError> {
+    let center = match parameters.get_as::<Vec<f64>>("center")?[..] {
+        [x, y] => Vector::new(x as f32, y as f32),
+        _ => {
+            return Err(NodeError::Config(
+                "Parameter 'center' must have an x and y".into(),
+            ))
+        }
+    };
+    Ok((
+        center,
+        parameters.get_as::<f64>("width")? as f32,
+        parameters.get_as::<f64>("height")? as f32,
+    ))
+}
+
 #[derive(Debug)]
 struct PlotCanvas {
     x: Vec<f32>,
@@ -1,12 +1,14 @@
-use super::{PortData, RustNode};
+use super::plot::{rect_from_parameters, rect_parameters};
+use super::{status::NodeError, PortData, RustNode};
 use crate::app::Message;
 use crate::gui_node::{PortDataContainer, PortDataReference};
 use crate::interface::node::{INNER_NODE_WIDTH, NODE_BORDER_WIDTH};
+use crate::interface::node_config::{parameters_view, NodeUIParameters};
 use crate::math::Vector;
 use crate::nodes::NodeTemplate;
 use crate::StableMap;
 use iced::widget::image::Handle;
-use iced::widget::{button, container, horizontal_space, image, row, text, text_input};
+use iced::widget::{button, container, horizontal_space, image, row, text};
 use iced::Alignment::Center;
 use iced::{widget::column, Element};
 use log::trace;
@@ -80,14 +82,26 @@ impl Plot2D {
         .into()
     }
 
+    pub fn parameters(&self) -> NodeUIParameters {
+        rect_parameters(self.rect.center, self.rect.width, self.rect.height)
+    }
+
+    pub fn set_parameters(&mut self, parameters: &NodeUIParameters) -> Result<(), NodeError> {
+        let (center, width, height) = rect_from_parameters(parameters)?;
+        self.rect = Rect {
+            center,
+            width,
+            height,
+        };
+        Ok(())
+    }
+
+    /// `parameters` are the node's own, with any edit in progress
     pub fn config_view(
         &self,
         id: u32,
-        _input_data: StableMap<String, PortDataContainer>,
+        parameters: &NodeUIParameters,
     ) -> Option<Element<'_, Message>> {
-        let center = self.rect.center;
-        let width = self.rect.width;
-        let height = self.rect.height;
         let message = move |rect| {
             Message::UpdateNodeTemplate(
                 id,
@@ -100,44 +114,7 @@ impl Plot2D {
         let zoom_speed = 0.125;
         Some(
             column![
-                row![
-                    text("center:"),
-                    horizontal_space(),
-                    text("x"),
-                    text_input("0", &center.x.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.center.x = value.parse().unwrap_or(0.);
-                        message(n)
-                    }),
-                    text("y"),
-                    text_input("0", &center.y.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.center.y = value.parse().unwrap_or(0.);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center)
-                .spacing(4.),
-                row![
-                    text("width:"),
-                    horizontal_space(),
-                    text_input("0", &width.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.width = value.parse().unwrap_or(1.0f32).max(0.001);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center),
-                row![
-                    text("height:"),
-                    horizontal_space(),
-                    text_input("0", &height.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.height = value.parse().unwrap_or(1.0f32).max(0.001);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center),
+                parameters_view(id, parameters),
                 row![
                     horizontal_space(),
                     button("+").on_press_with(move || {
@@ -210,24 +187,40 @@ impl Plot2D {
         self.image_handle = match input_data.get("a") {
             Some(port) => {
                 let data = match &**port {
-                    PortData::ArrayReal(a) => &Array3::<f64>::from_shape_vec(
-                        (a.shape()[0], a.shape()[1], 3),
-                        a.iter().flat_map(|v| [*v, *v, *v]).collect::<Vec<_>>(),
-                    )
-                    .expect("square matrix"),
-                    PortData::ArrayComplex(a) => &Array3::<f64>::from_shape_vec(
-                        (
-                            (a.len() as f32).sqrt() as usize,
-                            (a.len() as f32).sqrt() as usize,
-                            3,
-                        ),
-                        a.iter()
-                            .map(|v| v.norm_sqr().sqrt())
-                            .flat_map(|v| [v, v, v])
-                            .collect::<Vec<_>>(),
-                    )
-                    .expect("square matrix"),
-                    _ => panic!("unsuported plot types {:?}", port),
+                    PortData::ArrayComplex(_) | PortData::ArrayComplex32(_) => {
+                        let a = port.complexes().expect("complex data");
+                        &Array3::<f64>::from_shape_vec(
+                            (
+                                (a.len() as f32).sqrt() as usize,
+                                (a.len() as f32).sqrt() as usize,
+                                3,
+                            ),
+                            a.iter()
+                                .map(|v| v.norm_sqr().sqrt())
+                                .flat_map(|v| [v, v, v])
+                                .collect::<Vec<_>>(),
+                        )
+                        .expect("square matrix")
+                    }
+                    data => {
+                        // Integer images span the range of their type, rather than -1..1
+                        let range = match data {
+                            PortData::ArrayUnsigned8(_) => Some(f64::from(u8::MAX)),
+                            PortData::ArrayUnsigned16(_) => Some(f64::from(u16::MAX)),
+                            _ => None,
+                        };
+                        let a = data
+                            .reals()
+                            .unwrap_or_else(|| panic!("unsuported plot types {:?}", port));
+                        &Array3::<f64>::from_shape_vec(
+                            (a.shape()[0], a.shape()[1], 3),
+                            a.iter()
+                                .map(|&v| range.map_or(v, |max| v / max * 2. - 1.))
+                                .flat_map(|v| [v, v, v])
+                                .collect::<Vec<_>>(),
+                        )
+                        .expect("square matrix")
+                    }
                 };
                 Some(Self::create_image_handle(data))
             }
@@ -1,11 +1,11 @@
 use derive_more::derive::Display;
 use itertools::Itertools;
-use ndarray::{ArrayD, ArrayView, AsArray};
-use numpy::Complex64;
+use ndarray::{ArcArray, ArrayView, AsArray, IxDyn};
+use numpy::{Complex32, Complex64};
 use serde::{Deserialize, Serialize};
 use strum::{EnumDiscriminants, EnumString, VariantNames};
 
-use crate::StableMap;
+use crate::{graph::PortCompatibility, StableMap};
 
 #[derive(
     Clone, Display, Debug, EnumString, VariantNames, PartialEq, Serialize, Deserialize, PartialOrd,
@@ -17,6 +17,12 @@ pub enum PortType {
     ArrayInteger,
     ArrayReal,
     ArrayComplex,
+    ArrayBool,
+    ArrayUnsigned8,
+    ArrayUnsigned16,
+    ArrayInteger32,
+    ArrayReal32,
+    ArrayComplex32,
     Dynamic,
     #[display("{_0:?}")]
     Object(StableMap<String, PortType>),
@@ -28,19 +34,264 @@ impl Default for PortType {
     }
 }
 
-//PERF: consider ArcArray
+impl PortType {
+    /// Element type, and whether the type is an array
+    pub fn numeric_kind(&self) -> Option<(DType, bool)> {
+        match self {
+            PortType::Integer => Some((DType::Int64, false)),
+            PortType::Real => Some((DType::Float64, false)),
+            PortType::Complex => Some((DType::Complex128, false)),
+            PortType::ArrayInteger => Some((DType::Int64, true)),
+            PortType::ArrayReal => Some((DType::Float64, true)),
+            PortType::ArrayComplex => Some((DType::Complex128, true)),
+            PortType::ArrayBool => Some((DType::Bool, true)),
+            PortType::ArrayUnsigned8 => Some((DType::UInt8, true)),
+            PortType::ArrayUnsigned16 => Some((DType::UInt16, true)),
+            PortType::ArrayInteger32 => Some((DType::Int32, true)),
+            PortType::ArrayReal32 => Some((DType::Float32, true)),
+            PortType::ArrayComplex32 => Some((DType::Complex64, true)),
+            PortType::Dynamic | PortType::Object(_) => None,
+        }
+    }
+
+    /// Element type of array ports, `None` for scalars, `Dynamic` and `Object`
+    pub fn array_dtype(&self) -> Option<DType> {
+        self.numeric_kind()
+            .and_then(|(dtype, is_array)| is_array.then_some(dtype))
+    }
+}
+
+/// Element types of numeric ports, named as in numpy
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+#[display("{}", self.name())]
+pub enum DType {
+    Bool,
+    UInt8,
+    UInt16,
+    Int32,
+    Int64,
+    Float32,
+    Float64,
+    Complex64,
+    Complex128,
+}
+
+impl DType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DType::Bool => "bool",
+            DType::UInt8 => "uint8",
+            DType::UInt16 => "uint16",
+            DType::Int32 => "int32",
+            DType::Int64 => "int64",
+            DType::Float32 => "float32",
+            DType::Float64 => "float64",
+            DType::Complex64 => "complex64",
+            DType::Complex128 => "complex128",
+        }
+    }
+
+    /// Whether every value of this type can be represented in `to`, as numpy's `can_cast` with
+    /// `casting="safe"`
+    pub fn can_cast(self, to: DType) -> bool {
+        use DType::*;
+        match self {
+            Bool => true,
+            UInt8 => to != Bool,
+            UInt16 => !matches!(to, Bool | UInt8),
+            Int32 => matches!(to, Int32 | Int64 | Float64 | Complex128),
+            Int64 => matches!(to, Int64 | Float64 | Complex128),
+            Float32 => matches!(to, Float32 | Float64 | Complex64 | Complex128),
+            Float64 => matches!(to, Float64 | Complex128),
+            Complex64 => matches!(to, Complex64 | Complex128),
+            Complex128 => to == Complex128,
+        }
+    }
+}
+
+/// Output → input compatibility
+/// - identical types
+/// - `Dynamic` on either end
+/// - numeric promotion, where no values are lost (Integer → Real → Complex, `ArrayUnsigned8` →
+///   `ArrayReal32` ...), see `DType::can_cast`
+/// - scalar → array, as a 0 dimensional array which broadcasts against any shape
+/// - `Object` → `Object` if every field of the input exists in the output, and is compatible
+impl PortCompatibility<PortData> for PortType {
+    fn is_compatible(&self, input: &Self) -> bool {
+        match (self, input) {
+            (_, PortType::Dynamic) | (PortType::Dynamic, _) => true,
+            (PortType::Object(fields), PortType::Object(required)) => {
+                required.iter().all(|(name, input_type)| {
+                    fields
+                        .get(name)
+                        .is_some_and(|field_type| field_type.is_compatible(input_type))
+                })
+            }
+            _ => match (self.numeric_kind(), input.numeric_kind()) {
+                (Some((from, from_array)), Some((to, to_array))) => {
+                    from.can_cast(to) && (!from_array || to_array)
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// `Dynamic` data is used as it is
+    fn converts(&self, input: &Self) -> bool {
+        self != input
+            && *self != PortType::Dynamic
+            && *input != PortType::Dynamic
+            && self.is_compatible(input)
+    }
+
+    fn convert(&self, input: &Self, data: &PortData) -> Option<PortData> {
+        if self == input {
+            None
+        } else {
+            data.convert_to(input)
+        }
+    }
+}
+
+/// Array data of a port. Cloning shares the elements, which are only copied if a shared array is
+/// written to, so outputs can be passed to several nodes, and to numpy, without copying
+pub type SharedArray<T> = ArcArray<T, IxDyn>;
+
 #[derive(Clone, Debug, EnumDiscriminants)]
 pub enum PortData {
     Integer(i64),
     Real(f64),
     Complex(Complex64),
-    ArrayInteger(ArrayD<i64>),
-    ArrayReal(ArrayD<f64>),
-    ArrayComplex(ArrayD<Complex64>),
-    Dynamic(ArrayD<f64>),
+    ArrayInteger(SharedArray<i64>),
+    ArrayReal(SharedArray<f64>),
+    ArrayComplex(SharedArray<Complex64>),
+    ArrayBool(SharedArray<bool>),
+    ArrayUnsigned8(SharedArray<u8>),
+    ArrayUnsigned16(SharedArray<u16>),
+    ArrayInteger32(SharedArray<i32>),
+    ArrayReal32(SharedArray<f32>),
+    ArrayComplex32(SharedArray<Complex32>),
+    Dynamic(SharedArray<f64>),
     Object(StableMap<String, PortData>),
 }
 
+impl PortData {
+    /// Element type, and whether the data is an array
+    pub fn numeric_kind(&self) -> Option<(DType, bool)> {
+        Some(match self {
+            PortData::Integer(_) => (DType::Int64, false),
+            PortData::Real(_) => (DType::Float64, false),
+            PortData::Complex(_) => (DType::Complex128, false),
+            PortData::ArrayInteger(_) => (DType::Int64, true),
+            PortData::ArrayReal(_) | PortData::Dynamic(_) => (DType::Float64, true),
+            PortData::ArrayComplex(_) => (DType::Complex128, true),
+            PortData::ArrayBool(_) => (DType::Bool, true),
+            PortData::ArrayUnsigned8(_) => (DType::UInt8, true),
+            PortData::ArrayUnsigned16(_) => (DType::UInt16, true),
+            PortData::ArrayInteger32(_) => (DType::Int32, true),
+            PortData::ArrayReal32(_) => (DType::Float32, true),
+            PortData::ArrayComplex32(_) => (DType::Complex64, true),
+            PortData::Object(_) => return None,
+        })
+    }
+
+    /// Convert to data for a port of type `port_type`, see `PortType::is_compatible`.
+    /// `None` if there is no conversion, and the data should be used as is
+    pub fn convert_to(&self, port_type: &PortType) -> Option<PortData> {
+        match (self, port_type) {
+            (PortData::Dynamic(a), PortType::ArrayReal) => {
+                return Some(PortData::ArrayReal(a.clone()))
+            }
+            //// Convert the fields an object input uses
+            (PortData::Object(fields), PortType::Object(field_types)) => {
+                return Some(PortData::Object(
+                    fields
+                        .iter()
+                        .map(|(name, data)| {
+                            let converted = field_types.get(name).and_then(|t| data.convert_to(t));
+                            (name.clone(), converted.unwrap_or_else(|| data.clone()))
+                        })
+                        .collect(),
+                ))
+            }
+            _ => {}
+        }
+
+        let (from, from_array) = self.numeric_kind()?;
+        let (to, to_array) = port_type.numeric_kind()?;
+        if !from.can_cast(to) || (from_array && !to_array) || (from, from_array) == (to, to_array) {
+            return None;
+        }
+        // Scalars become 0 dimensional arrays, which broadcast against any shape
+        Some(match (to, to_array) {
+            (DType::Int64, false) => PortData::Integer(*self.integers()?.first()?),
+            (DType::Float64, false) => PortData::Real(*self.reals()?.first()?),
+            (DType::Complex128, false) => PortData::Complex(*self.complexes()?.first()?),
+            (DType::Bool, _) => return None,
+            (DType::UInt8, _) => {
+                PortData::ArrayUnsigned8(self.integers()?.mapv(|i| i as u8).into_shared())
+            }
+            (DType::UInt16, _) => {
+                PortData::ArrayUnsigned16(self.integers()?.mapv(|i| i as u16).into_shared())
+            }
+            (DType::Int32, _) => {
+                PortData::ArrayInteger32(self.integers()?.mapv(|i| i as i32).into_shared())
+            }
+            (DType::Int64, _) => PortData::ArrayInteger(self.integers()?),
+            (DType::Float32, _) => {
+                PortData::ArrayReal32(self.reals()?.mapv(|r| r as f32).into_shared())
+            }
+            (DType::Float64, _) => PortData::ArrayReal(self.reals()?),
+            (DType::Complex64, _) => PortData::ArrayComplex32(
+                self.complexes()?
+                    .mapv(|c| Complex32::new(c.re as f32, c.im as f32))
+                    .into_shared(),
+            ),
+            (DType::Complex128, _) => PortData::ArrayComplex(self.complexes()?),
+        })
+    }
+
+    /// Boolean and integer data as `i64` elements. Scalars are 0 dimensional
+    pub fn integers(&self) -> Option<SharedArray<i64>> {
+        Some(match self {
+            PortData::Integer(i) => ArcArray::from_elem(IxDyn(&[]), *i),
+            PortData::ArrayInteger(a) => a.clone(),
+            PortData::ArrayBool(a) => a.mapv(i64::from).into_shared(),
+            PortData::ArrayUnsigned8(a) => a.mapv(i64::from).into_shared(),
+            PortData::ArrayUnsigned16(a) => a.mapv(i64::from).into_shared(),
+            PortData::ArrayInteger32(a) => a.mapv(i64::from).into_shared(),
+            _ => return None,
+        })
+    }
+
+    /// Real valued data of any element type as `f64` elements, which is how nodes that only work
+    /// on reals read their inputs. Scalars are 0 dimensional, `None` for complex data and objects
+    pub fn reals(&self) -> Option<SharedArray<f64>> {
+        Some(match self {
+            PortData::Real(r) => ArcArray::from_elem(IxDyn(&[]), *r),
+            PortData::ArrayReal(a) | PortData::Dynamic(a) => a.clone(),
+            PortData::ArrayReal32(a) => a.mapv(f64::from).into_shared(),
+            PortData::ArrayBool(a) => a.mapv(|b| f64::from(u8::from(b))).into_shared(),
+            PortData::ArrayUnsigned8(a) => a.mapv(f64::from).into_shared(),
+            PortData::ArrayUnsigned16(a) => a.mapv(f64::from).into_shared(),
+            PortData::ArrayInteger32(a) => a.mapv(f64::from).into_shared(),
+            _ => self.integers()?.mapv(|i| i as f64).into_shared(),
+        })
+    }
+
+    /// Numeric data of any element type as `Complex64` elements. Scalars are 0 dimensional
+    pub fn complexes(&self) -> Option<SharedArray<Complex64>> {
+        Some(match self {
+            PortData::Complex(c) => ArcArray::from_elem(IxDyn(&[]), *c),
+            PortData::ArrayComplex(a) => a.clone(),
+            PortData::ArrayComplex32(a) => a
+                .mapv(|c| Complex64::new(c.re.into(), c.im.into()))
+                .into_shared(),
+            _ => self.reals()?.mapv(|r| Complex64::new(r, 0.)).into_shared(),
+        })
+    }
+}
+
 fn write_nd_array<'a, A, T, D>(data: T) -> String
 where
     T: AsArray<'a, A, D>,
@@ -68,6 +319,12 @@ impl std::fmt::Display for PortData {
                 PortData::ArrayInteger(array_base) => write_nd_array(array_base),
                 PortData::ArrayReal(array_base) => write_nd_array(array_base),
                 PortData::ArrayComplex(array_base) => write_nd_array(array_base),
+                PortData::ArrayBool(array_base) => write_nd_array(array_base),
+                PortData::ArrayUnsigned8(array_base) => write_nd_array(array_base),
+                PortData::ArrayUnsigned16(array_base) => write_nd_array(array_base),
+                PortData::ArrayInteger32(array_base) => write_nd_array(array_base),
+                PortData::ArrayReal32(array_base) => write_nd_array(array_base),
+                PortData::ArrayComplex32(array_base) => write_nd_array(array_base),
                 PortData::Dynamic(array_base) => write_nd_array(array_base),
                 PortData::Object(index_map) => index_map
                     .iter()
@@ -77,3 +334,88 @@ impl std::fmt::Display for PortData {
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compatibility() {
+        use PortType::*;
+        assert!(Real.is_compatible(&ArrayReal));
+        assert!(Integer.is_compatible(&Real));
+        assert!(ArrayReal.is_compatible(&ArrayComplex));
+        assert!(ArrayComplex.is_compatible(&Dynamic));
+        assert!(!ArrayReal.is_compatible(&Real));
+        assert!(!Complex.is_compatible(&Real));
+        assert!(!Object([].into()).is_compatible(&Real));
+        assert!(ArrayUnsigned8.is_compatible(&ArrayReal32));
+        assert!(ArrayReal32.is_compatible(&ArrayComplex32));
+        assert!(ArrayBool.is_compatible(&ArrayInteger));
+        assert!(!ArrayInteger32.is_compatible(&ArrayReal32));
+        assert!(!ArrayReal.is_compatible(&ArrayReal32));
+        assert!(!Integer.is_compatible(&ArrayUnsigned8));
+
+        let point =
+            |t: PortType| Object([("x".to_string(), t.clone()), ("y".to_string(), t)].into());
+        let x_only = Object([("x".to_string(), ArrayReal)].into());
+        assert!(point(Real).is_compatible(&x_only));
+        assert!(!x_only.is_compatible(&point(Real)));
+        assert!(!point(Complex).is_compatible(&x_only));
+
+        assert!(Real.converts(&ArrayReal));
+        assert!(!Real.converts(&Real));
+        assert!(!Real.converts(&Dynamic));
+        assert!(!Dynamic.converts(&ArrayReal));
+    }
+
+    #[test]
+    fn conversion() {
+        let converted = PortType::Integer.convert(&PortType::ArrayComplex, &PortData::Integer(2));
+        let Some(PortData::ArrayComplex(a)) = converted else {
+            panic!("expected a complex array, found {converted:?}")
+        };
+        assert_eq!(a.ndim(), 0);
+        assert_eq!(a.first(), Some(&Complex64::new(2., 0.)));
+
+        assert!(PortType::Real
+            .convert(&PortType::Real, &PortData::Real(1.))
+            .is_none());
+
+        let image = ndarray::array![[0u8, 255], [7, 1]].into_dyn().into_shared();
+        let converted = PortType::ArrayUnsigned8
+            .convert(&PortType::ArrayComplex32, &PortData::ArrayUnsigned8(image));
+        let Some(PortData::ArrayComplex32(a)) = converted else {
+            panic!("expected a complex64 array, found {converted:?}")
+        };
+        assert_eq!(a.shape(), &[2, 2]);
+        assert_eq!(a[[0, 1]], Complex32::new(255., 0.));
+    }
+
+    #[test]
+    fn conversions_are_cached() {
+        use crate::{
+            gui_node::GuiGraph,
+            nodes::{plot_complex::Plot2D, NodeTemplate, RustNode},
+        };
+        use std::sync::Arc;
+
+        let mut graph = GuiGraph::new();
+        let constant = graph.node(NodeTemplate::RustNode(RustNode::Constant(1.)).into());
+        let plot = graph.node(NodeTemplate::RustNode(RustNode::Plot2D(Plot2D::default())).into());
+        graph.connect((constant, "out"), (plot, "a")).unwrap();
+        graph.update_wire_data(constant, [("out".into(), PortData::Real(1.))].into());
+
+        let first = graph.get_input_data(&plot)["a"].clone();
+        assert!(matches!(*first.read().unwrap(), PortData::ArrayReal(_)));
+        assert!(Arc::ptr_eq(&first, &graph.get_input_data(&plot)["a"]));
+
+        graph.update_wire_data(constant, [("out".into(), PortData::Real(2.))].into());
+        let second = graph.get_input_data(&plot)["a"].clone();
+        assert!(!Arc::ptr_eq(&first, &second));
+        let PortData::ArrayReal(a) = &*second.read().unwrap() else {
+            panic!("expected a real array");
+        };
+        assert_eq!(a.first(), Some(&2.));
+    }
+}
@@ -11,22 +11,31 @@ pub enum NodeStatus {
     Idle,
     #[display("Running")]
     Running(Instant),
+    /// Outputs were reused from an earlier compute with the same template and inputs
+    Cached,
+    /// Not computed, because these required inputs have no data
+    #[display("Waiting for input")]
+    Waiting(Vec<String>),
     Error(NodeError),
 }
 
 impl NodeStatus {
-    pub fn icon(&self) -> Text {
+    pub fn icon(&self) -> Text<'_> {
         match self {
             NodeStatus::Idle => icon(""),
             NodeStatus::Running(_) => icon(""), //icon(""),
+            NodeStatus::Cached => icon("󰃨"),
+            NodeStatus::Waiting(_) => icon("󰔟"),
             NodeStatus::Error(_) => icon("").style(text::danger),
         }
     }
 
-    pub fn text_element(&self) -> Text {
+    pub fn text_element(&self) -> Text<'_> {
         match self {
             NodeStatus::Idle => text(""),
             NodeStatus::Running(_) => text(""),
+            NodeStatus::Cached => text("reused cached outputs"),
+            NodeStatus::Waiting(missing) => text(format!("waiting for {}", missing.join(", "))),
             NodeStatus::Error(err) => text(err.to_string()).style(text::danger),
         }
     }
@@ -42,6 +51,8 @@ pub enum NodeError {
     FileSys(String),
     Runtime(String),
     MissingCompute(String),
+    /// The compute was cancelled, or superseded by a newer one, before it started
+    Cancelled,
     #[default]
     Other,
 }
@@ -0,0 +1,456 @@
+use std::{collections::HashSet, thread};
+
+use derive_more::derive::Debug;
+use iced::{
+    widget::{column, text},
+    Alignment::Center,
+    Element,
+};
+use indexmap::IndexMap;
+use ndarray::{ArrayD, ArrayViewD, Axis};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    group::{Collapsed, GroupNode},
+    port::{PortData, PortType, SharedArray},
+    status::NodeError,
+};
+use crate::{
+    app::Message,
+    graph::{PortRef, IO},
+    gui_node::{GuiGraph, PortDataContainer, PortDataReference},
+    interface::node_config::{Limits, NodeUIParameters, NodeUIWidget, Parameters},
+    overrides::{find_node, OverrideValue},
+    StableMap,
+};
+
+/// Computes the part of the network between a node parameter and the sweep's input once for
+/// each value in a range, and stacks the results.
+/// The swept part of the network is copied for each value and run in parallel, so the network
+/// itself is unchanged
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sweep {
+    /// Node whose parameter is swept, by id or name as in parameter overrides
+    pub node: String,
+    pub parameter: String,
+    pub start: f64,
+    pub stop: f64,
+    pub num: i64,
+    /// The swept part of the network, taken from the graph when the sweep is computed
+    #[serde(skip)]
+    #[debug(skip)]
+    region: Option<Result<Box<Region>, String>>,
+}
+
+/// The nodes between the swept node and the sweep, as a group
+#[derive(Clone)]
+struct Region {
+    group: GroupNode,
+    /// Data on the group's inputs, from the rest of the network
+    inputs: StableMap<String, PortDataContainer>,
+    /// Swept node, inside the group
+    node: u32,
+    /// Group output that feeds the sweep
+    output: String,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            node: String::new(),
+            parameter: String::new(),
+            start: 0.,
+            stop: 1.,
+            num: 8,
+            region: None,
+        }
+    }
+}
+
+/// Sweeps are equal if they sweep the same parameter over the same range
+impl PartialEq for Sweep {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.node, &self.parameter, self.start, self.stop, self.num)
+            == (
+                &other.node,
+                &other.parameter,
+                other.start,
+                other.stop,
+                other.num,
+            )
+    }
+}
+
+impl PartialOrd for Sweep {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (&self.node, &self.parameter, self.start, self.stop, self.num).partial_cmp(&(
+            &other.node,
+            &other.parameter,
+            other.start,
+            other.stop,
+            other.num,
+        ))
+    }
+}
+
+impl Sweep {
+    pub fn inputs() -> StableMap<String, PortType> {
+        [("a".to_string(), PortType::Dynamic)].into()
+    }
+
+    pub fn outputs() -> StableMap<String, PortType> {
+        [
+            ("out".to_string(), PortType::Dynamic),
+            ("values".to_string(), PortType::ArrayReal),
+        ]
+        .into()
+    }
+
+    /// Swept values, evenly spaced from `start` to `stop`
+    pub fn values(&self) -> Vec<f64> {
+        let num = usize::try_from(self.num).unwrap_or(0);
+        let step = (self.stop - self.start) / num.saturating_sub(1).max(1) as f64;
+        (0..num)
+            .map(|i| match i {
+                i if i > 0 && i + 1 == num => self.stop,
+                i => self.start + i as f64 * step,
+            })
+            .collect()
+    }
+
+    /// Take the swept part of the network from `graph`, where the sweep is node `nx`
+    pub fn prepare(&mut self, graph: &GuiGraph, nx: u32) {
+        self.region = Some(Self::region(&self.node, graph, nx).map(Box::new));
+    }
+
+    fn region(selector: &str, graph: &GuiGraph, nx: u32) -> Result<Region, String> {
+        let node = find_node(graph, selector).map_err(|e| e.to_string())?;
+        let source = graph
+            .get_parent(&nx, "a".into())
+            .ok_or("input 'a' is not connected")?;
+
+        // Everything downstream of the swept node that the sweep's input depends on
+        let mut upstream = graph.ancestors(source.node);
+        upstream.insert(source.node);
+        let mut downstream = graph.descendants(node);
+        downstream.insert(node);
+        let nodes: HashSet<u32> = upstream.intersection(&downstream).copied().collect();
+        if nodes.is_empty() {
+            return Err(format!("input 'a' doesn't depend on '{selector}'"));
+        }
+
+        let Collapsed {
+            group,
+            inputs,
+            outputs,
+        } = GroupNode::collapse(graph, &IndexMap::new(), &nodes);
+        let sweep_input = PortRef {
+            node: nx,
+            name: "a".into(),
+            io: IO::In,
+        };
+        let output = outputs
+            .into_iter()
+            .find_map(|(name, to)| (to == sweep_input).then_some(name))
+            .expect("the sweep's input comes from inside the region");
+        let inputs = inputs
+            .into_iter()
+            .map(|(from, name)| {
+                let data = graph
+                    .get_wire_data(&from.node, &from.name)
+                    .ok_or(format!("no data on '{}' of node #{}", from.name, from.node))?;
+                Ok((name, data.clone()))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Region {
+            group,
+            inputs,
+            node,
+            output,
+        })
+    }
+
+    pub fn compute(
+        &mut self,
+        _inputs: StableMap<String, PortDataReference>,
+    ) -> Result<StableMap<String, PortData>, NodeError> {
+        // Don't keep the region, it holds on to upstream data
+        let region = self
+            .region
+            .take()
+            .ok_or(NodeError::Runtime("sweep was not prepared".into()))?
+            .map_err(NodeError::Input)?;
+        let values = self.values();
+        if values.is_empty() {
+            return Err(NodeError::Input("sweep needs at least one value".into()));
+        }
+
+        //// Compute each value, spread over the available threads
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = values.len().div_ceil(threads);
+        let sweep = &*self;
+        let results: Vec<PortData> = thread::scope(|scope| {
+            let handles: Vec<_> = values
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let region = &region;
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|value| sweep.compute_value(region, *value))
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("sweep thread panicked"))
+                .collect::<Result<Vec<_>, _>>()
+        })?
+        .into_iter()
+        .flatten()
+        .collect();
+
+        Ok([
+            ("out".to_string(), stack(results)?),
+            (
+                "values".to_string(),
+                PortData::ArrayReal(
+                    ArrayD::from_shape_vec(vec![values.len()], values)
+                        .expect("1D array shape should always work")
+                        .into_shared(),
+                ),
+            ),
+        ]
+        .into())
+    }
+
+    /// Run the region with the parameter set to `value`
+    fn compute_value(&self, region: &Region, value: f64) -> Result<PortData, NodeError> {
+        let mut group = region.group.clone();
+        group
+            .graph
+            .get_mut_node(region.node)
+            .expect("swept node is in the region")
+            .template
+            .set_parameter(&self.node, &self.parameter, &OverrideValue::Number(value))
+            .map_err(|e| NodeError::Input(e.to_string()))?;
+        let mut outputs = group
+            .compute(region.inputs.clone())
+            .map_err(|e| NodeError::Runtime(format!("{}={value}: {e}", self.parameter)))?;
+        Ok(outputs
+            .remove(&region.output)
+            .expect("group outputs have data after computing"))
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        column![
+            text(format!("{}.{}", self.node, self.parameter)).size(12.),
+            text(format!("× {}", self.num)),
+        ]
+        .align_x(Center)
+        .into()
+    }
+
+    pub fn parameters(&self) -> NodeUIParameters {
+        [
+            ("node", NodeUIWidget::Text(self.node.clone())),
+            ("parameter", NodeUIWidget::Text(self.parameter.clone())),
+            ("start", NodeUIWidget::number(self.start, Limits::default())),
+            ("stop", NodeUIWidget::number(self.stop, Limits::default())),
+            ("num", NodeUIWidget::spinner(self.num, Limits::at_least(1))),
+        ]
+        .map(|(name, widget)| (name.to_string(), widget))
+        .into()
+    }
+
+    pub fn set_parameters(&mut self, parameters: &NodeUIParameters) -> Result<(), NodeError> {
+        self.node = parameters.get_as("node")?;
+        self.parameter = parameters.get_as("parameter")?;
+        self.start = parameters.get_as("start")?;
+        self.stop = parameters.get_as("stop")?;
+        self.num = parameters.get_as("num")?;
+        Ok(())
+    }
+}
+
+/// Stack sweep results along a new first axis. Scalars become 1D arrays
+fn stack(results: Vec<PortData>) -> Result<PortData, NodeError> {
+    fn stack_arrays<T: Clone>(
+        results: &[PortData],
+        view: impl Fn(&PortData) -> Option<ArrayViewD<'_, T>>,
+    ) -> Result<SharedArray<T>, NodeError> {
+        let views = results
+            .iter()
+            .map(|data| {
+                view(data).ok_or(NodeError::Output(
+                    "sweep results have different types".into(),
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        ndarray::stack(Axis(0), &views)
+            .map(ArrayD::into_shared)
+            .map_err(|e| NodeError::Output(format!("can't stack sweep results: {e}")))
+    }
+
+    let results: Vec<_> = results
+        .into_iter()
+        .map(|data| {
+            let array_type = match data {
+                PortData::Integer(_) => PortType::ArrayInteger,
+                PortData::Real(_) => PortType::ArrayReal,
+                PortData::Complex(_) => PortType::ArrayComplex,
+                _ => return data,
+            };
+            data.convert_to(&array_type).unwrap_or(data)
+        })
+        .collect();
+    Ok(match results.first() {
+        Some(PortData::ArrayInteger(_)) => {
+            PortData::ArrayInteger(stack_arrays(&results, |data| match data {
+                PortData::ArrayInteger(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::ArrayReal(_)) => {
+            PortData::ArrayReal(stack_arrays(&results, |data| match data {
+                PortData::ArrayReal(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::ArrayComplex(_)) => {
+            PortData::ArrayComplex(stack_arrays(&results, |data| match data {
+                PortData::ArrayComplex(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::ArrayBool(_)) => {
+            PortData::ArrayBool(stack_arrays(&results, |data| match data {
+                PortData::ArrayBool(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::ArrayUnsigned8(_)) => {
+            PortData::ArrayUnsigned8(stack_arrays(&results, |data| match data {
+                PortData::ArrayUnsigned8(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::ArrayUnsigned16(_)) => {
+            PortData::ArrayUnsigned16(stack_arrays(&results, |data| match data {
+                PortData::ArrayUnsigned16(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::ArrayInteger32(_)) => {
+            PortData::ArrayInteger32(stack_arrays(&results, |data| match data {
+                PortData::ArrayInteger32(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::ArrayReal32(_)) => {
+            PortData::ArrayReal32(stack_arrays(&results, |data| match data {
+                PortData::ArrayReal32(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::ArrayComplex32(_)) => {
+            PortData::ArrayComplex32(stack_arrays(&results, |data| match data {
+                PortData::ArrayComplex32(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(PortData::Dynamic(_)) => {
+            PortData::Dynamic(stack_arrays(&results, |data| match data {
+                PortData::Dynamic(a) => Some(a.view()),
+                _ => None,
+            })?)
+        }
+        Some(_) => return Err(NodeError::Output("objects can't be stacked".into())),
+        None => return Err(NodeError::Output("sweep has no results".into())),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::scheduler::{Scheduler, SyncExecutor};
+    use crate::nodes::{NodeTemplate, RustNode};
+
+    #[test]
+    fn sweep_constant() {
+        let mut graph = GuiGraph::new();
+        let c = graph.node(NodeTemplate::RustNode(RustNode::Constant(1.)).into());
+        let two = graph.node(NodeTemplate::RustNode(RustNode::Constant(2.)).into());
+        let mul = graph.node(NodeTemplate::RustNode(RustNode::Multiply).into());
+        let sweep = graph.node(
+            NodeTemplate::RustNode(RustNode::Sweep(Sweep {
+                node: c.to_string(),
+                parameter: "value".into(),
+                start: 0.,
+                stop: 3.,
+                num: 4,
+                ..Default::default()
+            }))
+            .into(),
+        );
+        graph
+            .add_edge_from_ref(
+                &PortRef::new(c, "out", IO::Out),
+                &PortRef::new(mul, "a", IO::In),
+            )
+            .unwrap();
+        graph
+            .add_edge_from_ref(
+                &PortRef::new(two, "out", IO::Out),
+                &PortRef::new(mul, "b", IO::In),
+            )
+            .unwrap();
+        graph
+            .add_edge_from_ref(
+                &PortRef::new(mul, "out", IO::Out),
+                &PortRef::new(sweep, "a", IO::In),
+            )
+            .unwrap();
+
+        let mut errors = vec![];
+        Scheduler::default().run(&mut graph, &mut SyncExecutor::default(), |g, completion| {
+            match completion.result {
+                Ok(node) => g.set_node_data(completion.nx, node).unwrap(),
+                Err(e) => errors.push(e),
+            }
+        });
+        assert!(errors.is_empty(), "{errors:?}");
+
+        let out = graph.get_wire_data(&sweep, "out").unwrap().read().unwrap();
+        let PortData::ArrayReal(out) = &*out else {
+            panic!("expected a real array, got {out:?}");
+        };
+        // One element per value, as the multiply's output is a scalar
+        assert_eq!(out.shape(), [4]);
+        assert_eq!(out.iter().copied().collect::<Vec<_>>(), [0., 2., 4., 6.]);
+        // The network itself is unchanged
+        assert!(matches!(
+            graph.get_node(c).unwrap().template,
+            NodeTemplate::RustNode(RustNode::Constant(1.))
+        ));
+    }
+
+    #[test]
+    fn values() {
+        let sweep = |start, stop, num| {
+            Sweep {
+                start,
+                stop,
+                num,
+                ..Default::default()
+            }
+            .values()
+        };
+        assert_eq!(sweep(0., 1., 11)[1], 0.1);
+        assert_eq!(sweep(0., 1., 11)[10], 1.);
+        assert_eq!(sweep(2., 0., 3), [2., 1., 0.]);
+        assert_eq!(sweep(5., 6., 1), [5.]);
+        assert!(sweep(0., 1., 0).is_empty());
+    }
+}
@@ -1,23 +1,20 @@
 use std::f32::consts::PI;
 
-use super::{PortData, RustNode};
+use super::plot::{rect_from_parameters, rect_parameters};
+use super::{status::NodeError, PortData};
 use crate::app::Message;
 use crate::gui_node::PortDataContainer;
 use crate::interface::node::{INNER_NODE_WIDTH, NODE_BORDER_WIDTH};
+use crate::interface::node_config::NodeUIParameters;
 use crate::math::{linspace_delta, Vector};
-use crate::nodes::NodeTemplate;
 use crate::StableMap;
 use colorgrad::Gradient;
 use glam::{Mat3, Vec3};
 use iced::mouse;
 use iced::widget::canvas::path::Builder;
 use iced::widget::canvas::{Path, Stroke};
-use iced::widget::{container, horizontal_space, row, text, text_input};
-use iced::Alignment::Center;
-use iced::{
-    widget::{canvas, column},
-    Element,
-};
+use iced::widget::{container, text};
+use iced::{widget::canvas, Element};
 use iced::{Rectangle, Renderer, Theme};
 use itertools::Itertools;
 use ndarray::Array4;
@@ -71,26 +68,33 @@ impl VectorField {
         match input_data.get("a") {
             Some(port) => {
                 let data = match (**port).read().unwrap().clone() {
-                    PortData::ArrayComplex(a) => Array4::<f64>::from_shape_vec(
-                        [
-                            (a.len() as f32).sqrt() as usize,
-                            (a.len() as f32).sqrt() as usize,
-                            1,
-                            3,
-                        ],
-                        a.iter()
-                            .flat_map(|v| {
-                                let (r, theta) = v.to_polar();
-                                [theta.sin(), theta.cos(), r]
-                            })
-                            .collect::<Vec<_>>(),
-                    )
-                    .expect("square matrix"),
-                    PortData::ArrayReal(a) => {
+                    data @ (PortData::ArrayComplex(_) | PortData::ArrayComplex32(_)) => {
+                        let a = data.complexes().expect("complex data");
+                        Array4::<f64>::from_shape_vec(
+                            [
+                                (a.len() as f32).sqrt() as usize,
+                                (a.len() as f32).sqrt() as usize,
+                                1,
+                                3,
+                            ],
+                            a.iter()
+                                .flat_map(|v| {
+                                    let (r, theta) = v.to_polar();
+                                    [theta.sin(), theta.cos(), r]
+                                })
+                                .collect::<Vec<_>>(),
+                        )
+                        .expect("square matrix")
+                    }
+                    data => {
+                        let a = data
+                            .reals()
+                            .unwrap_or_else(|| panic!("unsuported plot types {:?}", port));
                         let xy_len = (a.len() as f32 / 3.0).sqrt() as usize;
-                        a.into_shape_with_order([xy_len, xy_len, 1, 3]).unwrap()
+                        a.into_owned()
+                            .into_shape_with_order([xy_len, xy_len, 1, 3])
+                            .unwrap()
                     }
-                    _ => panic!("unsuported plot types {:?}", port),
                 };
                 container(
                     canvas(VectorFieldCanvas {
@@ -113,65 +117,18 @@ impl VectorField {
         }
     }
 
-    pub fn config_view(
-        &self,
-        id: u32,
-        _input_data: StableMap<String, PortDataContainer>,
-    ) -> Option<Element<'_, Message>> {
-        let center = self.rect.center;
-        let width = self.rect.width;
-        let height = self.rect.height;
-        let message = move |rect| {
-            Message::UpdateNodeTemplate(
-                id,
-                //TODO: handle z_index
-                NodeTemplate::RustNode(RustNode::VectorField(VectorField { rect, z_index: 0 })),
-            )
+    pub fn parameters(&self) -> NodeUIParameters {
+        rect_parameters(self.rect.center, self.rect.width, self.rect.height)
+    }
+
+    pub fn set_parameters(&mut self, parameters: &NodeUIParameters) -> Result<(), NodeError> {
+        let (center, width, height) = rect_from_parameters(parameters)?;
+        self.rect = Rect {
+            center,
+            width,
+            height,
         };
-        Some(
-            column![
-                row![
-                    text("center:"),
-                    horizontal_space(),
-                    text("x"),
-                    text_input("0", &center.x.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.center.x = value.parse().unwrap_or(0.);
-                        message(n)
-                    }),
-                    text("y"),
-                    text_input("0", &center.y.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.center.y = value.parse().unwrap_or(0.);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center)
-                .spacing(4.),
-                row![
-                    text("width:"),
-                    horizontal_space(),
-                    text_input("0", &width.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.width = value.parse().unwrap_or(1.0f32).max(0.001);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center),
-                row![
-                    text("height:"),
-                    horizontal_space(),
-                    text_input("0", &height.to_string()).on_input(move |value| {
-                        let mut n = self.rect;
-                        n.height = value.parse().unwrap_or(1.0f32).max(0.001);
-                        message(n)
-                    }),
-                ]
-                .align_y(Center)
-            ]
-            .spacing(5.0)
-            .into(),
-        )
+        Ok(())
     }
 }
 
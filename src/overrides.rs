@@ -0,0 +1,511 @@
+use std::{fs::read_to_string, path::Path, str::FromStr};
+
+use derive_more::derive::Display;
+use itertools::Itertools;
+
+use crate::{
+    gui_node::{GUINode, GuiGraph},
+    interface::{node_config::NodeUIWidget, numeric_input::PartialUIValue},
+    network::Network,
+    nodes::NodeTemplate,
+};
+
+/// A value for a named node parameter that is supplied from outside the network,
+/// e.g. `linspace.num=256` on the command line.
+/// Lets a network be reused with new data, without editing the network itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterOverride {
+    /// Node name (as displayed, case insensitive, spaces as '_') or node id
+    pub node: String,
+    pub parameter: String,
+    pub value: OverrideValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum OverrideValue {
+    Number(f64),
+    Bool(bool),
+    #[display("\"{_0}\"")]
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Display)]
+pub enum OverrideError {
+    #[display("Invalid override '{_0}', expected <node>.<parameter>=<value>")]
+    Syntax(String),
+    #[display("Could not read overrides file {_0}")]
+    File(String),
+    #[display("No node named '{node}', available nodes: {available}")]
+    UnknownNode { node: String, available: String },
+    #[display("'{node}' matches multiple nodes ({ids}), use the node id instead")]
+    AmbiguousNode { node: String, ids: String },
+    #[display("Node '{node}' has no parameter '{parameter}', available parameters: {available}")]
+    UnknownParameter {
+        node: String,
+        parameter: String,
+        available: String,
+    },
+    #[display("Parameter '{node}.{parameter}' expects {expected}, found {found}")]
+    InvalidValue {
+        node: String,
+        parameter: String,
+        expected: String,
+        found: OverrideValue,
+    },
+}
+
+impl std::error::Error for OverrideError {}
+
+impl FromStr for OverrideValue {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "true" => OverrideValue::Bool(true),
+            "false" => OverrideValue::Bool(false),
+            _ => match s.parse() {
+                Ok(n) => OverrideValue::Number(n),
+                Err(_) => OverrideValue::Text(s.to_string()),
+            },
+        })
+    }
+}
+
+impl FromStr for ParameterOverride {
+    type Err = OverrideError;
+    /// Parse `<node>.<parameter>=<value>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let syntax_error = || OverrideError::Syntax(s.to_string());
+        let (target, value) = s.split_once('=').ok_or_else(syntax_error)?;
+        let (node, parameter) = target.split_once('.').ok_or_else(syntax_error)?;
+        if node.is_empty() || parameter.is_empty() {
+            return Err(syntax_error());
+        }
+        Ok(ParameterOverride {
+            node: node.to_string(),
+            parameter: parameter.to_string(),
+            value: value.parse().map_err(|_| syntax_error())?,
+        })
+    }
+}
+
+/// Read overrides from a toml file, with a table per node
+/// ```toml
+/// [load_image]
+/// path = "scan42.npy"
+///
+/// [linspace]
+/// num = 256
+/// ```
+pub fn read_overrides_file(path: &Path) -> Result<Vec<ParameterOverride>, OverrideError> {
+    let file_error = |e: String| OverrideError::File(format!("{path:?}: {e}"));
+    let src = read_to_string(path).map_err(|e| file_error(e.to_string()))?;
+    let table = src
+        .parse::<toml::Table>()
+        .map_err(|e| file_error(e.to_string()))?;
+
+    table
+        .into_iter()
+        .map(|(node, parameters)| match parameters {
+            toml::Value::Table(parameters) => parameters
+                .into_iter()
+                .map(|(parameter, value)| {
+                    let value = match value {
+                        toml::Value::Integer(i) => OverrideValue::Number(i as f64),
+                        toml::Value::Float(f) => OverrideValue::Number(f),
+                        toml::Value::Boolean(b) => OverrideValue::Bool(b),
+                        toml::Value::String(s) => OverrideValue::Text(s),
+                        other => {
+                            return Err(file_error(format!(
+                                "unsupported value for '{node}.{parameter}': {other}"
+                            )))
+                        }
+                    };
+                    Ok(ParameterOverride {
+                        node: node.clone(),
+                        parameter,
+                        value,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>(),
+            _ => Err(file_error(format!(
+                "expected a table of parameters for node '{node}'"
+            ))),
+        })
+        .flatten_ok()
+        .collect()
+}
+
+/// Name used to refer to a node from outside the network, e.g. "Plot 2D" -> "plot_2d"
+pub fn override_name(template: &NodeTemplate) -> String {
+    template.name().to_lowercase().replace([' ', '-'], "_")
+}
+
+impl Network {
+    /// Apply all overrides to the matching node parameters.
+    /// Every override is attempted, and all errors are returned
+    pub fn apply_overrides(
+        &mut self,
+        overrides: &[ParameterOverride],
+    ) -> Result<(), Vec<OverrideError>> {
+        let errors: Vec<_> = overrides
+            .iter()
+            .filter_map(|o| self.apply_override(o).err())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn apply_override(&mut self, o: &ParameterOverride) -> Result<(), OverrideError> {
+        let nx = find_node(&self.graph, &o.node)?;
+        let template = &mut self
+            .graph
+            .get_mut_node(nx)
+            .expect("found nodes exist")
+            .template;
+        template.set_parameter(&o.node, &o.parameter, &o.value)
+    }
+}
+
+/// Find a node by id, or by its (unique) name
+pub fn find_node(graph: &GuiGraph, selector: &str) -> Result<u32, OverrideError> {
+    let nodes = graph.nodes_ref();
+    if let Ok(id) = selector.parse::<u32>() {
+        if nodes.contains(&id) {
+            return Ok(id);
+        }
+    }
+    let name = |nx: u32| {
+        graph
+            .get_node(nx)
+            .map(|node| override_name(&node.template))
+            .unwrap_or_default()
+    };
+    let selector_name = selector.to_lowercase();
+    let matches: Vec<_> = nodes
+        .iter()
+        .filter(|nx| name(**nx) == selector_name)
+        .collect();
+    match matches.as_slice() {
+        [nx] => Ok(**nx),
+        [] => Err(OverrideError::UnknownNode {
+            node: selector.to_string(),
+            available: nodes
+                .iter()
+                .map(|nx| format!("{} (#{nx})", name(*nx)))
+                .join(", "),
+        }),
+        _ => Err(OverrideError::AmbiguousNode {
+            node: selector.to_string(),
+            ids: matches.iter().map(|nx| format!("#{nx}")).join(", "),
+        }),
+    }
+}
+
+impl NodeTemplate {
+    /// Names of parameters that can be set with `set_parameter`
+    pub fn parameter_names(&self) -> Vec<String> {
+        match self {
+            NodeTemplate::RustNode(rust_node) => rust_node.parameters().into_keys().collect(),
+            NodeTemplate::Group(_) => vec![],
+            NodeTemplate::PyNode(py_node) => py_node
+                .parameters
+                .as_ref()
+                .map(|p| p.keys().cloned().collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The current value of each parameter in `parameter_names`, for display
+    pub fn parameter_values(&self) -> Vec<(String, String)> {
+        match self {
+            NodeTemplate::RustNode(rust_node) => rust_node
+                .parameters()
+                .into_iter()
+                .map(|(k, v)| (k, v.to_string()))
+                .collect(),
+            NodeTemplate::Group(_) => vec![],
+            NodeTemplate::PyNode(py_node) => py_node
+                .parameters
+                .as_ref()
+                .map(|p| p.iter().map(|(k, v)| (k.clone(), v.to_string())).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Set a named parameter, checking that the value is appropriate for the parameter
+    pub fn set_parameter(
+        &mut self,
+        node_name: &str,
+        parameter: &str,
+        value: &OverrideValue,
+    ) -> Result<(), OverrideError> {
+        let unknown_parameter = OverrideError::UnknownParameter {
+            node: node_name.to_string(),
+            parameter: parameter.to_string(),
+            available: self.parameter_names().join(", "),
+        };
+        let invalid_value = |expected: &str| OverrideError::InvalidValue {
+            node: node_name.to_string(),
+            parameter: parameter.to_string(),
+            expected: expected.to_string(),
+            found: value.clone(),
+        };
+        let number = || match value {
+            OverrideValue::Number(n) => Ok(*n),
+            _ => Err(invalid_value("a number")),
+        };
+        // Text parameters take numbers and booleans as they were written
+        let text = match value {
+            OverrideValue::Text(s) => s.clone(),
+            OverrideValue::Number(n) => n.to_string(),
+            OverrideValue::Bool(b) => b.to_string(),
+        };
+
+        let widget = match self {
+            NodeTemplate::RustNode(rust_node) => rust_node.parameters().remove(parameter),
+            NodeTemplate::PyNode(py_node) => py_node
+                .parameters
+                .as_ref()
+                .ok()
+                .and_then(|p| p.get(parameter))
+                .cloned(),
+            NodeTemplate::Group(_) => None,
+        }
+        .ok_or(unknown_parameter)?;
+        let widget = match &widget {
+            NodeUIWidget::Slider { limits, .. } | NodeUIWidget::NumberField { limits, .. } => {
+                let n = number()?;
+                if !limits.contains(n) {
+                    return Err(invalid_value(&format!("a number {limits}")));
+                }
+                widget.with_number(0, n, PartialUIValue::Complete)
+            }
+            NodeUIWidget::Spinner { limits, .. } => match number()? {
+                n if n.fract() != 0. => return Err(invalid_value("an integer")),
+                n if !limits.contains(n as i64) => {
+                    return Err(invalid_value(&format!("an integer {limits}")))
+                }
+                n => widget.with_number(0, n, PartialUIValue::Complete),
+            },
+            NodeUIWidget::Dropdown { options, .. } => match options.contains(&text) {
+                true => NodeUIWidget::Dropdown {
+                    value: text,
+                    options: options.clone(),
+                },
+                false => return Err(invalid_value(&format!("one of {}", options.join(", ")))),
+            },
+            NodeUIWidget::Text(_) => NodeUIWidget::Text(text),
+            NodeUIWidget::File { extensions, .. } => NodeUIWidget::File {
+                path: text,
+                extensions: extensions.clone(),
+            },
+            NodeUIWidget::CheckBox(_) => match value {
+                OverrideValue::Bool(b) => NodeUIWidget::CheckBox(*b),
+                _ => return Err(invalid_value("true or false")),
+            },
+            // Components separated by commas, `point.at=1,2.5`
+            NodeUIWidget::Vector {
+                value: components,
+                limits,
+                ..
+            } => {
+                let expected = format!("{} comma separated numbers", components.len());
+                let numbers = text
+                    .split(',')
+                    .map(|n| n.trim().parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .ok()
+                    .filter(|numbers| numbers.len() == components.len())
+                    .ok_or_else(|| invalid_value(&expected))?;
+                if !numbers.iter().all(|n| limits.contains(*n)) {
+                    return Err(invalid_value(&format!("{expected} {limits}")));
+                }
+                numbers
+                    .into_iter()
+                    .enumerate()
+                    .fold(widget.clone(), |widget, (i, n)| {
+                        widget.with_number(i, n, PartialUIValue::Complete)
+                    })
+            }
+        };
+
+        match self {
+            NodeTemplate::RustNode(rust_node) => rust_node
+                .update_parameter(parameter, &widget)
+                .map_err(|e| invalid_value(&e.to_string()))?,
+            NodeTemplate::PyNode(py_node) => {
+                if let Ok(parameters) = &mut py_node.parameters {
+                    parameters.insert(parameter.to_string(), widget);
+                }
+            }
+            NodeTemplate::Group(_) => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interface::node_config::ParameterValue;
+    use crate::nodes::{linspace::LinspaceConfig, RustNode};
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "linspace.num=256".parse::<ParameterOverride>(),
+            Ok(ParameterOverride {
+                node: "linspace".into(),
+                parameter: "num".into(),
+                value: OverrideValue::Number(256.)
+            })
+        );
+        assert_eq!(
+            "load_image.path=data/scan.42.npy"
+                .parse::<ParameterOverride>()
+                .map(|o| o.value),
+            Ok(OverrideValue::Text("data/scan.42.npy".into()))
+        );
+        assert!("linspace=3".parse::<ParameterOverride>().is_err());
+        assert!("linspace.num".parse::<ParameterOverride>().is_err());
+    }
+
+    #[test]
+    fn apply() {
+        let mut network = Network::default();
+        let linspace = network.graph.node(
+            NodeTemplate::RustNode(RustNode::Linspace(LinspaceConfig::new(0., 1., 10))).into(),
+        );
+        let c1 = network
+            .graph
+            .node(NodeTemplate::RustNode(RustNode::Constant(0.)).into());
+        let _c2 = network
+            .graph
+            .node(NodeTemplate::RustNode(RustNode::Constant(0.)).into());
+
+        let set = |s: &str| s.parse::<ParameterOverride>().unwrap();
+        assert_eq!(
+            network.apply_overrides(&[set("linspace.num=256"), set(&format!("{c1}.value=0.5"))]),
+            Ok(())
+        );
+        assert_eq!(
+            network.graph.get_node(linspace).unwrap().template,
+            NodeTemplate::RustNode(RustNode::Linspace(LinspaceConfig::new(0., 1., 256)))
+        );
+        assert_eq!(
+            network.graph.get_node(c1).unwrap().template,
+            NodeTemplate::RustNode(RustNode::Constant(0.5))
+        );
+
+        let errors = network
+            .apply_overrides(&[
+                set("missing.value=1"),
+                set("constant.value=1"),
+                set("linspace.steps=1"),
+                set("linspace.num=true"),
+            ])
+            .unwrap_err();
+        assert!(matches!(errors[0], OverrideError::UnknownNode { .. }));
+        assert!(matches!(errors[1], OverrideError::AmbiguousNode { .. }));
+        assert!(matches!(errors[2], OverrideError::UnknownParameter { .. }));
+        assert!(matches!(errors[3], OverrideError::InvalidValue { .. }));
+    }
+
+    #[test]
+    fn python_parameters() {
+        let widget = |s: &str| s.parse::<NodeUIWidget>().unwrap();
+        let mut template = NodeTemplate::PyNode(crate::python::py_node::PyNode {
+            name: "load_image".into(),
+            absolute_path: "/nodes/load_image.py".into(),
+            relative_path: "load_image.py".into(),
+            ports: Ok(Default::default()),
+            parameters: Ok([
+                ("path".into(), widget("File")),
+                ("d".into(), widget("Slider")),
+                ("n".into(), widget("Spinner")),
+                ("at".into(), widget("Vector")),
+            ]
+            .into()),
+            timeout: None,
+            cacheable: false,
+        });
+        let mut set = |parameter: &str, value: &str| {
+            template.set_parameter("load_image", parameter, &value.parse().unwrap())
+        };
+        assert_eq!(set("path", "data/scan.42.npy"), Ok(()));
+        assert_eq!(set("n", "3"), Ok(()));
+        assert_eq!(set("at", "1, 2.5"), Ok(()));
+        assert!(matches!(
+            set("d", "2"),
+            Err(OverrideError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            set("n", "0.5"),
+            Err(OverrideError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            set("at", "1"),
+            Err(OverrideError::InvalidValue { .. })
+        ));
+        assert_eq!(
+            template.parameter_values(),
+            vec![
+                ("at".into(), "1, 2.5".into()),
+                ("d".into(), "0".into()),
+                ("n".into(), "3".into()),
+                ("path".into(), "data/scan.42.npy".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rust_parameters() {
+        let mut template = NodeTemplate::RustNode(RustNode::Plot(Default::default()));
+        let mut set = |parameter: &str, value: &str| {
+            template.set_parameter("plot", parameter, &value.parse().unwrap())
+        };
+        assert_eq!(set("center", "1, 2.5"), Ok(()));
+        assert_eq!(set("width", "4"), Ok(()));
+        assert!(matches!(
+            set("width", "0"),
+            Err(OverrideError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            set("z_index", "1"),
+            Err(OverrideError::UnknownParameter { .. })
+        ));
+        assert_eq!(
+            &template.parameter_values()[..2],
+            [
+                ("center".into(), "1, 2.5".into()),
+                ("height".into(), "10".into())
+            ]
+        );
+
+        // Edits from the side bar outside the parameter's limits are shown as invalid, and not
+        // used until they are corrected
+        let mut linspace = RustNode::Linspace(LinspaceConfig::new(0., 1., 10));
+        let spinner = linspace.parameters()["num"].with_number(0, 0., PartialUIValue::Complete);
+        let unlimited = NodeUIWidget::spinner(-3, Default::default());
+        assert_eq!(spinner.value(), ParameterValue::Integer(10));
+        assert!(matches!(
+            &spinner,
+            NodeUIWidget::Spinner { in_progress: PartialUIValue::UnParsable(text), .. } if text == "0"
+        ));
+        let spinner = spinner.with_number(0, 20., PartialUIValue::Complete);
+        assert_eq!(spinner.value(), ParameterValue::Integer(20));
+        assert!(matches!(
+            spinner,
+            NodeUIWidget::Spinner {
+                in_progress: PartialUIValue::Complete,
+                ..
+            }
+        ));
+        assert_eq!(linspace.update_parameter("num", &unlimited), Ok(()));
+        assert_eq!(linspace, RustNode::Linspace(LinspaceConfig::new(0., 1., 1)));
+        assert!(linspace.update_parameter("steps", &unlimited).is_err());
+    }
+}
@@ -3,14 +3,18 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use log::error;
 use relative_path::PathExt;
 use strum::IntoEnumIterator;
 
 use crate::{
-    nodes::{NodeTemplate, RustNode},
+    nodes::{group::GroupNode, NodeTemplate, RustNode},
     python::py_node::PyNode,
 };
 
+/// Directory of a project that saved groups are kept in
+pub const MACRO_DIR: &str = "macros";
+
 #[derive(Debug, PartialEq, PartialOrd, Eq, Ord, Clone)]
 pub enum NodeTree<T> {
     Group(String, Vec<NodeTree<T>>),
@@ -53,8 +57,16 @@ pub struct Project {
 
 pub fn python_project(absolute_path: &Path) -> Project {
     let node_tree = python_tree(absolute_path.to_path_buf(), |dir| {
-        (dir.path().extension().map(|os| os.to_str()) == Some(Some("py")) || dir.path().is_dir())
-            && not_hidden(dir)
+        let extension = dir
+            .path()
+            .extension()
+            .and_then(|os| os.to_str().map(String::from));
+        let included = match extension.as_deref() {
+            Some("py") => is_python_node(&dir.path()),
+            Some("ron") => is_macro(absolute_path, &dir.path()),
+            _ => dir.path().is_dir(),
+        };
+        included && not_hidden(dir)
     });
     Project {
         absolute_path: absolute_path.to_path_buf(),
@@ -72,6 +84,41 @@ pub fn rust_project() -> Project {
     }
 }
 
+/// Whether a python file defines a node, rather than being a helper for nodes to import.
+/// Files starting with `_`, such as `__init__.py`, are always helpers.
+pub fn is_python_node(path: &Path) -> bool {
+    let private = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('_'));
+    !private
+        && fs::read_to_string(path)
+            .is_ok_and(|src| src.lines().any(|line| line.starts_with("def config(")))
+}
+
+/// Whether a file is a group saved as a macro. Only `.ron` files in the project's `MACRO_DIR` are,
+/// other `.ron` files may be saved networks or data
+pub fn is_macro(project_path: &Path, path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "ron")
+        && path.parent() == Some(project_path.join(MACRO_DIR).as_path())
+}
+
+/// File a group named `name` is saved to as a macro of the project. Characters that could lead
+/// out of `MACRO_DIR`, like `/` and `.`, are replaced with `_`
+pub fn macro_path(project_path: &Path, name: &str) -> PathBuf {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() => c,
+            ' ' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+    let stem = if stem.is_empty() { "macro" } else { &stem };
+    project_path.join(MACRO_DIR).join(format!("{stem}.ron"))
+}
+
 pub fn not_hidden(entry: &DirEntry) -> bool {
     !entry
         .file_name()
@@ -111,6 +158,17 @@ where
                             entries,
                         ))
                     }
+                } else if is_macro(&project_path, &entry.path()) {
+                    // A saved group
+                    match fs::read_to_string(entry.path())
+                        .map_err(|e| e.to_string())
+                        .and_then(|src| ron::from_str::<GroupNode>(&src).map_err(|e| e.to_string()))
+                    {
+                        Ok(group) => {
+                            root.push(NodeTree::Leaf(NodeTemplate::Group(Box::new(group))))
+                        }
+                        Err(e) => error!("Could not read macro {:?}: {e}", entry.path()),
+                    }
                 } else {
                     root.push(NodeTree::Leaf(NodeTemplate::PyNode(PyNode::new(
                         entry.path(),
@@ -128,3 +186,28 @@ where
 
     inner(project_path.clone(), project_path, filter_entries)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn macro_paths() {
+        let project = Path::new("/nodes");
+        assert_eq!(
+            macro_path(project, "Coil sum"),
+            Path::new("/nodes/macros/Coil sum.ron")
+        );
+        assert_eq!(
+            macro_path(project, "../a/b"),
+            Path::new("/nodes/macros/___a_b.ron")
+        );
+        assert_eq!(
+            macro_path(project, " "),
+            Path::new("/nodes/macros/macro.ron")
+        );
+        assert!(is_macro(project, &macro_path(project, "../x")));
+        assert!(!is_macro(project, Path::new("/nodes/networks/scan.ron")));
+        assert!(!is_macro(project, Path::new("/nodes/macros/sum.py")));
+    }
+}
@@ -1,4 +1,6 @@
+pub mod module_cache;
 pub mod py_node;
+pub mod worker;
 
 //#[cfg(test)]
 //mod test {
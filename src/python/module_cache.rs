@@ -0,0 +1,363 @@
+//! Python node modules, kept between computes
+//!
+//! Node directories are imported as python packages: each project directory becomes a package
+//! under [`ROOT_PACKAGE`], so `nodes/core/bloch/Bloch.py` is the module
+//! `gpi_nodes.nodes.core.bloch.Bloch`, and nodes can import the files next to them with relative
+//! imports (`from .Spin import N`). A node module is shared by reading the node's config and
+//! running its compute, and is only imported again once a file in the namespace changes, found by
+//! its modified time or on [`refresh`].
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use log::trace;
+use pyo3::{
+    ffi::c_str,
+    sync::GILOnceCell,
+    types::{PyAnyMethods, PyList, PyListMethods, PyModule},
+    Bound, Py, PyResult, Python,
+};
+use relative_path::RelativePath;
+
+use crate::{graph::memo::fingerprint_hasher, nodes::status::NodeError};
+
+/// Package every project's nodes are imported under
+pub const ROOT_PACKAGE: &str = "gpi_nodes";
+
+struct Loaded {
+    /// Node modules by file
+    nodes: BTreeMap<PathBuf, Py<PyModule>>,
+    /// Modified time of every file imported into the node namespace, nodes and helpers alike
+    files: BTreeMap<PathBuf, Option<SystemTime>>,
+    /// Package name of each project directory
+    projects: BTreeMap<PathBuf, String>,
+    /// Hash of each python file's source, and the modified time it was read at
+    sources: BTreeMap<PathBuf, (SystemTime, u64)>,
+}
+
+/// The lock is never held while python code runs: python can hand the GIL to another thread
+/// mid-execution, which could then block on the lock while holding the GIL.
+static LOADED: Mutex<Loaded> = Mutex::new(Loaded {
+    nodes: BTreeMap::new(),
+    files: BTreeMap::new(),
+    projects: BTreeMap::new(),
+    sources: BTreeMap::new(),
+});
+
+static GPI: GILOnceCell<Py<PyModule>> = GILOnceCell::new();
+
+/// The `gpi` helper module nodes import, installed once per interpreter
+pub fn gpi(py: Python<'_>) -> PyResult<&Bound<'_, PyModule>> {
+    GPI.get_or_try_init(py, || {
+        PyModule::from_code(
+            py,
+            c_str!(include_str!("../../python/gpi.py")),
+            c_str!("gpi.py"),
+            c_str!("gpi"),
+        )
+        .map(Bound::unbind)
+    })
+    .map(|module| module.bind(py))
+}
+
+/// The module for the node at `path`, importing it only if it has not been loaded yet or the file
+/// has changed since. `relative_path` is the node's path within its project directory.
+pub fn load<'py>(
+    py: Python<'py>,
+    path: &Path,
+    relative_path: &RelativePath,
+) -> Result<Bound<'py, PyModule>, NodeError> {
+    let modified = modified(path);
+    let stale = {
+        let loaded = LOADED.lock().expect("module cache is not poisoned");
+        match loaded.nodes.get(path) {
+            Some(module) if modified.is_some() && loaded.files.get(path) == Some(&modified) => {
+                return Ok(module.bind(py).clone())
+            }
+            module => module.is_some(),
+        }
+    };
+    if stale {
+        refresh(py);
+    }
+
+    let error = |e| NodeError::Syntax(format!("Error in node '{relative_path}' \n{e}"));
+    gpi(py).map_err(|e| NodeError::Syntax(format!("Error in gpi module\n{e}")))?;
+    let project = path
+        .ancestors()
+        .nth(relative_path.components().count())
+        .expect("relative path is within the node's absolute path");
+    let name = module_name(&project_package(py, project).map_err(error)?, relative_path);
+    trace!("Importing python module '{name}'");
+    let module = py
+        .import("importlib")
+        .and_then(|importlib| importlib.call_method1("import_module", (&name,)))
+        .map_err(error)?
+        .downcast_into::<PyModule>()
+        .map_err(|e| error(e.into()))?;
+
+    // Record every file the import brought in, so changes to helpers are noticed too
+    let imported = imported_files(py);
+    let mut loaded = LOADED.lock().expect("module cache is not poisoned");
+    loaded.files.entry(path.to_path_buf()).or_insert(modified);
+    for file in imported {
+        let modified = self::modified(&file);
+        loaded.files.entry(file).or_insert(modified);
+    }
+    loaded
+        .nodes
+        .insert(path.to_path_buf(), module.clone().unbind());
+    Ok(module)
+}
+
+/// Hash of the source of the node at `path`, and of the python files next to it, which it can
+/// import as helpers. Helpers elsewhere, e.g. `from ..common import f`, aren't included.
+/// Files are only read again once they change
+pub fn source_hash(path: &Path) -> Option<u64> {
+    let mut files: Vec<_> = fs::read_dir(path.parent()?)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|file| file.extension().is_some_and(|extension| extension == "py"))
+        .collect();
+    files.sort();
+
+    let mut hasher = fingerprint_hasher();
+    let mut loaded = LOADED.lock().expect("module cache is not poisoned");
+    for file in files {
+        let modified = modified(&file)?;
+        let hash = match loaded.sources.get(&file) {
+            Some((read_at, hash)) if *read_at == modified => *hash,
+            _ => {
+                let mut file_hasher = fingerprint_hasher();
+                fs::read(&file).ok()?.hash(&mut file_hasher);
+                let hash = file_hasher.finish();
+                loaded.sources.insert(file.clone(), (modified, hash));
+                hash
+            }
+        };
+        (file.file_name()?.to_str()?, hash).hash(&mut hasher);
+    }
+    Some(hasher.finish())
+}
+
+/// If any file imported into the node namespace changed, drop every module in it, so nodes and
+/// the helpers they depend on are imported fresh the next time they're used.
+pub fn refresh(py: Python<'_>) {
+    {
+        let mut loaded = LOADED.lock().expect("module cache is not poisoned");
+        if loaded
+            .files
+            .iter()
+            .all(|(path, before)| before.is_some() && modified(path) == *before)
+        {
+            return;
+        }
+        loaded.nodes.clear();
+        loaded.files.clear();
+    }
+    trace!("Node files changed, dropping the '{ROOT_PACKAGE}' namespace");
+
+    let result = py.import("sys").and_then(|sys| {
+        let sys_modules = sys.getattr("modules")?;
+        let names = sys_modules
+            .call_method0("keys")?
+            .try_iter()?
+            .filter_map(|name| name.ok()?.extract::<String>().ok())
+            .filter(|name| in_namespace(name))
+            .collect::<Vec<_>>();
+        for name in names {
+            sys_modules.del_item(name)?;
+        }
+        // New files may have been added to directories python has already listed
+        py.import("importlib")?
+            .call_method0("invalidate_caches")
+            .map(|_| ())
+    });
+    if let Err(e) = result {
+        log::error!("Could not drop python node modules: {e}");
+    }
+}
+
+/// The package a project directory is imported as, created on first use
+fn project_package(py: Python<'_>, project: &Path) -> PyResult<String> {
+    let package = {
+        let mut loaded = LOADED.lock().expect("module cache is not poisoned");
+        match loaded.projects.get(project) {
+            Some(package) => package.clone(),
+            None => {
+                let stem: String = project
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_default()
+                    .chars()
+                    .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                    .collect();
+                let stem = if stem.starts_with(|c: char| c.is_alphabetic()) {
+                    stem
+                } else {
+                    format!("project_{stem}")
+                };
+                let package = (0..)
+                    .map(|n| match n {
+                        0 => format!("{ROOT_PACKAGE}.{stem}"),
+                        n => format!("{ROOT_PACKAGE}.{stem}_{n}"),
+                    })
+                    .find(|name| !loaded.projects.values().any(|p| p == name))
+                    .expect("some package name is unused");
+                loaded
+                    .projects
+                    .insert(project.to_path_buf(), package.clone());
+                package
+            }
+        }
+    };
+
+    // Packages are dropped along with their modules on refresh, so are recreated as needed
+    let sys = py.import("sys")?;
+    let sys_modules = sys.getattr("modules")?;
+    if !sys_modules.contains(ROOT_PACKAGE)? {
+        // Bytecode caches are keyed by modified time in seconds, which misses quick edits
+        sys.setattr("dont_write_bytecode", true)?;
+        let root = PyModule::new(py, ROOT_PACKAGE)?;
+        root.setattr("__path__", PyList::empty(py))?;
+        sys_modules.set_item(ROOT_PACKAGE, root)?;
+    }
+    if !sys_modules.contains(&package)? {
+        let module = PyModule::new(py, &package)?;
+        let search = PyList::empty(py);
+        search.append(project)?;
+        module.setattr("__path__", search)?;
+        module.setattr("__package__", &package)?;
+        sys_modules.set_item(&package, &module)?;
+        let (_, stem) = package.split_at(ROOT_PACKAGE.len() + 1);
+        sys_modules.get_item(ROOT_PACKAGE)?.setattr(stem, module)?;
+    }
+    Ok(package)
+}
+
+/// `gpi_nodes.<project>.dir.node` for `dir/node.py`
+fn module_name(package: &str, relative_path: &RelativePath) -> String {
+    let path = relative_path.with_extension("");
+    [package]
+        .into_iter()
+        .chain(path.components().map(|c| c.as_str()))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn in_namespace(name: &str) -> bool {
+    name == ROOT_PACKAGE
+        || name
+            .strip_prefix(ROOT_PACKAGE)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Source files of every module currently imported into the node namespace
+fn imported_files(py: Python<'_>) -> Vec<PathBuf> {
+    let modules = py
+        .import("sys")
+        .and_then(|sys| sys.getattr("modules")?.call_method0("items")?.try_iter());
+    let Ok(modules) = modules else {
+        return vec![];
+    };
+    modules
+        .filter_map(|item| {
+            let (name, module) = item.ok()?.extract::<(String, Bound<_>)>().ok()?;
+            in_namespace(&name)
+                .then(|| module.getattr("__file__").ok()?.extract::<PathBuf>().ok())
+                .flatten()
+        })
+        .collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::File, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn reload_dependents() {
+        pyo3::prepare_freethreaded_python();
+        let project =
+            std::env::temp_dir().join(format!("foray_module_cache_{}", std::process::id()));
+        fs::create_dir_all(project.join("bloch")).unwrap();
+        let helper = project.join("bloch/_constants.py");
+        fs::write(&helper, "N = 12\n").unwrap();
+        fs::write(project.join("bloch/Spin.py"), "from ._constants import N\n").unwrap();
+        fs::write(
+            project.join("bloch/Bloch.py"),
+            "from .Spin import N\ncalls = []\n",
+        )
+        .unwrap();
+        Python::with_gil(|py| {
+            let load = |py, node: &str| {
+                let relative = RelativePath::new(node);
+                load(py, &relative.to_path(&project), relative).unwrap()
+            };
+            let n =
+                |module: &Bound<PyModule>| module.getattr("N").unwrap().extract::<i64>().unwrap();
+            let calls = |module: &Bound<PyModule>| module.getattr("calls").unwrap().len().unwrap();
+            let spin = load(py, "bloch/Spin.py");
+            let bloch = load(py, "bloch/Bloch.py");
+            bloch
+                .getattr("calls")
+                .unwrap()
+                .call_method1("append", (1,))
+                .unwrap();
+            // Modules and their state are kept between uses, and shared with the nodes importing them
+            assert_eq!(calls(&load(py, "bloch/Bloch.py")), 1);
+            assert!(load(py, "bloch/Spin.py").is(&spin));
+            assert_eq!(n(&bloch), 12);
+
+            fs::write(&helper, "N = 4\n").unwrap();
+            let later = SystemTime::now() + Duration::from_secs(5);
+            File::options()
+                .write(true)
+                .open(&helper)
+                .unwrap()
+                .set_modified(later)
+                .unwrap();
+            refresh(py);
+            // Dependents of the helper are imported again
+            let bloch = load(py, "bloch/Bloch.py");
+            assert_eq!(calls(&bloch), 0);
+            assert_eq!(n(&bloch), 4);
+        });
+        fs::remove_dir_all(project).unwrap();
+    }
+
+    #[test]
+    fn source_hash_includes_helpers() {
+        let project =
+            std::env::temp_dir().join(format!("foray_source_hash_{}", std::process::id()));
+        fs::create_dir_all(&project).unwrap();
+        let node = project.join("Bloch.py");
+        let helper = project.join("_constants.py");
+        fs::write(&node, "from ._constants import N\n").unwrap();
+        fs::write(&helper, "N = 12\n").unwrap();
+
+        let before = source_hash(&node).unwrap();
+        assert_eq!(source_hash(&node), Some(before));
+        fs::write(&helper, "N = 4\n").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        File::options()
+            .write(true)
+            .open(&helper)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_ne!(source_hash(&node), Some(before));
+        fs::remove_dir_all(project).unwrap();
+    }
+}
@@ -1,16 +1,13 @@
-use std::{ffi::CString, fs, path::PathBuf, str::FromStr};
+use std::{collections::BTreeSet, ffi::CStr, path::PathBuf, str::FromStr};
 
 use derive_more::derive::{Debug, Display};
-use iced::{
-    widget::{column, *},
-    Alignment::Center,
-};
-use iced::{Element, Length::Fill};
+use iced::{widget::text, Element};
 use log::trace;
-use numpy::{Complex64, PyArrayMethods, ToPyArray};
+use numpy::{Complex32, Complex64, PyArrayDyn, PyArrayMethods};
 use pyo3::{
+    exceptions::PyValueError,
     ffi::c_str,
-    types::{PyAnyMethods, PyComplex, PyDict, PyDictMethods, PyModule},
+    types::{PyAnyMethods, PyCapsule, PyCapsuleMethods, PyComplex, PyDict, PyDictMethods},
     Bound, FromPyObject, IntoPyObject, PyAny, PyErr, PyObject, PyResult, Python,
 };
 use relative_path::RelativePathBuf;
@@ -19,13 +16,17 @@ use strum::VariantNames;
 
 use crate::{
     app::Message,
-    interface::node_config::{NodeUIParameters, NodeUIWidget},
+    interface::{
+        node_config::{parameters_view, Limits, NodeUIParameters, NodeUIWidget, ParameterValue},
+        numeric_input::PartialUIValue,
+    },
+    python::{module_cache, worker},
     StableMap,
 };
 use crate::{
     gui_node::PortDataReference,
     nodes::{
-        port::{PortData, PortType},
+        port::{PortData, PortType, SharedArray},
         status::NodeError,
     },
 };
@@ -39,12 +40,48 @@ pub struct PyNode {
     pub relative_path: RelativePathBuf,
     pub ports: Result<PortDef, NodeError>,
     pub parameters: Result<NodeUIParameters, NodeError>,
+    /// Seconds the node may run for in a python worker, from the `timeout` of its config
+    #[serde(default)]
+    pub timeout: Option<f64>,
+    /// Outputs only depend on inputs and parameters, from the `cacheable` of its config.
+    /// Only these nodes are memoized, as others may read files or other state
+    #[serde(default)]
+    pub cacheable: bool,
 }
 
-#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq, FromPyObject, PartialOrd)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
 pub struct PortDef {
     pub inputs: StableMap<String, PortType>,
     pub outputs: StableMap<String, PortType>,
+    /// Inputs declared with `gpi.optional`, which don't need to be connected
+    #[serde(default)]
+    pub optional: BTreeSet<String>,
+}
+
+impl<'py> FromPyObject<'py> for PortDef {
+    fn extract_bound(ob: &pyo3::Bound<'py, pyo3::PyAny>) -> pyo3::PyResult<Self> {
+        let mut optional = BTreeSet::new();
+        let inputs = ob
+            .getattr("inputs")?
+            .extract::<StableMap<String, Bound<PyAny>>>()?
+            .into_iter()
+            .map(|(name, port)| {
+                // `optional(port, default)` wraps the port type
+                let port_type = if port.hasattr("default")? {
+                    optional.insert(name.clone());
+                    port.getattr("port")?.extract()?
+                } else {
+                    port.extract()?
+                };
+                Ok((name, port_type))
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(PortDef {
+            inputs,
+            outputs: ob.getattr("outputs")?.extract()?,
+            optional,
+        })
+    }
 }
 
 impl<'py> FromPyObject<'py> for PortType {
@@ -64,16 +101,126 @@ impl<'py> FromPyObject<'py> for PortType {
     }
 }
 
+impl<'py> FromPyObject<'py> for NodeUIWidget {
+    /// A widget kind (`ui.Slider`), or a widget declared with its settings (`ui.slider(max=10)`)
+    fn extract_bound(ob: &pyo3::Bound<'py, pyo3::PyAny>) -> pyo3::PyResult<Self> {
+        if let Ok(kind) = ob.extract::<String>() {
+            return NodeUIWidget::from_str(&kind).map_err(|_| {
+                PyValueError::new_err(format!(
+                    "unknown widget '{kind}', expected one of {:?}",
+                    NodeUIWidget::VARIANTS
+                ))
+            });
+        }
+        fn limits<'py, T: FromPyObject<'py>>(ob: &Bound<'py, PyAny>) -> PyResult<Limits<T>> {
+            Ok(Limits {
+                min: ob.getattr("min")?.extract()?,
+                max: ob.getattr("max")?.extract()?,
+                step: ob.getattr("step")?.extract()?,
+                units: ob.getattr("units")?.extract()?,
+            })
+        }
+        let default = ob.getattr("default")?;
+        let kind: String = ob.getattr("__class__")?.getattr("__name__")?.extract()?;
+        Ok(match kind.as_str() {
+            "slider" | "number" => {
+                let limits = limits(ob)?;
+                let value = limits.clamp(default.extract()?);
+                let in_progress = PartialUIValue::Complete;
+                match kind.as_str() {
+                    "slider" => NodeUIWidget::Slider {
+                        value,
+                        limits,
+                        in_progress,
+                    },
+                    _ => NodeUIWidget::NumberField {
+                        value,
+                        limits,
+                        in_progress,
+                    },
+                }
+            }
+            "spinner" => {
+                let limits = limits(ob)?;
+                NodeUIWidget::Spinner {
+                    value: limits.clamp(default.extract()?),
+                    limits,
+                    in_progress: PartialUIValue::Complete,
+                }
+            }
+            "dropdown" => {
+                let options: Vec<String> = ob.getattr("options")?.extract()?;
+                let value = match default.extract::<Option<String>>()? {
+                    Some(value) if options.contains(&value) => value,
+                    Some(value) => {
+                        return Err(PyValueError::new_err(format!(
+                            "dropdown default '{value}' is not one of its options {options:?}"
+                        )))
+                    }
+                    None => options.first().cloned().ok_or_else(|| {
+                        PyValueError::new_err("dropdown needs at least one option")
+                    })?,
+                };
+                NodeUIWidget::Dropdown { value, options }
+            }
+            "text" => NodeUIWidget::Text(default.extract()?),
+            "file" => NodeUIWidget::File {
+                path: default.extract()?,
+                extensions: ob.getattr("extensions")?.extract()?,
+            },
+            "checkbox" => NodeUIWidget::CheckBox(default.extract()?),
+            "vector" => {
+                let limits = limits::<f64>(ob)?;
+                NodeUIWidget::Vector {
+                    value: default
+                        .extract::<Vec<f64>>()?
+                        .into_iter()
+                        .map(|v| limits.clamp(v))
+                        .collect(),
+                    limits,
+                    in_progress: vec![],
+                }
+            }
+            _ => return Err(PyValueError::new_err(format!("unknown widget '{kind}'"))),
+        })
+    }
+}
+
+impl ParameterValue {
+    /// The value as the python type its widget gives: float, int, bool, str, pathlib.Path or a
+    /// list of floats
+    pub fn to_py(&self, py: Python) -> PyResult<PyObject> {
+        Ok(match self {
+            ParameterValue::Real(value) => value.into_pyobject(py)?.into_any().unbind(),
+            ParameterValue::Integer(value) => value.into_pyobject(py)?.into_any().unbind(),
+            ParameterValue::Bool(value) => value.into_pyobject(py)?.to_owned().into_any().unbind(),
+            ParameterValue::Text(value) => value.into_pyobject(py)?.into_any().unbind(),
+            ParameterValue::Path(path) => py
+                .import("pathlib")?
+                .getattr("Path")?
+                .call1((path,))?
+                .unbind(),
+            ParameterValue::List(value) => value.into_pyobject(py)?.into_any().unbind(),
+        })
+    }
+}
+
 impl PortData {
     pub fn to_py(&self, py: Python) -> PyObject {
         match self {
             PortData::Integer(val) => val.into_pyobject(py).expect("valid python integer").into(),
             PortData::Real(val) => val.into_pyobject(py).expect("valid python float").into(),
             PortData::Complex(val) => PyComplex::from_doubles(py, val.re, val.im).into(),
-            PortData::ArrayReal(array_base) => array_base.to_pyarray(py).into_any().into(),
-            PortData::ArrayInteger(array_base) => array_base.to_pyarray(py).into_any().into(),
-            PortData::ArrayComplex(array_base) => array_base.to_pyarray(py).into_any().into(),
-            PortData::Dynamic(array_base) => array_base.to_pyarray(py).into_any().into(),
+            PortData::ArrayReal(array_base) => lend(array_base, py),
+            PortData::ArrayInteger(array_base) => lend(array_base, py),
+            PortData::ArrayComplex(array_base) => lend(array_base, py),
+            PortData::ArrayBool(array_base) => lend(array_base, py),
+            PortData::ArrayUnsigned8(array_base) => lend(array_base, py),
+            PortData::ArrayUnsigned16(array_base) => lend(array_base, py),
+            PortData::ArrayInteger32(array_base) => lend(array_base, py),
+            PortData::ArrayReal32(array_base) => lend(array_base, py),
+            PortData::ArrayComplex32(array_base) => lend(array_base, py),
+            PortData::Dynamic(array_base) => lend(array_base, py),
             PortData::Object(obj) => {
                 let dict = PyDict::new(py);
                 obj.iter().for_each(|(k, v)| {
@@ -85,59 +232,135 @@ impl PortData {
     }
 }
 
+/// Element types of arrays that can be lent to numpy
+trait Shareable: numpy::Element + Clone + Send + 'static {
+    /// Name of the capsules that keep lent arrays alive
+    const CAPSULE: &'static CStr;
+}
+
+impl Shareable for i64 {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<i64>");
+}
+
+impl Shareable for f64 {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<f64>");
+}
+
+impl Shareable for Complex64 {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<Complex64>");
+}
+
+impl Shareable for bool {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<bool>");
+}
+
+impl Shareable for u8 {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<u8>");
+}
+
+impl Shareable for u16 {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<u16>");
+}
+
+impl Shareable for i32 {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<i32>");
+}
+
+impl Shareable for f32 {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<f32>");
+}
+
+impl Shareable for Complex32 {
+    const CAPSULE: &'static CStr = c_str!("foray.SharedArray<Complex32>");
+}
+
+/// Expose `array` to python as a read only numpy array, without copying. Its elements may be
+/// shared with other nodes, so python can't write to them
+fn lend<T: Shareable>(array: &SharedArray<T>, py: Python) -> PyObject {
+    let owner = PyCapsule::new(py, array.clone(), Some(T::CAPSULE.to_owned()))
+        .expect("capsule can be created");
+    // SAFETY: the capsule holds a clone of `array`, so the elements stay where they are for as
+    // long as numpy uses them. Writing to another clone copies the elements first
+    let py_array = unsafe { PyArrayDyn::<T>::borrow_from_array(array, owner.into_any()) };
+    let _ = py_array
+        .getattr("flags")
+        .and_then(|flags| flags.setattr("writeable", false));
+    py_array.into_any().unbind()
+}
+
+/// Read a numpy array. Arrays python returns unchanged from `lend` are taken without copying.
+/// Others, which numpy owns, are copied, so python can keep and write to them.
+/// `None` if `py_object` isn't an array of `T`
+fn take<T: Shareable>(py_object: &Bound<PyAny>) -> Option<SharedArray<T>> {
+    let py_array = py_object.downcast::<PyArrayDyn<T>>().ok()?;
+    // SAFETY: the view is only read while the GIL is held
+    let view = unsafe { py_array.as_array() };
+    let lent = py_array
+        .getattr("base")
+        .ok()
+        .and_then(|base| base.downcast_into::<PyCapsule>().ok())
+        .filter(|owner| owner.name().ok().flatten() == Some(T::CAPSULE))
+        // SAFETY: capsules with this name always contain a `SharedArray<T>`
+        .map(|owner| unsafe { owner.reference::<SharedArray<T>>() }.clone())
+        .filter(|lent| {
+            lent.as_ptr() == view.as_ptr()
+                && lent.shape() == view.shape()
+                && lent.strides() == view.strides()
+        });
+    lent.or_else(|| Some(view.to_owned().into_shared()))
+}
+
+/// Read a numpy array of any dtype ports support
+fn take_any(py_object: &Bound<PyAny>) -> Option<PortData> {
+    None.or_else(|| take(py_object).map(PortData::ArrayReal))
+        .or_else(|| take(py_object).map(PortData::ArrayInteger))
+        .or_else(|| take(py_object).map(PortData::ArrayComplex))
+        .or_else(|| take(py_object).map(PortData::ArrayBool))
+        .or_else(|| take(py_object).map(PortData::ArrayUnsigned8))
+        .or_else(|| take(py_object).map(PortData::ArrayUnsigned16))
+        .or_else(|| take(py_object).map(PortData::ArrayInteger32))
+        .or_else(|| take(py_object).map(PortData::ArrayReal32))
+        .or_else(|| take(py_object).map(PortData::ArrayComplex32))
+}
+
+/// Read an array for a port of `port_type`, as `T` if numpy has it in that dtype. Arrays of other
+/// dtypes are accepted if they convert to the port's without losing values, e.g. uint8 for
+/// `ArrayReal`
+fn take_port<T: Shareable>(
+    port_type: &PortType,
+    py_object: &Bound<PyAny>,
+    variant: fn(SharedArray<T>) -> PortData,
+) -> Option<PortData> {
+    match take(py_object) {
+        Some(array) => Some(variant(array)),
+        None => take_any(py_object)?.convert_to(port_type),
+    }
+}
+
 impl PyNode {
     pub fn new(absolute_path: PathBuf, relative_path: RelativePathBuf) -> Self {
         //// Get necessary info
         let node_name = relative_path.file_stem().expect("node exists").to_string();
 
-        let node_src = match fs::read_to_string(&absolute_path)
-            .map_err(|e| NodeError::FileSys(e.to_string()))
-        {
-            Ok(src) => src,
-            Err(_) => {
-                let py_node = PyNode {
-                    name: node_name.to_string(),
-                    absolute_path,
-                    relative_path,
-                    ports: Err(NodeError::FileSys("Could not find src file".into())),
-                    parameters: Err(NodeError::FileSys("Could not find src file".into())),
-                };
-                log::error!("Failed to load node {node_name} {py_node:?}");
-                return py_node;
-            }
-        };
+        if !absolute_path.is_file() {
+            let py_node = PyNode {
+                name: node_name.to_string(),
+                absolute_path,
+                relative_path,
+                ports: Err(NodeError::FileSys("Could not find src file".into())),
+                parameters: Err(NodeError::FileSys("Could not find src file".into())),
+                timeout: None,
+                cacheable: false,
+            };
+            log::error!("Failed to load node {node_name} {py_node:?}");
+            return py_node;
+        }
         //// Call into python
         Python::with_gil(|py| {
-            //TODO: install this into the environment once, rather than every time
-            let gpi_module = c_str!(include_str!("../../python/gpi.py"));
-            PyModule::from_code(py, gpi_module, c_str!("gpi.py"), c_str!("gpi")).unwrap();
-
             trace!("Reading node config '{node_name}'");
 
-            let read_src = || -> Result<Bound<PyModule>, NodeError> {
-                PyModule::from_code(
-                    py,
-                    CString::new(node_src)
-                        .map_err(|e| {
-                            NodeError::Syntax(format!("Error parsing node '{node_name}'\n{e}"))
-                        })?
-                        .as_c_str(),
-                    CString::new(format!("{node_name}.py"))
-                        .map_err(|e| {
-                            NodeError::Syntax(format!("Error with node name {node_name}\n{e}"))
-                        })?
-                        .as_c_str(),
-                    CString::new(node_name.to_string())
-                        .map_err(|e| {
-                            NodeError::Syntax(format!("Error with node name {node_name}\n{e}"))
-                        })?
-                        .as_c_str(),
-                )
-                .map_err(|e| NodeError::Syntax(format!("Error in node '{node_name}' \n{e}")))
-            };
-
             //TODO Clean up error handling
-            match read_src() {
+            match module_cache::load(py, &absolute_path, &relative_path) {
                 Ok(module) => {
                     let config: Result<Bound<PyAny>, NodeError> = module
                         .getattr("config")
@@ -155,36 +378,37 @@ impl PyNode {
                             })
                         });
 
+                    let timeout = config
+                        .as_ref()
+                        .ok()
+                        .and_then(|c| c.getattr("timeout").ok()?.extract::<f64>().ok());
+                    let cacheable = config
+                        .as_ref()
+                        .ok()
+                        .and_then(|c| c.getattr("cacheable").ok()?.extract::<bool>().ok())
+                        .unwrap_or_default();
+
                     let ports = config.clone().and_then(|c| {
                         c.extract::<PortDef>()
                             .map_err(|e| NodeError::Output(e.to_string()))
                     });
 
                     let parameters = config
-                            .and_then(|c| {
-                                c.getattr("parameters").map_err(|_e| {
-                                    NodeError::Config(
-                                        "'parameters' attribute not found, does it exist for the node?"
-                                            .to_string(),
-                                    )
-                                })
+                        .and_then(|c| {
+                            c.getattr("parameters").map_err(|_e| {
+                                NodeError::Config(
+                                    "'parameters' attribute not found, does it exist for the node?"
+                                        .to_string(),
+                                )
                             })
-                            .and_then(|out_py| {
-                                out_py
-                                    .extract::<StableMap<String, String>>()
-                                    .map_err(|e| {
-                                        NodeError::Config(format!(
-                                        "Failed to interperet  {node_name}'s `config`: {e}, {out_py}"
-                                    ))
-                                    })?
-                                    .into_iter()
-                                    .map(|(k, v)| {
-                                        NodeUIWidget::from_str(&v)
-                                            .map(|v| (k, v))
-                                            .map_err(|_e| NodeError::Other)
-                                    })
-                                    .collect()
-                            });
+                        })
+                        .and_then(|out_py| {
+                            out_py.extract::<NodeUIParameters>().map_err(|e| {
+                                NodeError::Config(format!(
+                                    "Failed to interperet {node_name}'s parameters: {e}"
+                                ))
+                            })
+                        });
 
                     PyNode {
                         name: node_name.to_string(),
@@ -192,6 +416,8 @@ impl PyNode {
                         relative_path,
                         ports,
                         parameters,
+                        timeout,
+                        cacheable,
                     }
                 }
                 Err(e) => PyNode {
@@ -200,6 +426,8 @@ impl PyNode {
                     relative_path,
                     ports: Err(e.clone()),
                     parameters: Err(e),
+                    timeout: None,
+                    cacheable: false,
                 },
             }
         })
@@ -208,13 +436,25 @@ impl PyNode {
     pub fn compute(
         &self,
         inputs: StableMap<String, PortDataReference>,
+    ) -> Result<StableMap<String, PortData>, NodeError> {
+        let inputs = inputs.iter().map(|(k, v)| (k.clone(), &**v)).collect();
+        match (&self.ports, worker::pool()) {
+            (Ok(_), Some(pool)) => pool.compute(self, &inputs),
+            _ => self.compute_in_process(&inputs),
+        }
+    }
+
+    /// Compute the node in this process's python interpreter
+    pub fn compute_in_process(
+        &self,
+        inputs: &StableMap<String, &PortData>,
     ) -> Result<StableMap<String, PortData>, NodeError> {
         match &self.ports {
             Ok(_ports) => {
                 // Convert inputs to python arrays/objects
                 Python::with_gil(|py| {
                     let py_inputs = inputs
-                        .into_iter()
+                        .iter()
                         .map(|(k, v)| (k.clone(), v.to_py(py)))
                         .collect();
                     //TODO: refactor to not pass this data as params
@@ -241,27 +481,48 @@ impl PyNode {
         parameters: &NodeUIParameters,
         py: Python<'py>,
     ) -> Result<StableMap<String, PortData>, NodeError> {
-        if let Some(node_name) = node_path.file_stem() {
+        if node_path.file_stem().is_some() {
             //TODO: use self parameters, instead of taking unecessary inputs
-            //PERF: cache this in the PyNode
-            let node_src = fs::read_to_string(node_path.clone())
-                .map_err(|e| NodeError::FileSys(e.to_string()))?;
-            trace!("Running '{:?}' compute:\n{node_src}", node_path.file_stem());
-            //PERF: test if caching this is a big performance win
-            //This would be more of a pain to cache becaues of the associated python lifetime, but could
-            //potentially be worth it
-            //Update: It may be possible to package the py type without a lifetime? pyo3 docs
-
-            let node_module = PyModule::from_code(
-                py,
-                CString::new(node_src)
-                    .map_err(|e| NodeError::FileSys(e.to_string()))?
-                    .as_c_str(),
-                &CString::new(format!("{:?}.py", node_name))
-                    .expect("Node names should not contain invalid characters"),
-                c_str!("gpi_node"),
-            )
-            .map_err(|e| NodeError::Syntax(e.to_string()))?;
+            let node_module = module_cache::load(py, node_path, &self.relative_path)?;
+
+            let config = node_module
+                .getattr("config")
+                .and_then(|config| config.call0())
+                .ok();
+
+            //// Unconnected optional inputs use their default, if they have one
+            let mut inputs: StableMap<String, PyObject> = inputs
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone_ref(py)))
+                .collect();
+            if let Some(declared) = config.as_ref().and_then(|config| {
+                config
+                    .getattr("inputs")
+                    .and_then(|inputs| inputs.extract::<StableMap<String, Bound<PyAny>>>())
+                    .ok()
+            }) {
+                for (name, port) in declared {
+                    if inputs.contains_key(&name) {
+                        continue;
+                    }
+                    if let Ok(default) = port.getattr("default") {
+                        if !default.is_none() {
+                            inputs.insert(name, default.unbind());
+                        }
+                    }
+                }
+            }
+
+            //// Parameters must still be what the node declares
+            let parameters = match config.and_then(|config| {
+                config
+                    .getattr("parameters")
+                    .and_then(|parameters| parameters.extract::<NodeUIParameters>())
+                    .ok()
+            }) {
+                Some(declared) => check_parameters(parameters, declared)?,
+                None => parameters.clone(),
+            };
 
             //// COMPUTE
             let node_output = node_module
@@ -274,8 +535,9 @@ impl PyNode {
                         inputs.iter().collect::<StableMap<_, _>>(),
                         parameters
                             .iter()
-                            .map(|(k, v)| (k, v.to_string()))
-                            .collect::<StableMap<_, _>>(),
+                            .map(|(k, v)| Ok((k, v.value().to_py(py)?)))
+                            .collect::<PyResult<StableMap<_, _>>>()
+                            .map_err(|e| NodeError::Runtime(format!("Python Error:\n{e}")))?,
                     ),
                     None,
                 )
@@ -319,82 +581,85 @@ impl PyNode {
     ) -> Result<PortData, NodeError> {
         // unsure how to make the repetion bellow more generic, while still
         // automatically converting to the PortType
-        unsafe {
-            Ok(match port_type {
-                PortType::Integer => PortData::Integer(
-                    py_object
-                        .bind(py)
-                        .extract()
-                        .map_err(|_e| output_error(port_type, py_object))?,
-                ),
-                PortType::Real => PortData::Real(
-                    py_object
-                        .bind(py)
-                        .extract()
-                        .map_err(|_e| output_error(port_type, py_object))?,
-                ),
-                PortType::Complex => PortData::Complex(
-                    py_object
-                        .bind(py)
-                        .extract::<(f64, f64)>()
-                        .map(|(r, i)| Complex64::new(r, i))
-                        .map_err(|_e| output_error(port_type, py_object))?,
-                ),
-                PortType::ArrayReal => PortData::ArrayReal(
-                    py_object
-                        .bind(py)
-                        .downcast()
-                        .map_err(|_e| output_error(port_type, py_object))?
-                        .as_array()
-                        .to_owned(),
-                ),
-                PortType::ArrayInteger => PortData::ArrayInteger(
-                    py_object
-                        .bind(py)
-                        .downcast()
-                        .map_err(|_e| output_error(port_type, py_object))?
-                        .as_array()
-                        .to_owned(),
-                ),
-                PortType::ArrayComplex => PortData::ArrayComplex(
-                    py_object
-                        .bind(py)
-                        .downcast()
-                        .map_err(|_e| output_error(port_type, py_object))?
-                        .as_array()
-                        .to_owned(),
-                ),
-                PortType::Dynamic => PortData::Dynamic(
-                    py_object
-                        .bind(py)
-                        .downcast()
-                        .map_err(|_e| output_error(port_type, py_object))?
-                        .as_array()
-                        .to_owned(),
-                ),
-                PortType::Object(types) => {
-                    let dict: &Bound<PyDict> = py_object
-                        .bind(py)
-                        .downcast()
-                        .map_err(|_e| output_error(port_type, py_object))?;
-                    let rust_dict: StableMap<String, PortData> = dict
-                        .iter()
-                        .map(|(k, v)| {
-                            let key = k.extract::<String>().map_err(|e| {
-                                NodeError::Output(format!("Failed to read output port name {e}"))
-                            });
-                            key.map(|k| {
-                                (
-                                    k.clone(),
-                                    Self::extract_py_data(&types[&k], &v.into(), py).unwrap(),
-                                )
-                            })
+        Ok(match port_type {
+            PortType::Integer => PortData::Integer(
+                py_object
+                    .bind(py)
+                    .extract()
+                    .map_err(|_e| output_error(port_type, py_object))?,
+            ),
+            PortType::Real => PortData::Real(
+                py_object
+                    .bind(py)
+                    .extract()
+                    .map_err(|_e| output_error(port_type, py_object))?,
+            ),
+            PortType::Complex => PortData::Complex(
+                py_object
+                    .bind(py)
+                    .extract::<(f64, f64)>()
+                    .map(|(r, i)| Complex64::new(r, i))
+                    .map_err(|_e| output_error(port_type, py_object))?,
+            ),
+            PortType::ArrayReal => take_port(port_type, py_object.bind(py), PortData::ArrayReal)
+                .ok_or_else(|| output_error(port_type, py_object))?,
+            PortType::ArrayInteger => {
+                take_port(port_type, py_object.bind(py), PortData::ArrayInteger)
+                    .ok_or_else(|| output_error(port_type, py_object))?
+            }
+            PortType::ArrayComplex => {
+                take_port(port_type, py_object.bind(py), PortData::ArrayComplex)
+                    .ok_or_else(|| output_error(port_type, py_object))?
+            }
+            PortType::ArrayBool => take_port(port_type, py_object.bind(py), PortData::ArrayBool)
+                .ok_or_else(|| output_error(port_type, py_object))?,
+            PortType::ArrayUnsigned8 => {
+                take_port(port_type, py_object.bind(py), PortData::ArrayUnsigned8)
+                    .ok_or_else(|| output_error(port_type, py_object))?
+            }
+            PortType::ArrayUnsigned16 => {
+                take_port(port_type, py_object.bind(py), PortData::ArrayUnsigned16)
+                    .ok_or_else(|| output_error(port_type, py_object))?
+            }
+            PortType::ArrayInteger32 => {
+                take_port(port_type, py_object.bind(py), PortData::ArrayInteger32)
+                    .ok_or_else(|| output_error(port_type, py_object))?
+            }
+            PortType::ArrayReal32 => {
+                take_port(port_type, py_object.bind(py), PortData::ArrayReal32)
+                    .ok_or_else(|| output_error(port_type, py_object))?
+            }
+            PortType::ArrayComplex32 => {
+                take_port(port_type, py_object.bind(py), PortData::ArrayComplex32)
+                    .ok_or_else(|| output_error(port_type, py_object))?
+            }
+            // Arrays of any dtype, f64 ones are kept as `Dynamic`
+            PortType::Dynamic => take(py_object.bind(py))
+                .map(PortData::Dynamic)
+                .or_else(|| take_any(py_object.bind(py)))
+                .ok_or_else(|| output_error(port_type, py_object))?,
+            PortType::Object(types) => {
+                let dict: &Bound<PyDict> = py_object
+                    .bind(py)
+                    .downcast()
+                    .map_err(|_e| output_error(port_type, py_object))?;
+                let rust_dict: StableMap<String, PortData> = dict
+                    .iter()
+                    .map(|(k, v)| {
+                        let key = k.extract::<String>().map_err(|e| {
+                            NodeError::Output(format!("Failed to read output port name {e}"))
+                        });
+                        key.map(|k| {
+                            (
+                                k.clone(),
+                                Self::extract_py_data(&types[&k], &v.into(), py).unwrap(),
+                            )
                         })
-                        .collect::<Result<_, _>>()?;
-                    PortData::Object(rust_dict)
-                }
-            })
-        }
+                    })
+                    .collect::<Result<_, _>>()?;
+                PortData::Object(rust_dict)
+            }
+        })
     }
 
     pub(crate) fn config_view(
@@ -403,21 +668,7 @@ impl PyNode {
         _input_data: StableMap<String, std::sync::Arc<std::sync::RwLock<PortData>>>,
     ) -> Option<Element<'_, Message>> {
         if let Ok(parameters) = &self.parameters {
-            Some(
-                column(parameters.iter().map(|(name, widget_type)| {
-                    let message = move |widget_value| {
-                        Message::UpdateNodeParameter(id, name.to_string(), widget_value)
-                    };
-                    row![text(name), widget_type.view(message)]
-                        .spacing(8.0)
-                        .align_y(Center)
-                        .width(Fill)
-                        .into()
-                }))
-                .spacing(8.)
-                .width(Fill)
-                .into(),
-            )
+            Some(parameters_view(id, parameters))
         } else {
             Some(text("").into())
         }
@@ -452,8 +703,151 @@ impl TryFrom<PyFacingNodeDef> for NodeUIParameters {
     }
 }
 
+/// The parameters a node is computed with: the values set in the network, which must have the
+/// type the node now declares, and the declared defaults of any added since
+fn check_parameters(
+    set: &NodeUIParameters,
+    declared: NodeUIParameters,
+) -> Result<NodeUIParameters, NodeError> {
+    declared
+        .into_iter()
+        .map(|(name, declared)| match set.get(&name) {
+            Some(widget) if widget.value().type_name() == declared.value().type_name() => {
+                Ok((name, widget.clone()))
+            }
+            Some(widget) => Err(NodeError::Config(format!(
+                "Parameter '{name}' is declared as a {} ({}), but the network has a {} ({}). \
+                 Reload the node to use the new declaration",
+                declared.kind(),
+                declared.value().type_name(),
+                widget.kind(),
+                widget.value().type_name(),
+            ))),
+            None => Ok((name, declared)),
+        })
+        .collect()
+}
+
 fn output_error(port_type: &PortType, py_object: &PyObject) -> NodeError {
     NodeError::Output(format!(
         "Received unexpected output from node. Expected one of {port_type:#?}, found {py_object:#?}"
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use ndarray::ArrayD;
+    use pyo3::types::PyTypeMethods;
+
+    use super::*;
+
+    #[test]
+    fn parameters() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let locals = PyDict::new(py);
+            locals
+                .set_item("ui", module_cache::gpi(py).unwrap().getattr("ui").unwrap())
+                .unwrap();
+            let declare = |code: &str| {
+                py.eval(&std::ffi::CString::new(code).unwrap(), None, Some(&locals))
+                    .and_then(|widget| widget.extract::<NodeUIWidget>())
+            };
+            let parameters = NodeUIParameters::from([
+                (
+                    "n".into(),
+                    declare("ui.spinner(default=300, max=200)").unwrap(),
+                ),
+                (
+                    "f".into(),
+                    declare("ui.file('data/scan.npy', ['npy'])").unwrap(),
+                ),
+                (
+                    "mode".into(),
+                    declare("ui.dropdown(['fast', 'exact'])").unwrap(),
+                ),
+                ("d".into(), declare("ui.Slider").unwrap()),
+            ]);
+            // Defaults are kept within their limits
+            assert!(matches!(
+                parameters["n"],
+                NodeUIWidget::Spinner { value: 200, .. }
+            ));
+            assert!(declare("ui.dropdown(['a'], default='b')").is_err());
+            assert!(declare("'Dial'").is_err());
+
+            let type_name = |name: &str| {
+                let value = parameters[name].value().to_py(py).unwrap();
+                value.bind(py).get_type().name().unwrap().to_string()
+            };
+            assert_eq!(type_name("n"), "int");
+            assert_eq!(type_name("mode"), "str");
+            assert_eq!(type_name("d"), "float");
+            assert!(type_name("f").ends_with("Path"));
+
+            // Values set in the network must still have the declared type
+            let declared = NodeUIParameters::from([
+                ("n".into(), declare("ui.slider()").unwrap()),
+                ("added".into(), declare("ui.Text").unwrap()),
+            ]);
+            assert!(matches!(
+                check_parameters(&parameters, declared),
+                Err(NodeError::Config(e)) if e.contains("'n'")
+            ));
+            let declared = NodeUIParameters::from([
+                ("n".into(), declare("ui.spinner()").unwrap()),
+                ("added".into(), declare("ui.Text").unwrap()),
+            ]);
+            let checked = check_parameters(&parameters, declared).unwrap();
+            assert_eq!(checked["n"], parameters["n"]);
+            assert_eq!(
+                checked["added"].value(),
+                ParameterValue::Text(String::new())
+            );
+        });
+    }
+
+    #[test]
+    fn take_lent_arrays_without_copying() {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let Ok(np) = py.import("numpy") else {
+                log::warn!("numpy isn't installed, skipping");
+                return;
+            };
+            let address = |array: &Bound<PyAny>| -> usize {
+                array
+                    .getattr("__array_interface__")
+                    .and_then(|interface| interface.get_item("data")?.get_item(0)?.extract())
+                    .unwrap()
+            };
+
+            // Lent arrays python returns unchanged share their elements
+            let shared = ArrayD::from_shape_vec(vec![2, 3], (0..6).map(f64::from).collect())
+                .unwrap()
+                .into_shared();
+            let lent = lend(&shared, py);
+            let lent = lent.bind(py);
+            assert_eq!(address(lent), shared.as_ptr() as usize);
+            assert_eq!(take::<f64>(lent).unwrap().as_ptr(), shared.as_ptr());
+
+            // Fresh arrays stay numpy's, and python can still write to them
+            let fresh = np
+                .call_method1("arange", (6.,))
+                .and_then(|a| a.call_method1("reshape", (2, 3)))
+                .unwrap();
+            let taken = take::<f64>(&fresh).unwrap();
+            assert_ne!(taken.as_ptr() as usize, address(&fresh));
+            assert_eq!(taken, shared);
+            fresh.call_method1("fill", (1.,)).unwrap();
+            assert_eq!(taken.sum(), 15.);
+
+            // Views of another array are copied
+            let view = fresh.get_item(0).unwrap();
+            assert_ne!(
+                take::<f64>(&view).unwrap().as_ptr() as usize,
+                address(&view)
+            );
+        });
+    }
+}
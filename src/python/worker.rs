@@ -0,0 +1,287 @@
+//! Running python nodes in worker processes
+//!
+//! When `[python] workers` is set in config.toml, python nodes are computed in a pool of
+//! subprocesses instead of the app's interpreter. Workers are this executable started as
+//! `foray worker <address> <token>`, and exchange [`protocol`] messages with the app over a local
+//! socket. Nodes in different workers run in parallel, a crashing extension only takes down its
+//! worker, and a worker that runs past the node's timeout is killed. Stopped workers are started
+//! again when next needed.
+
+pub mod protocol;
+
+use std::{
+    env,
+    hash::{BuildHasher, Hasher, RandomState},
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    process::{Child, Command, ExitCode},
+    sync::{Condvar, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, trace};
+use pyo3::Python;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    nodes::{port::PortData, status::NodeError},
+    python::{module_cache, py_node::PyNode},
+    StableMap,
+};
+
+/// How long a new worker has to connect back to the app
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Python worker settings, read from the `[python]` table of config.toml
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WorkerConfig {
+    /// Number of worker processes python nodes run in. 0 runs them in the app's process
+    pub workers: usize,
+    /// Seconds a node may run for before its worker is stopped, unless the node's config sets a
+    /// `timeout`. 0 for no limit
+    pub timeout: f64,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            workers: 0,
+            timeout: 60.,
+        }
+    }
+}
+
+static POOL: OnceLock<WorkerPool> = OnceLock::new();
+
+/// Run python nodes in workers from now on, if `config` asks for any
+pub fn start(config: &WorkerConfig) {
+    if config.workers > 0 {
+        info!("Running python nodes in {} workers", config.workers);
+        let _ = POOL.set(WorkerPool::new(config.clone()));
+    }
+}
+
+/// The worker pool python nodes run in, `None` if they run in the app's process
+pub fn pool() -> Option<&'static WorkerPool> {
+    POOL.get()
+}
+
+pub struct WorkerPool {
+    config: WorkerConfig,
+    /// Workers that aren't running a node, `None` for those that haven't been started yet
+    idle: Mutex<Vec<Option<Worker>>>,
+    returned: Condvar,
+}
+
+impl WorkerPool {
+    fn new(config: WorkerConfig) -> Self {
+        Self {
+            idle: Mutex::new((0..config.workers).map(|_| None).collect()),
+            returned: Condvar::new(),
+            config,
+        }
+    }
+
+    /// Compute `node` in the next free worker, waiting for one if they're all busy
+    pub fn compute(
+        &self,
+        node: &PyNode,
+        inputs: &StableMap<String, &PortData>,
+    ) -> Result<StableMap<String, PortData>, NodeError> {
+        let worker = {
+            let mut idle = self.idle.lock().expect("worker pool is not poisoned");
+            loop {
+                match idle.pop() {
+                    Some(worker) => break worker,
+                    None => {
+                        idle = self
+                            .returned
+                            .wait(idle)
+                            .expect("worker pool is not poisoned")
+                    }
+                }
+            }
+        };
+        let mut worker = match worker.map_or_else(Worker::spawn, Ok) {
+            Ok(worker) => worker,
+            Err(e) => {
+                self.give_back(None);
+                return Err(NodeError::Runtime(format!(
+                    "Could not start python worker: {e}"
+                )));
+            }
+        };
+
+        let seconds = node.timeout.unwrap_or(self.config.timeout);
+        let timeout = Duration::try_from_secs_f64(seconds)
+            .ok()
+            .filter(|t| !t.is_zero());
+        match worker.compute(node, inputs, timeout) {
+            Ok(outputs) => {
+                self.give_back(Some(worker));
+                outputs
+            }
+            Err(e) => {
+                let reason = match e.kind() {
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut => format!(
+                        "'{}' did not finish within {seconds}s, its worker was stopped",
+                        node.name
+                    ),
+                    _ => format!(
+                        "Python worker stopped while computing '{}' ({})",
+                        node.name,
+                        worker.stop()
+                    ),
+                };
+                self.give_back(None);
+                Err(NodeError::Runtime(reason))
+            }
+        }
+    }
+
+    fn give_back(&self, worker: Option<Worker>) {
+        self.idle
+            .lock()
+            .expect("worker pool is not poisoned")
+            .push(worker);
+        self.returned.notify_one();
+    }
+}
+
+/// A worker process, and the socket connected to it. Dropping it stops the process
+struct Worker {
+    child: Child,
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl Worker {
+    fn spawn() -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        // Only accept the process we start, not anything else that finds the port
+        let token = RandomState::new().build_hasher().finish();
+        let mut child = Command::new(env::current_exe()?)
+            .arg("worker")
+            .arg(listener.local_addr()?.to_string())
+            .arg(token.to_string())
+            .spawn()?;
+        trace!("Started python worker {}", child.id());
+
+        match Self::accept(&listener, &mut child, token) {
+            Ok(stream) => Ok(Worker {
+                child,
+                reader: BufReader::new(stream.try_clone()?),
+                writer: BufWriter::new(stream),
+            }),
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(e)
+            }
+        }
+    }
+
+    fn accept(listener: &TcpListener, child: &mut Child, token: u64) -> io::Result<TcpStream> {
+        listener.set_nonblocking(true)?;
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            match listener.accept() {
+                Ok((mut stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+                    let mut received = [0; 8];
+                    if stream.read_exact(&mut received).is_ok()
+                        && u64::from_le_bytes(received) == token
+                    {
+                        stream.set_read_timeout(None)?;
+                        stream.set_nodelay(true)?;
+                        return Ok(stream);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if let Some(status) = child.try_wait()? {
+                        return Err(io::Error::other(format!("worker exited with {status}")));
+                    }
+                    if Instant::now() > deadline {
+                        return Err(io::Error::new(
+                            ErrorKind::TimedOut,
+                            "worker did not connect",
+                        ));
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Send `node` to the worker and wait for its outputs. An `Err` means the worker can't be
+    /// used again, errors from the node itself are in the `Ok`
+    fn compute(
+        &mut self,
+        node: &PyNode,
+        inputs: &StableMap<String, &PortData>,
+        timeout: Option<Duration>,
+    ) -> io::Result<protocol::Outputs> {
+        self.writer.get_ref().set_write_timeout(timeout)?;
+        self.reader.get_ref().set_read_timeout(timeout)?;
+        protocol::write_request(&mut self.writer, node, inputs)?;
+        protocol::read_response(&mut self.reader)
+    }
+
+    /// Stop the process, describing how it ended
+    fn stop(&mut self) -> String {
+        // It may have already exited, in which case its status is kept
+        let _ = self.child.kill();
+        match self.child.wait() {
+            Ok(status) => status.to_string(),
+            Err(e) => e.to_string(),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Entry point of a worker process: compute nodes sent by the app at `address` until it
+/// disconnects
+pub fn serve(address: &str, token: u64) -> ExitCode {
+    pyo3::prepare_freethreaded_python();
+    let connect = || -> io::Result<(BufReader<TcpStream>, BufWriter<TcpStream>)> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        stream.write_all(&token.to_le_bytes())?;
+        Ok((BufReader::new(stream.try_clone()?), BufWriter::new(stream)))
+    };
+    let (mut reader, mut writer) = match connect() {
+        Ok(streams) => streams,
+        Err(e) => {
+            error!("Python worker could not connect to {address}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    loop {
+        let (node, inputs) = match protocol::read_request(&mut reader) {
+            Ok(request) => request,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return ExitCode::SUCCESS,
+            Err(e) => {
+                error!("Python worker received an invalid request: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        // Node files may have changed since the last request
+        Python::with_gil(module_cache::refresh);
+        let inputs = inputs.iter().map(|(k, v)| (k.clone(), v)).collect();
+        let outputs = node.compute_in_process(&inputs);
+        if let Err(e) = protocol::write_response(&mut writer, &outputs) {
+            error!("Python worker could not reply: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Messages between the app and its python workers
+//!
+//! Values are written little endian. Strings are their byte length followed by their bytes, and
+//! arrays are their shape followed by their elements in standard (row major) order, so large
+//! arrays are copied once into the socket rather than formatted.
+
+use std::{
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+use ndarray::{ArrayD, IxDyn};
+use numpy::{Complex32, Complex64};
+
+use crate::{
+    nodes::{
+        port::{PortData, SharedArray},
+        status::NodeError,
+    },
+    python::py_node::PyNode,
+    StableMap,
+};
+
+pub type Outputs = Result<StableMap<String, PortData>, NodeError>;
+
+pub fn write_request(
+    w: &mut impl Write,
+    node: &PyNode,
+    inputs: &StableMap<String, &PortData>,
+) -> io::Result<()> {
+    // The absolute path isn't serialized with the node
+    write_str(w, &to_ron(&(&node.absolute_path, node))?)?;
+    write_len(w, inputs.len())?;
+    for (name, data) in inputs {
+        write_str(w, name)?;
+        write_data(w, data)?;
+    }
+    w.flush()
+}
+
+pub fn read_request(r: &mut impl Read) -> io::Result<(PyNode, StableMap<String, PortData>)> {
+    let (absolute_path, mut node): (PathBuf, PyNode) = from_ron(&read_str(r)?)?;
+    node.absolute_path = absolute_path;
+    let inputs = (0..read_len(r)?)
+        .map(|_| Ok((read_str(r)?, read_data(r)?)))
+        .collect::<io::Result<_>>()?;
+    Ok((node, inputs))
+}
+
+pub fn write_response(w: &mut impl Write, outputs: &Outputs) -> io::Result<()> {
+    match outputs {
+        Ok(outputs) => {
+            w.write_all(&[0])?;
+            write_len(w, outputs.len())?;
+            for (name, data) in outputs {
+                write_str(w, name)?;
+                write_data(w, data)?;
+            }
+        }
+        Err(e) => {
+            w.write_all(&[1])?;
+            write_str(w, &to_ron(e)?)?;
+        }
+    }
+    w.flush()
+}
+
+pub fn read_response(r: &mut impl Read) -> io::Result<Outputs> {
+    Ok(match read_u8(r)? {
+        0 => Ok((0..read_len(r)?)
+            .map(|_| Ok((read_str(r)?, read_data(r)?)))
+            .collect::<io::Result<_>>()?),
+        _ => Err(from_ron(&read_str(r)?)?),
+    })
+}
+
+fn write_data(w: &mut impl Write, data: &PortData) -> io::Result<()> {
+    match data {
+        PortData::Integer(i) => {
+            w.write_all(&[0])?;
+            i.write(w)
+        }
+        PortData::Real(r) => {
+            w.write_all(&[1])?;
+            r.write(w)
+        }
+        PortData::Complex(c) => {
+            w.write_all(&[2])?;
+            c.write(w)
+        }
+        PortData::ArrayInteger(a) => {
+            w.write_all(&[3])?;
+            write_array(w, a)
+        }
+        PortData::ArrayReal(a) => {
+            w.write_all(&[4])?;
+            write_array(w, a)
+        }
+        PortData::ArrayComplex(a) => {
+            w.write_all(&[5])?;
+            write_array(w, a)
+        }
+        PortData::Dynamic(a) => {
+            w.write_all(&[6])?;
+            write_array(w, a)
+        }
+        PortData::Object(fields) => {
+            w.write_all(&[7])?;
+            write_len(w, fields.len())?;
+            fields.iter().try_for_each(|(name, data)| {
+                write_str(w, name)?;
+                write_data(w, data)
+            })
+        }
+        PortData::ArrayBool(a) => {
+            w.write_all(&[8])?;
+            write_array(w, a)
+        }
+        PortData::ArrayUnsigned8(a) => {
+            w.write_all(&[9])?;
+            write_array(w, a)
+        }
+        PortData::ArrayUnsigned16(a) => {
+            w.write_all(&[10])?;
+            write_array(w, a)
+        }
+        PortData::ArrayInteger32(a) => {
+            w.write_all(&[11])?;
+            write_array(w, a)
+        }
+        PortData::ArrayReal32(a) => {
+            w.write_all(&[12])?;
+            write_array(w, a)
+        }
+        PortData::ArrayComplex32(a) => {
+            w.write_all(&[13])?;
+            write_array(w, a)
+        }
+    }
+}
+
+fn read_data(r: &mut impl Read) -> io::Result<PortData> {
+    Ok(match read_u8(r)? {
+        0 => PortData::Integer(i64::read(r)?),
+        1 => PortData::Real(f64::read(r)?),
+        2 => PortData::Complex(Complex64::read(r)?),
+        3 => PortData::ArrayInteger(read_array(r)?),
+        4 => PortData::ArrayReal(read_array(r)?),
+        5 => PortData::ArrayComplex(read_array(r)?),
+        6 => PortData::Dynamic(read_array(r)?),
+        7 => PortData::Object(
+            (0..read_len(r)?)
+                .map(|_| Ok((read_str(r)?, read_data(r)?)))
+                .collect::<io::Result<_>>()?,
+        ),
+        8 => PortData::ArrayBool(read_array(r)?),
+        9 => PortData::ArrayUnsigned8(read_array(r)?),
+        10 => PortData::ArrayUnsigned16(read_array(r)?),
+        11 => PortData::ArrayInteger32(read_array(r)?),
+        12 => PortData::ArrayReal32(read_array(r)?),
+        13 => PortData::ArrayComplex32(read_array(r)?),
+        tag => return Err(invalid(format!("unknown port data {tag}"))),
+    })
+}
+
+/// Array element types
+trait Element: Sized {
+    const SIZE: usize;
+    fn write(&self, w: &mut impl Write) -> io::Result<()>;
+    fn from_bytes(bytes: &[u8]) -> Self;
+
+    fn read(r: &mut impl Read) -> io::Result<Self> {
+        let mut bytes = vec![0; Self::SIZE];
+        r.read_exact(&mut bytes)?;
+        Ok(Self::from_bytes(&bytes))
+    }
+}
+
+impl Element for i64 {
+    const SIZE: usize = 8;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        i64::from_le_bytes(bytes.try_into().expect("element size"))
+    }
+}
+
+impl Element for f64 {
+    const SIZE: usize = 8;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        f64::from_le_bytes(bytes.try_into().expect("element size"))
+    }
+}
+
+impl Element for Complex64 {
+    const SIZE: usize = 16;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        self.re.write(w)?;
+        self.im.write(w)
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Complex64::new(f64::from_bytes(&bytes[..8]), f64::from_bytes(&bytes[8..]))
+    }
+}
+
+impl Element for bool {
+    const SIZE: usize = 1;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&[u8::from(*self)])
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+}
+
+impl Element for u8 {
+    const SIZE: usize = 1;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        u8::from_le_bytes(bytes.try_into().expect("element size"))
+    }
+}
+
+impl Element for u16 {
+    const SIZE: usize = 2;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        u16::from_le_bytes(bytes.try_into().expect("element size"))
+    }
+}
+
+impl Element for i32 {
+    const SIZE: usize = 4;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        i32::from_le_bytes(bytes.try_into().expect("element size"))
+    }
+}
+
+impl Element for f32 {
+    const SIZE: usize = 4;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        f32::from_le_bytes(bytes.try_into().expect("element size"))
+    }
+}
+
+impl Element for Complex32 {
+    const SIZE: usize = 8;
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        self.re.write(w)?;
+        self.im.write(w)
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Complex32::new(f32::from_bytes(&bytes[..4]), f32::from_bytes(&bytes[4..]))
+    }
+}
+
+fn write_array<T: Element>(w: &mut impl Write, array: &SharedArray<T>) -> io::Result<()> {
+    write_len(w, array.ndim())?;
+    array
+        .shape()
+        .iter()
+        .try_for_each(|&len| write_len(w, len))?;
+    array.iter().try_for_each(|element| element.write(w))
+}
+
+fn read_array<T: Element + Clone>(r: &mut impl Read) -> io::Result<SharedArray<T>> {
+    let shape = (0..read_len(r)?)
+        .map(|_| read_len(r))
+        .collect::<io::Result<Vec<_>>>()?;
+    let bytes = shape
+        .iter()
+        .try_fold(T::SIZE, |size, &len| size.checked_mul(len))
+        .ok_or_else(|| invalid(format!("array of shape {shape:?} is too large")))?;
+    let mut buffer = vec![0; bytes];
+    r.read_exact(&mut buffer)?;
+    let elements = buffer.chunks_exact(T::SIZE).map(T::from_bytes).collect();
+    ArrayD::from_shape_vec(IxDyn(&shape), elements)
+        .map(ArrayD::into_shared)
+        .map_err(|e| invalid(e.to_string()))
+}
+
+fn write_len(w: &mut impl Write, len: usize) -> io::Result<()> {
+    w.write_all(&(len as u64).to_le_bytes())
+}
+
+fn read_len(r: &mut impl Read) -> io::Result<usize> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    usize::try_from(u64::from_le_bytes(bytes)).map_err(|e| invalid(e.to_string()))
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0];
+    r.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_len(w, s.len())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_str(r: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_len(r)?];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))
+}
+
+fn to_ron(value: &impl serde::Serialize) -> io::Result<String> {
+    ron::to_string(value).map_err(|e| invalid(e.to_string()))
+}
+
+fn from_ron<T: serde::de::DeserializeOwned>(s: &str) -> io::Result<T> {
+    ron::from_str(s).map_err(|e| invalid(e.to_string()))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::array;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let node = PyNode {
+            name: "fft".into(),
+            absolute_path: "/nodes/core/fft.py".into(),
+            relative_path: "core/fft.py".into(),
+            ports: Err(NodeError::Other),
+            parameters: Ok(Default::default()),
+            timeout: Some(2.5),
+            cacheable: false,
+        };
+        let complex = array![[Complex64::new(1., -1.)], [Complex64::new(0., 2.)]].into_dyn();
+        let object = PortData::Object(
+            [
+                ("n".to_string(), PortData::Integer(-3)),
+                ("x".to_string(), PortData::Real(0.5)),
+            ]
+            .into(),
+        );
+        let array = PortData::ArrayComplex(complex.clone().into_shared());
+        let inputs = StableMap::from([("a".to_string(), &array), ("b".to_string(), &object)]);
+
+        let mut buffer = vec![];
+        write_request(&mut buffer, &node, &inputs).unwrap();
+        let (read_node, read_inputs) = read_request(&mut buffer.as_slice()).unwrap();
+        assert_eq!(read_node, node);
+        assert_eq!(read_node.absolute_path, node.absolute_path);
+        assert!(matches!(&read_inputs["a"], PortData::ArrayComplex(a) if *a == complex));
+        assert!(matches!(
+            &read_inputs["b"],
+            PortData::Object(fields) if matches!(fields["n"], PortData::Integer(-3))
+        ));
+
+        let mut buffer = vec![];
+        write_response(&mut buffer, &Err(NodeError::Runtime("Python Error".into()))).unwrap();
+        assert!(matches!(
+            read_response(&mut buffer.as_slice()).unwrap(),
+            Err(NodeError::Runtime(e)) if e == "Python Error"
+        ));
+        // A transposed array is sent in its logical order
+        let transposed = array![[1., 2., 3.], [4., 5., 6.]]
+            .reversed_axes()
+            .into_dyn();
+        let mut buffer = vec![];
+        let outputs = Ok(StableMap::from([(
+            "out".to_string(),
+            PortData::ArrayReal(transposed.clone().into_shared()),
+        )]));
+        write_response(&mut buffer, &outputs).unwrap();
+        let read = read_response(&mut buffer.as_slice()).unwrap().unwrap();
+        assert!(matches!(&read["out"], PortData::ArrayReal(a) if *a == transposed));
+
+        let image = array![[0u8, 255], [3, 4]].into_dyn();
+        let mask = array![true, false].into_dyn();
+        let mut buffer = vec![];
+        let outputs = Ok(StableMap::from([
+            (
+                "image".to_string(),
+                PortData::ArrayUnsigned8(image.clone().into_shared()),
+            ),
+            (
+                "mask".to_string(),
+                PortData::ArrayBool(mask.clone().into_shared()),
+            ),
+        ]));
+        write_response(&mut buffer, &outputs).unwrap();
+        let read = read_response(&mut buffer.as_slice()).unwrap().unwrap();
+        assert!(matches!(&read["image"], PortData::ArrayUnsigned8(a) if *a == image));
+        assert!(matches!(&read["mask"], PortData::ArrayBool(a) if *a == mask));
+    }
+}